@@ -7,6 +7,20 @@ fn is_integer(v: String) -> Result<(), String> {
     Err(format!("{} is not an integer", v))
 }
 
+fn is_published(v: String) -> Result<(), String> {
+    match v.split_once('=') {
+        Some((name, version)) if !name.is_empty() && version.parse::<u32>().is_ok() => Ok(()),
+        _ => Err(format!("{} is not of the form name=version", v)),
+    }
+}
+
+fn is_checkout(v: String) -> Result<(), String> {
+    match v.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => Ok(()),
+        _ => Err(format!("{} is not of the form name=path", v)),
+    }
+}
+
 /// lal clap app
 pub fn new<'a>() -> App<'a, 'a> {
     #[rustfmt::skip]
@@ -31,12 +45,31 @@ pub fn new<'a>() -> App<'a, 'a> {
             .short("d")
             .long("debug")
             .help("Adds line numbers to log statements"))
+        .arg(Arg::with_name("offline")
+            .long("offline")
+            .help("Only use components already in the cache"))
+        .arg(Arg::with_name("output")
+            .long("output")
+            .takes_value(true)
+            .global(true)
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .help("Print results and errors as text or json"))
         .subcommand(SubCommand::with_name("fetch")
             .about("Fetch dependencies listed in the manifest into INPUT")
             .arg(Arg::with_name("core")
                 .long("core")
                 .short("c")
-                .help("Only fetch core dependencies")))
+                .help("Only fetch core dependencies"))
+            .arg(Arg::with_name("locked")
+                .long("locked")
+                .help("Only fetch exactly what is resolved in .lal/lock.json"))
+            .arg(Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .validator(is_integer)
+                .help("Number of components to fetch concurrently (defaults to jobs in config)")))
         .subcommand(SubCommand::with_name("build")
             .about("Runs BUILD script in current directory in the configured container")
             .arg(Arg::with_name("component")
@@ -82,6 +115,31 @@ pub fn new<'a>() -> App<'a, 'a> {
                 .multiple(true)
                 .takes_value(true)
                 .number_of_values(1))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .validator(is_integer)
+                .help("Stop after this many seconds"))
+            .arg(Arg::with_name("all")
+                .long("all")
+                .conflicts_with_all(&["component", "configuration", "release"])
+                .help("Build every component in the manifest"))
+            .arg(Arg::with_name("configs")
+                .long("configs")
+                .takes_value(true)
+                .possible_values(&["default", "all"])
+                .requires("all")
+                .help("Build the default or all configurations of every component"))
+            .arg(Arg::with_name("matrix")
+                .long("matrix")
+                .conflicts_with_all(&["all", "print"])
+                .help("Build in every supported environment, each with its own INPUT"))
+            .arg(Arg::with_name("keep-going")
+                .long("keep-going")
+                .help("Continue with the other builds of --all or --matrix after a failure"))
+            .arg(Arg::with_name("no-cache")
+                .long("no-cache")
+                .help("Run the build even if the build cache has its OUTPUT"))
             .arg(Arg::with_name("print")
                 .long("print-only")
                 .conflicts_with("release")
@@ -101,7 +159,13 @@ pub fn new<'a>() -> App<'a, 'a> {
                 .short("D")
                 .long("save-dev")
                 .conflicts_with("save")
-                .help("Save updated versions in devDependencies in the manifest")))
+                .help("Save updated versions in devDependencies in the manifest"))
+            .arg(Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .validator(is_integer)
+                .help("Number of components to fetch concurrently (defaults to jobs in config)")))
         .subcommand(SubCommand::with_name("verify")
             .arg(Arg::with_name("simple")
                 .short("s")
@@ -123,6 +187,14 @@ pub fn new<'a>() -> App<'a, 'a> {
                 .long("origin")
                 .help("Print version and environment origin of artifact"))
             .about("Prints current dependencies and their status"))
+        .subcommand(SubCommand::with_name("graph")
+            .about("Prints the full dependency graph with versions and conflicts")
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["dot", "json", "mermaid"])
+                .default_value("dot")
+                .help("Output format of the graph")))
         .subcommand(SubCommand::with_name("shell")
             .about("Enters the configured container mounting the current directory")
             .alias("sh")
@@ -144,6 +216,11 @@ pub fn new<'a>() -> App<'a, 'a> {
                 .multiple(true)
                 .takes_value(true)
                 .number_of_values(1))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .validator(is_integer)
+                .help("Stop after this many seconds"))
             .arg(Arg::with_name("print")
                 .long("print-only")
                 .help("Only print the docker run command and exit"))
@@ -169,6 +246,11 @@ pub fn new<'a>() -> App<'a, 'a> {
                 .multiple(true)
                 .takes_value(true)
                 .number_of_values(1))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .validator(is_integer)
+                .help("Stop after this many seconds"))
             .arg(Arg::with_name("print")
                 .long("print-only")
                 .help("Only print the docker run command and exit"))
@@ -195,9 +277,9 @@ pub fn new<'a>() -> App<'a, 'a> {
             .arg(Arg::with_name("component")
                 .help("The component to export")
                 .required(true))
-            .arg(Arg::with_name("output")
+            .arg(Arg::with_name("output-dir")
                 .short("o")
-                .long("output")
+                .long("output-dir")
                 .takes_value(true)
                 .help("Output directory to save to")))
         .subcommand(SubCommand::with_name("env")
@@ -250,6 +332,17 @@ pub fn new<'a>() -> App<'a, 'a> {
             .arg(Arg::with_name("component")
                 .required(true)
                 .help("Component name to search for")))
+        .subcommand(SubCommand::with_name("rdeps")
+            .about("Find published components that depend on a component")
+            .arg(Arg::with_name("component")
+                .required(true)
+                .help("Component to find dependants of, optionally as name=version"))
+            .arg(Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .validator(is_integer)
+                .help("Number of lockfiles to fetch concurrently (defaults to jobs in config)")))
         .subcommand(SubCommand::with_name("propagate")
             .about("Show steps to propagate a version fully through the tree")
             .arg(Arg::with_name("component")
@@ -258,7 +351,39 @@ pub fn new<'a>() -> App<'a, 'a> {
             .arg(Arg::with_name("json")
                 .short("j")
                 .long("json")
-                .help("Produce a machine readable instruction set")))
+                .conflicts_with("execute")
+                .help("Produce a machine readable instruction set"))
+            .arg(Arg::with_name("root")
+                .long("root")
+                .takes_value(true)
+                .validator(is_published)
+                .conflicts_with("execute")
+                .help("Plan from the published lockfiles of name=version instead of INPUT"))
+            .arg(Arg::with_name("execute")
+                .long("execute")
+                .help("Update, build and publish every repo in local checkouts"))
+            .arg(Arg::with_name("workspace")
+                .long("workspace")
+                .takes_value(true)
+                .requires("execute")
+                .help("Directory with checkouts of the repos to propagate through"))
+            .arg(Arg::with_name("repo")
+                .long("repo")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_checkout)
+                .requires("execute")
+                .help("Checkout of a repo to propagate through as name=path"))
+            .arg(Arg::with_name("state")
+                .long("state")
+                .takes_value(true)
+                .requires("execute")
+                .help("File to save progress in (defaults to .lal/propagate.json)"))
+            .arg(Arg::with_name("resume")
+                .long("resume")
+                .requires("execute")
+                .help("Continue a failed propagation from its saved state")))
         .subcommand(SubCommand::with_name("update-all")
             .about("Update all dependencies in the manifest")
             .arg(Arg::with_name("dev")
@@ -268,7 +393,13 @@ pub fn new<'a>() -> App<'a, 'a> {
             .arg(Arg::with_name("save")
                 .short("S")
                 .long("save")
-                .help("Save updated versions in the right object in the manifest")))
+                .help("Save updated versions in the right object in the manifest"))
+            .arg(Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .validator(is_integer)
+                .help("Number of components to fetch concurrently (defaults to jobs in config)")))
         .subcommand(SubCommand::with_name("publish")
             .setting(AppSettings::Hidden)
             .arg(Arg::with_name("component")
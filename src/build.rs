@@ -1,10 +1,15 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
 
 use super::{
-    ensure_dir_exists_fresh, output, CliError, Config, DockerRunFlags, Environment, LalResult, Lockfile,
-    Manifest, ShellModes,
+    buildcache, checksum, ensure_dir_exists_fresh, output, prepare_rootfs, BuildInfo, CliError, Config,
+    DockerRunFlags, Environment, HostInfo, LalResult, Lockfile, Manifest, ShellModes,
 };
-use crate::{shell, verify::verify};
+use crate::{fetch::fetch, shell, storage::CachedBackend, verify::verify};
 
 fn find_valid_build_script(component_dir: &Path) -> LalResult<String> {
     use std::os::unix::fs::PermissionsExt;
@@ -36,7 +41,32 @@ fn find_valid_build_script(component_dir: &Path) -> LalResult<String> {
     Ok(build_string)
 }
 
+/// What the environment currently resolves to, so that updating it invalidates cached builds
+///
+/// `None` when a container image is not available locally to inspect.
+fn environment_digest(cfg: &Config, environment: &Environment) -> Option<String> {
+    match environment {
+        Environment::Container(container) => {
+            let out = Command::new(container.runtime.binary())
+                .args(["image", "inspect", "--format", "{{.Id}}"])
+                .arg(container.to_string())
+                .output()
+                .ok()?;
+            if !out.status.success() {
+                return None;
+            }
+            Some(String::from_utf8_lossy(&out.stdout).trim().into())
+        }
+        Environment::Rootfs(rootfs) => prepare_rootfs(&cfg.cache, rootfs)
+            .ok()?
+            .file_name()
+            .map(|f| f.to_string_lossy().into()),
+        Environment::Sandbox(_) | Environment::None => Some(String::new()),
+    }
+}
+
 /// Configurable build flags for `lal build`
+#[derive(Clone)]
 pub struct BuildOptions {
     /// Component to build if specified
     pub name: Option<String>,
@@ -54,6 +84,8 @@ pub struct BuildOptions {
     pub force: bool,
     /// Use the `simple` verify algorithm
     pub simple_verify: bool,
+    /// Always run the build, even with the build cache enabled
+    pub no_cache: bool,
 }
 
 /// Runs the `./BUILD` script in a controlled environment and packages artifacts.
@@ -69,6 +101,8 @@ pub fn build(
     _modes: ShellModes,
 ) -> LalResult<()> {
     let mut modes = _modes;
+    // resolved here rather than by the caller, as `--matrix` builds in many environments
+    modes.limits = modes.limits.or(Some(&cfg.get_limits(&envname, manifest)));
 
     // have a better warning on first file-io operation
     // if nfs mounts and stuff cause issues this usually catches it
@@ -109,7 +143,7 @@ pub fn build(
         let ename = format!("{} not found in configurations list", configuration_name);
         return Err(CliError::InvalidBuildConfiguration(ename));
     }
-    let lockfile = Lockfile::new(
+    let mut lockfile = Lockfile::new(
         &component,
         &opts.environment,
         &envname,
@@ -124,26 +158,104 @@ pub fn build(
     lockfile.write(&lockpth)?; // always put a lockfile in OUTPUT at the start of a build
 
     let bpath = find_valid_build_script(&component_dir)?;
-    let cmd = vec![bpath, component.clone(), configuration_name];
+    let cmd = vec![bpath, component.clone(), configuration_name.clone()];
 
     if let Some(v) = opts.version.clone() {
         modes.env_vars.push(format!("BUILD_VERSION={}", v));
     }
 
+    let hook_vars = [
+        ("LAL_COMPONENT", component.clone()),
+        ("LAL_CONFIG", configuration_name.clone()),
+    ];
+    if !modes.printonly {
+        let pre_build = manifest.hooks.preBuild.as_deref();
+        shell::hook(
+            cfg,
+            &opts.environment,
+            &modes,
+            "preBuild",
+            pre_build,
+            &hook_vars,
+            component_dir,
+        )?;
+    }
+
     debug!("Build script is {:?} in {}", cmd, component_dir.display());
     if !modes.printonly {
         info!("Running build script in {} environment", envname);
     }
 
-    let run_flags = DockerRunFlags {
-        interactive: cfg.interactive,
-        privileged: false,
+    // key the build cache on everything that goes into the build before it runs
+    let cache_key = if cfg.build_cache && !opts.no_cache && !modes.printonly {
+        match environment_digest(cfg, &opts.environment) {
+            Some(digest) => {
+                let parts = vec![
+                    env!("CARGO_PKG_VERSION").to_string(),
+                    component.clone(),
+                    configuration_name,
+                    envname.clone(),
+                    serde_json::to_string(&opts.environment)?,
+                    digest,
+                    opts.version.clone().unwrap_or_default(),
+                    opts.sha.clone().unwrap_or_default(),
+                    modes.env_vars.join("\n"),
+                ];
+                Some(buildcache::key(component_dir, &parts)?)
+            }
+            None => {
+                warn!(
+                    "Not using the build cache as {} could not be inspected",
+                    opts.environment
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let cached = match &cache_key {
+        Some(key) => buildcache::restore_output(&cfg.cache, key, component_dir)?,
+        None => false,
     };
 
-    shell::run(cfg, &opts.environment, cmd, &run_flags, &modes, &component_dir)?;
+    if cached {
+        info!("Restored OUTPUT from the build cache");
+    } else {
+        let run_flags = DockerRunFlags {
+            interactive: cfg.interactive,
+            privileged: false,
+        };
+
+        // outside of OUTPUT until the end, so that the build cannot clobber it
+        let log = tempfile::NamedTempFile::new()?;
+        if !modes.printonly {
+            modes.log = Some(log.path().to_path_buf());
+        }
+        let start = Instant::now();
+        let r = shell::run(cfg, &opts.environment, cmd, &run_flags, &modes, component_dir);
 
-    if modes.printonly {
-        return Ok(()); // nothing else worth doing - warnings are pointless
+        if modes.printonly {
+            return r; // nothing else worth doing - warnings are pointless
+        }
+        let exit_code = match &r {
+            Ok(_) => Some(0),
+            Err(CliError::SubprocessFailure(code)) => Some(*code),
+            Err(_) => None,
+        };
+        lockfile.build = Some(BuildInfo {
+            wallTime: start.elapsed().as_secs_f64(),
+            exitCode: exit_code,
+            image: environment_digest(cfg, &opts.environment).filter(|d| !d.is_empty()),
+            host: HostInfo::current(),
+        });
+        fs::create_dir_all(component_dir.join("OUTPUT"))?;
+        fs::copy(log.path(), component_dir.join("OUTPUT").join(output::BUILD_LOG))?;
+        lockfile.write(&lockpth)?;
+        r?;
+        if let Some(key) = &cache_key {
+            buildcache::store_output(&cfg.cache, key, component_dir)?;
+        }
     }
 
     // Extra info and warnings for people who missed the leading ones (build is spammy)
@@ -158,17 +270,276 @@ pub fn build(
         warn!("Build was using non-default {} environment", envname);
     }
 
-    if opts.release && !modes.printonly {
+    let restored = match &cache_key {
+        Some(key) if cached && opts.release => buildcache::restore_artifact(&cfg.cache, key, component_dir)?,
+        _ => false,
+    };
+    if opts.release && !modes.printonly && !restored {
         trace!("Create ARTIFACT dir");
         ensure_dir_exists_fresh(&component_dir.join("./ARTIFACT"))?;
-        trace!("Copy lockfile to ARTIFACT dir");
-        fs::copy(&lockpth, &component_dir.join("./ARTIFACT/lockfile.json"))?;
 
         trace!("Tar up OUTPUT into ARTIFACT/component.tar.gz");
         let tarpth = component_dir
             .join("./ARTIFACT")
-            .join([component, ".tar.gz".into()].concat());
+            .join([component.clone(), ".tar.gz".into()].concat());
         output::tar(&component_dir, &tarpth)?;
+
+        // The digest cannot live inside the tarball, so only the ARTIFACT lockfile records it
+        trace!("Write lockfile with tarball checksum to ARTIFACT dir");
+        let mut release_lock = Lockfile::from_path(&lockpth, &component)?;
+        release_lock.sha256 = Some(checksum::sha256_file(&tarpth)?);
+        release_lock.write(&component_dir.join("./ARTIFACT/lockfile.json"))?;
+        let log = component_dir.join("OUTPUT").join(output::BUILD_LOG);
+        if log.is_file() {
+            fs::copy(&log, component_dir.join("./ARTIFACT/build.log"))?;
+        }
+
+        if let Some(key) = &cache_key {
+            buildcache::store_artifact(&cfg.cache, key, component_dir)?;
+        }
     }
+
+    let post_build = manifest.hooks.postBuild.as_deref();
+    shell::hook(
+        cfg,
+        &opts.environment,
+        &modes,
+        "postBuild",
+        post_build,
+        &hook_vars,
+        component_dir,
+    )?;
     Ok(())
 }
+
+/// Flags for building every component with `lal build --all`
+#[derive(Default)]
+pub struct BuildAllOptions {
+    /// Build every configuration rather than just the `defaultConfig`
+    pub all_configurations: bool,
+    /// Continue with the remaining builds after a failure
+    pub keep_going: bool,
+}
+
+/// Where finished `--all` builds are collected before becoming `OUTPUT`
+const ALL_OUTPUT: &str = "OUTPUT.all";
+
+fn print_summary(results: &[(String, Option<bool>, Duration)]) {
+    let width = results.iter().map(|r| r.0.len()).max().unwrap_or(0);
+    println!();
+    for (name, result, elapsed) in results {
+        match result {
+            Some(true) => println!("{:w$}  ok      {:.1}s", name, elapsed.as_secs_f64(), w = width),
+            Some(false) => println!("{:w$}  FAILED  {:.1}s", name, elapsed.as_secs_f64(), w = width),
+            None => println!("{:w$}  skipped", name, w = width),
+        }
+    }
+}
+
+/// Runs `build` for every component in the manifest
+///
+/// Each component is built in its `defaultConfig`, or in all its configurations,
+/// one after the other. The `OUTPUT` of each build ends up in
+/// `OUTPUT/<component>/<configuration>` once all are done, and a summary of the
+/// results and timings is printed.
+pub fn build_all(
+    component_dir: &Path,
+    cfg: &Config,
+    manifest: &Manifest,
+    opts: &BuildOptions,
+    all: &BuildAllOptions,
+    envname: String,
+    modes: ShellModes,
+) -> LalResult<()> {
+    let mut builds = vec![];
+    for (name, settings) in &manifest.components {
+        if all.all_configurations {
+            for c in &settings.configurations {
+                builds.push((name.clone(), c.clone()));
+            }
+        } else {
+            builds.push((name.clone(), settings.defaultConfig.clone()));
+        }
+    }
+
+    let staging = component_dir.join(ALL_OUTPUT);
+    if !modes.printonly {
+        ensure_dir_exists_fresh(&staging)?;
+    }
+    let mut results = vec![];
+    let mut failures = vec![];
+    for (component, configuration) in builds {
+        if !failures.is_empty() && !all.keep_going {
+            results.push((
+                format!("{}/{}", component, configuration),
+                None,
+                Duration::default(),
+            ));
+            continue;
+        }
+        info!("Building {} in its {} configuration", component, configuration);
+        let build_opts = BuildOptions {
+            name: Some(component.clone()),
+            configuration: Some(configuration.clone()),
+            ..opts.clone()
+        };
+        let start = Instant::now();
+        let r = build(
+            component_dir,
+            cfg,
+            manifest,
+            &build_opts,
+            envname.clone(),
+            modes.clone(),
+        );
+        let elapsed = start.elapsed();
+        if let Err(e) = &r {
+            error!("Failed to build {}/{}: {}", component, configuration, e);
+            failures.push(format!("{}/{}", component, configuration));
+        }
+        if modes.printonly {
+            continue;
+        }
+        // keep whatever the build left, failed or not
+        let output = component_dir.join("OUTPUT");
+        if output.is_dir() {
+            let dest = staging.join(&component);
+            fs::create_dir_all(&dest)?;
+            fs::rename(&output, dest.join(&configuration))?;
+        }
+        results.push((
+            format!("{}/{}", component, configuration),
+            Some(r.is_ok()),
+            elapsed,
+        ));
+    }
+
+    if !modes.printonly {
+        let output = component_dir.join("OUTPUT");
+        if output.exists() {
+            fs::remove_dir_all(&output)?;
+        }
+        fs::rename(&staging, &output)?;
+        print_summary(&results);
+    }
+    if !failures.is_empty() {
+        return Err(CliError::BuildFailures(failures));
+    }
+    Ok(())
+}
+
+/// Where `--matrix` builds each supported environment
+const MATRIX: &str = "MATRIX";
+
+/// Copy the source of a component into an empty work directory
+fn copy_source(component_dir: &Path, workdir: &Path) -> LalResult<()> {
+    use std::os::unix::fs::symlink;
+
+    for rel in buildcache::source_files(component_dir)? {
+        let (src, dest) = (component_dir.join(&rel), workdir.join(&rel));
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if src.symlink_metadata()?.file_type().is_symlink() {
+            symlink(fs::read_link(&src)?, &dest)?;
+        } else {
+            fs::copy(&src, &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `build` once in every supported environment of the manifest
+///
+/// Each environment gets a fresh copy of the source in `MATRIX/<env>` with its
+/// own `INPUT` fetched for that environment, so that builds can not see each
+/// others dependencies. Release builds leave an `ARTIFACT` in each of these,
+/// which `lal publish` uploads together. A summary of the results and timings
+/// is printed at the end.
+pub async fn build_matrix(
+    component_dir: &Path,
+    cfg: &Config,
+    manifest: &Manifest,
+    opts: &BuildOptions,
+    keep_going: bool,
+    backend: &dyn CachedBackend,
+    modes: ShellModes,
+) -> LalResult<()> {
+    let matrix = component_dir.join(MATRIX);
+    ensure_dir_exists_fresh(&matrix)?;
+    // a plain release build would otherwise be published instead
+    let artifact = component_dir.join("ARTIFACT");
+    if artifact.is_dir() {
+        fs::remove_dir_all(&artifact)?;
+    }
+
+    let mut results = vec![];
+    let mut failures = vec![];
+    for env in &manifest.supportedEnvironments {
+        if !failures.is_empty() && !keep_going {
+            results.push((env.clone(), None, Duration::default()));
+            continue;
+        }
+        info!("Building {} in the {} environment", manifest.name, env);
+        let start = Instant::now();
+        let workdir = matrix.join(env);
+        let r = build_in(component_dir, &workdir, cfg, manifest, opts, env, backend, &modes).await;
+        let elapsed = start.elapsed();
+        if let Err(e) = &r {
+            error!("Failed to build in {}: {}", env, e);
+            failures.push(env.clone());
+        }
+        results.push((env.clone(), Some(r.is_ok()), elapsed));
+    }
+
+    print_summary(&results);
+    if !failures.is_empty() {
+        return Err(CliError::BuildFailures(failures));
+    }
+    Ok(())
+}
+
+/// One environment of `build_matrix`
+#[allow(clippy::too_many_arguments)]
+async fn build_in(
+    component_dir: &Path,
+    workdir: &Path,
+    cfg: &Config,
+    manifest: &Manifest,
+    opts: &BuildOptions,
+    env: &str,
+    backend: &dyn CachedBackend,
+    modes: &ShellModes,
+) -> LalResult<()> {
+    let environment = manifest
+        .get_environment(env)
+        .or_else(|_| cfg.get_environment(env))?;
+    ensure_dir_exists_fresh(workdir)?;
+    copy_source(component_dir, workdir)?;
+    fetch(workdir, manifest, backend, false, env, cfg.jobs, false).await?;
+    let post_fetch = manifest.hooks.postFetch.as_deref();
+    let vars = [("LAL_COMPONENT", manifest.name.clone())];
+    shell::hook(cfg, &environment, modes, "postFetch", post_fetch, &vars, workdir)?;
+    let build_opts = BuildOptions {
+        environment,
+        ..opts.clone()
+    };
+    build(workdir, cfg, manifest, &build_opts, env.into(), modes.clone())
+}
+
+/// Work directories of `--matrix` builds that left a release `ARTIFACT`
+pub fn matrix_builds(component_dir: &Path, name: &str) -> LalResult<Vec<std::path::PathBuf>> {
+    let matrix = component_dir.join(MATRIX);
+    if !matrix.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut dirs = vec![];
+    for entry in fs::read_dir(&matrix)? {
+        let dir = entry?.path();
+        if dir.join("ARTIFACT").join(format!("{}.tar.gz", name)).is_file() {
+            dirs.push(dir);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
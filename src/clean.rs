@@ -45,5 +45,9 @@ pub fn clean(cache: &Path, days: i64) -> LalResult<()> {
     let dirs = WalkDir::new(&cache).min_depth(3).max_depth(3);
     clean_in_dir(cutoff, dirs)?;
 
+    // clean out build cache entries that have not been used for a while
+    let bdirs = WalkDir::new(cache.join("builds")).min_depth(1).max_depth(1);
+    clean_in_dir(cutoff, bdirs)?;
+
     Ok(())
 }
@@ -0,0 +1,168 @@
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+    vec::Vec,
+};
+use tar::Archive;
+use walkdir::WalkDir;
+
+use super::{ensure_dir_exists_fresh, output, LalResult};
+
+/// Top level directories that builds produce or that lal manages
+const EXCLUDED: &[&str] = &["OUTPUT", "OUTPUT.all", "ARTIFACT", "INPUT", "MATRIX", ".git"];
+
+fn excluded(rel: &Path) -> bool {
+    match rel.components().next() {
+        Some(c) => EXCLUDED.iter().any(|e| c.as_os_str() == *e),
+        None => true,
+    }
+}
+
+/// Source files of a component, relative to it and sorted
+///
+/// Inside a git work tree, git decides which files are ignored. Otherwise
+/// everything but the excluded directories is part of the source.
+pub fn source_files(component_dir: &Path) -> LalResult<Vec<PathBuf>> {
+    let git = Command::new("git")
+        .args(["ls-files", "-z", "--cached", "--others", "--exclude-standard"])
+        .current_dir(component_dir)
+        .output();
+    let mut files: Vec<PathBuf> = match git {
+        Ok(out) if out.status.success() => out
+            .stdout
+            .split(|b| *b == 0)
+            .filter(|f| !f.is_empty())
+            .map(|f| PathBuf::from(String::from_utf8_lossy(f).into_owned()))
+            // deleted files are still listed until the deletion is staged
+            .filter(|f| component_dir.join(f).symlink_metadata().is_ok())
+            .collect(),
+        _ => {
+            let mut files = vec![];
+            for entry in WalkDir::new(component_dir).follow_links(false) {
+                let entry = entry.map_err(io::Error::from)?;
+                if !entry.file_type().is_dir() {
+                    files.push(entry.path().strip_prefix(component_dir)?.to_path_buf());
+                }
+            }
+            files
+        }
+    };
+    files.retain(|f| !excluded(f));
+    files.sort();
+    Ok(files)
+}
+
+/// Key identifying a build from its source, its `INPUT` and everything else that
+/// can influence the `OUTPUT`
+///
+/// The `parts` are the environment, configuration, env vars and so on, as
+/// decided by the caller.
+pub fn key(component_dir: &Path, parts: &[String]) -> LalResult<String> {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update(b"\0");
+    }
+
+    for rel in source_files(component_dir)? {
+        let path = component_dir.join(&rel);
+        let meta = path.symlink_metadata()?;
+        hasher.update(rel.to_string_lossy().as_bytes());
+        if meta.file_type().is_symlink() {
+            hasher.update(b"\0link\0");
+            hasher.update(fs::read_link(&path)?.to_string_lossy().as_bytes());
+        } else {
+            let exec = meta.permissions().mode() & 0o111 != 0;
+            hasher.update(format!("\0{}\0{}\0", exec, meta.len()).as_bytes());
+            io::copy(&mut File::open(&path)?, &mut hasher)?;
+        }
+        hasher.update(b"\0");
+    }
+
+    // what was built against, rather than all of INPUT
+    let input = component_dir.join("INPUT");
+    let mut deps = fs::read_dir(&input)
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .map(|e| e.file_name())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    deps.sort();
+    for dep in deps {
+        let lock = input.join(&dep).join("lockfile.json");
+        if lock.is_file() {
+            hasher.update(dep.to_string_lossy().as_bytes());
+            hasher.update(b"\0");
+            hasher.write_all(&fs::read(&lock)?)?;
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn entry_dir(cache: &Path, key: &str) -> PathBuf {
+    cache.join("builds").join(key)
+}
+
+/// Restore `OUTPUT` from an earlier build with the same key
+///
+/// Returns whether there was anything to restore.
+pub fn restore_output(cache: &Path, key: &str, component_dir: &Path) -> LalResult<bool> {
+    let tarball = entry_dir(cache, key).join("OUTPUT.tar.gz");
+    if !tarball.is_file() {
+        return Ok(false);
+    }
+    let output = component_dir.join("OUTPUT");
+    ensure_dir_exists_fresh(&output)?;
+    Archive::new(GzDecoder::new(File::open(&tarball)?)).unpack(&output)?;
+    // `lal clean` goes by the mtime, so keep entries in use around
+    filetime::set_file_mtime(entry_dir(cache, key), filetime::FileTime::now())?;
+    Ok(true)
+}
+
+/// Remember `OUTPUT` of a successful build under its key
+pub fn store_output(cache: &Path, key: &str, component_dir: &Path) -> LalResult<()> {
+    let dir = entry_dir(cache, key);
+    fs::create_dir_all(&dir)?;
+    // tar next to it first so a failed build cache write never leaves a partial entry
+    let partial = dir.join("OUTPUT.tar.gz.partial");
+    output::tar(component_dir, &partial)?;
+    fs::rename(&partial, dir.join("OUTPUT.tar.gz"))?;
+    Ok(())
+}
+
+/// Restore the release `ARTIFACT` from an earlier release build with the same key
+///
+/// Returns whether there was anything to restore.
+pub fn restore_artifact(cache: &Path, key: &str, component_dir: &Path) -> LalResult<bool> {
+    let cached = entry_dir(cache, key).join("ARTIFACT");
+    if !cached.is_dir() {
+        return Ok(false);
+    }
+    let artifact = component_dir.join("ARTIFACT");
+    ensure_dir_exists_fresh(&artifact)?;
+    for entry in fs::read_dir(&cached)? {
+        let entry = entry?;
+        fs::copy(entry.path(), artifact.join(entry.file_name()))?;
+    }
+    Ok(true)
+}
+
+/// Remember the release `ARTIFACT` under its key
+pub fn store_artifact(cache: &Path, key: &str, component_dir: &Path) -> LalResult<()> {
+    let dir = entry_dir(cache, key);
+    let partial = dir.join("ARTIFACT.partial");
+    ensure_dir_exists_fresh(&partial)?;
+    for entry in fs::read_dir(component_dir.join("ARTIFACT"))? {
+        let entry = entry?;
+        fs::copy(entry.path(), partial.join(entry.file_name()))?;
+    }
+    let _ = fs::remove_dir_all(dir.join("ARTIFACT"));
+    fs::rename(&partial, dir.join("ARTIFACT"))?;
+    Ok(())
+}
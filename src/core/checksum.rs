@@ -0,0 +1,24 @@
+use sha2::{Digest, Sha256};
+use std::{fs::File, io, path::Path};
+
+use super::{CliError, LalResult};
+
+/// Compute the hex encoded SHA-256 digest of a file
+pub fn sha256_file(path: &Path) -> LalResult<String> {
+    let mut f = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut f, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Verify that a file has the SHA-256 digest we expect
+///
+/// The `name` is only used to produce a sensible error on mismatch.
+pub fn verify_sha256(path: &Path, expected: &str, name: &str) -> LalResult<()> {
+    let found = sha256_file(path)?;
+    if found != expected {
+        return Err(CliError::ChecksumMismatch(name.into(), expected.into(), found));
+    }
+    debug!("Verified sha256 of {} as {}", path.display(), found);
+    Ok(())
+}
@@ -8,7 +8,7 @@ use std::{
     vec::Vec,
 };
 
-use super::{CliError, Container, Environment, LalResult};
+use super::{CliError, Container, Environment, LalResult, Limits, Manifest};
 use crate::storage::{BackendConfiguration, LocalConfig};
 
 fn find_home_dir() -> PathBuf {
@@ -60,6 +60,31 @@ pub struct Config {
     pub interactive: bool,
     /// Minimum version restriction of lal enforced by this config
     pub minimum_lal: Option<String>,
+    /// Maximum number of components to fetch concurrently
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+    /// Only use components already in the cache
+    #[serde(default)]
+    pub offline: bool,
+    /// Reuse the OUTPUT of earlier builds of identical inputs
+    #[serde(default)]
+    pub build_cache: bool,
+    /// Resource limits for each environment on this machine
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub limits: BTreeMap<String, Limits>,
+    /// Ed25519 private key in PEM format to sign releases with on publish
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<PathBuf>,
+    /// Ed25519 public keys in PEM format whose signatures are trusted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_keys: Vec<String>,
+    /// Refuse published artifacts not signed by a trusted key
+    #[serde(default)]
+    pub require_signatures: bool,
+}
+
+fn default_jobs() -> usize {
+    4
 }
 
 /// Representation of a configuration defaults file
@@ -75,6 +100,12 @@ pub struct ConfigDefaults {
     pub mounts: Vec<Mount>,
     /// Optional minimum version restriction of lal
     pub minimum_lal: Option<String>,
+    /// Ed25519 public keys in PEM format whose signatures are trusted
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    /// Refuse published artifacts not signed by a trusted key
+    #[serde(default)]
+    pub require_signatures: bool,
 }
 
 impl ConfigDefaults {
@@ -85,6 +116,8 @@ impl ConfigDefaults {
             environments: BTreeMap::<String, Environment>::new(),
             mounts: Vec::<Mount>::new(),
             minimum_lal: Option::<String>::None,
+            trusted_keys: vec![],
+            require_signatures: false,
         }
     }
 }
@@ -152,6 +185,13 @@ impl Config {
             backend: defaults.backend,
             minimum_lal: defaults.minimum_lal,
             interactive: true,
+            jobs: default_jobs(),
+            offline: false,
+            build_cache: false,
+            limits: BTreeMap::new(),
+            signing_key: None,
+            trusted_keys: defaults.trusted_keys,
+            require_signatures: defaults.require_signatures,
         }
     }
 
@@ -210,11 +250,24 @@ impl Config {
         Err(CliError::MissingEnvironment(env))
     }
 
+    /// Resource limits for an environment
+    ///
+    /// Limits set for this machine take precedence over those in the manifest.
+    pub fn get_limits(&self, env: &str, manifest: &Manifest) -> Limits {
+        self.limits
+            .get(env)
+            .cloned()
+            .unwrap_or_default()
+            .or(manifest.limits.get(env))
+    }
+
     /// Resolve an arbitrary container shorthand
     pub fn get_container(&self, env: String) -> LalResult<Container> {
         match self.get_environment(env.clone())? {
             Environment::Container(container) => Ok(container),
-            Environment::None => Err(CliError::MissingEnvironment(env)),
+            Environment::Sandbox(_) | Environment::Rootfs(_) | Environment::None => {
+                Err(CliError::MissingEnvironment(env))
+            }
         }
     }
 }
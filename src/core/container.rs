@@ -1,6 +1,31 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+/// Container engine used to run an image
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    /// Docker with the `lal` user remapped in a derived image
+    #[default]
+    Docker,
+    /// Rootless podman with the user namespace keeping the host ids
+    Podman,
+}
+
+impl ContainerRuntime {
+    /// Name of the executable for this runtime
+    pub fn binary(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == ContainerRuntime::default()
+    }
+}
+
 /// Representation of a docker container image
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Container {
@@ -8,6 +33,9 @@ pub struct Container {
     pub name: String,
     /// The tag to use
     pub tag: String,
+    /// The engine to run the image with
+    #[serde(default, skip_serializing_if = "ContainerRuntime::is_default")]
+    pub runtime: ContainerRuntime,
 }
 
 impl Container {
@@ -16,6 +44,7 @@ impl Container {
         Container {
             name: name.into(),
             tag: "latest".into(),
+            runtime: ContainerRuntime::default(),
         }
     }
 }
@@ -33,6 +62,7 @@ impl Default for Container {
         Container {
             name: "ubuntu".into(),
             tag: "xenial".into(),
+            runtime: ContainerRuntime::default(),
         }
     }
 }
@@ -49,6 +79,7 @@ impl Container {
         Container {
             name: cname.into(),
             tag: tag.into(),
+            runtime: ContainerRuntime::default(),
         }
     }
 }
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use super::Container;
+use super::{Container, Rootfs, Sandbox};
 
 /// Representation of a possible command execution environment
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -9,6 +9,10 @@ use super::Container;
 pub enum Environment {
     /// A Docker container environment.
     Container(Container),
+    /// A native environment isolated from the host with Linux namespaces.
+    Sandbox(Sandbox),
+    /// An unpacked root filesystem, isolated like a sandbox.
+    Rootfs(Rootfs),
     /// No environment, use what is already on the host.
     None,
 }
@@ -17,6 +21,8 @@ impl fmt::Display for Environment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Environment::Container(container) => write!(f, "{}", container),
+            Environment::Sandbox(sandbox) => write!(f, "{}", sandbox),
+            Environment::Rootfs(rootfs) => write!(f, "{}", rootfs),
             Environment::None => write!(f, "No environment"),
         }
     }
@@ -19,6 +19,8 @@ pub enum CliError {
     ParseInt(num::ParseIntError),
     /// Errors propagated from `hyper`
     Hype(hyper::Error),
+    /// Errors propagated from `reqwest`
+    Reqwest(reqwest::Error),
     /// Errors propagated from `semver`
     SemVer(semver::Error),
     /// Errors propagated from `log`
@@ -48,6 +50,8 @@ pub enum CliError {
     UnmappableRootUser,
     /// Missing predefined mount
     MissingMount(String),
+    /// Backend in the config cannot be used
+    InvalidBackendConfiguration(String),
 
     // status/verify errors
     /// Core dependencies missing in INPUT
@@ -56,6 +60,10 @@ pub enum CliError {
     DependencyCycle(String),
     /// Dependency present at wrong version
     InvalidVersion(String),
+    /// Version requirement in the manifest is not a valid semver range
+    InvalidVersionRequirement(String, String),
+    /// Version requirement constraining minor or patch versions, which published versions lack
+    MinorVersionRequirement(String),
     /// Extraneous dependencies in INPUT
     ExtraneousDependencies(String),
     /// No lockfile found for a component in INPUT
@@ -88,6 +96,8 @@ pub enum CliError {
     BuildScriptNotExecutable(String),
     /// BUILD script not found
     MissingBuildScript,
+    /// Builds that failed with `lal build --all`
+    BuildFailures(Vec<String>),
 
     // script errors
     /// Script not found in local .lal/scripts/ directory
@@ -98,6 +108,12 @@ pub enum CliError {
     MissingTarball,
     /// Failed to find build artifacts in OUTPUT after a build or before stashing
     MissingBuild,
+    /// Tarball digest did not match the one recorded in its lockfile
+    ChecksumMismatch(String, String, String),
+    /// Published artifact not signed by a trusted key, or with a bad signature
+    InvalidSignature(String, String),
+    /// Release could not be signed with the configured key
+    SigningFailure(String),
 
     // stash errors
     /// Invalid integer name used with lal stash
@@ -107,27 +123,59 @@ pub enum CliError {
 
     /// Shell errors from docker subprocess
     SubprocessFailure(i32),
+    /// Subprocess stopped after running for longer than its timeout in seconds
+    Timeout(u64),
+    /// A hook from the manifest failed
+    HookFailure(String, String),
     /// Docker permission gate
     DockerPermissionSafety(String, u32, u32),
     /// Docker image not found
     DockerImageNotFound(String),
+    /// Namespace sandbox could not be set up
+    SandboxFailure(String),
+    /// Rootfs environment component not fetched yet
+    MissingRootfs(String),
+    /// Rootfs tarball or image layout that cannot be unpacked
+    InvalidRootfs(String),
 
     // fetch/update failures
     /// Unspecified install failure
     InstallFailure,
     /// Fetch failure related to backend
     BackendFailure(String),
+    /// The backend definitely does not have a file
+    MissingArtifact(String),
     /// No version found at same version across `supportedEnvironments`
     NoIntersectedVersion(String),
+    /// No available version satisfies a version requirement
+    NoMatchingVersion(String, String),
+    /// Dependency not resolved in `.lal/lock.json` when fetching with `--locked`
+    UnlockedDependency(String),
+    /// Component not in the cache while offline
+    NotCached(String, String),
+    /// Components whose lockfiles could not be read by `lal rdeps`
+    IncompleteScan(Vec<String>),
+    /// Operation that needs the backend attempted while offline
+    Offline(String),
 
     // publish errors
     /// Missing release build
     MissingReleaseBuild,
+    /// Matrix release builds that do not cover every supported environment at one version
+    IncompleteMatrix(String),
     /// Config missing backend credentials
     MissingBackendCredentials,
     /// Failed upload request to the backend
     UploadFailure(String),
 
+    // propagate errors
+    /// Repository to propagate through without a local checkout
+    MissingCheckout(String),
+    /// Repositories that failed during `lal propagate --execute`
+    PropagationFailures(Vec<String>),
+    /// Saved propagation state belongs to another propagation
+    PropagationStateMismatch(String, String),
+
     // upgrade error
     /// Failing to write to our current install prefix
     MissingPrefixPermissions(String),
@@ -154,6 +202,7 @@ impl fmt::Display for CliError {
             CliError::Parse(ref err) => err.fmt(f),
             CliError::ParseInt(ref err) => err.fmt(f),
             CliError::Hype(ref err) => err.fmt(f),
+            CliError::Reqwest(ref err) => err.fmt(f),
             CliError::SemVer(ref err) => err.fmt(f),
             CliError::Log(ref err) => err.fmt(f),
 
@@ -177,6 +226,9 @@ impl fmt::Display for CliError {
             CliError::MissingSslCerts => write!(f, "Missing SSL certificates"),
             CliError::UnmappableRootUser => write!(f, "Root user is not supported for lal builds"),
             CliError::MissingMount(ref s) => write!(f, "Missing mount {}", s),
+            CliError::InvalidBackendConfiguration(ref s) => {
+                write!(f, "Invalid backend in ~/.lal/config: {}", s)
+            }
             CliError::MissingConfig => write!(f, "No ~/.lal/config found"),
             CliError::MissingComponent(ref s) => write!(f, "Component '{}' not found in manifest", s),
             CliError::InvalidComponentName(ref s) => {
@@ -188,6 +240,14 @@ impl fmt::Display for CliError {
             }
             CliError::DependencyCycle(ref s) => write!(f, "Cyclical dependencies found for {} in INPUT", s),
             CliError::InvalidVersion(ref s) => write!(f, "Dependency {} using incorrect version", s),
+            CliError::InvalidVersionRequirement(ref s, ref r) => {
+                write!(f, "Invalid version requirement '{}' for {}", r, s)
+            }
+            CliError::MinorVersionRequirement(ref r) => write!(
+                f,
+                "Version requirement '{}' can only constrain major versions, as published versions are integers",
+                r
+            ),
             CliError::ExtraneousDependencies(ref s) => write!(f, "Extraneous dependencies in INPUT ({})", s),
             CliError::MissingLockfile(ref s) => write!(f, "No lockfile found for {}", s),
             CliError::MultipleVersions(ref s) => write!(f, "Depending on multiple versions of {}", s),
@@ -219,11 +279,21 @@ impl fmt::Display for CliError {
             CliError::InvalidBuildConfiguration(ref s) => write!(f, "Invalid build configuration - {}", s),
             CliError::BuildScriptNotExecutable(ref s) => write!(f, "BUILD script at {} is not executable", s),
             CliError::MissingBuildScript => write!(f, "No `BUILD` script found"),
+            CliError::BuildFailures(ref v) => write!(f, "Failed to build {}", v.join(", ")),
             CliError::MissingScript(ref s) => {
                 write!(f, "Missing script '{}' in local folder .lal/scripts/", s)
             }
             CliError::MissingTarball => write!(f, "Tarball missing in PWD"),
             CliError::MissingBuild => write!(f, "No build found in OUTPUT"),
+            CliError::InvalidSignature(ref name, ref reason) => {
+                write!(f, "Signature verification failed for {} - {}", name, reason)
+            }
+            CliError::SigningFailure(ref s) => write!(f, "Failed to sign the release: {}", s),
+            CliError::ChecksumMismatch(ref name, ref expected, ref found) => write!(
+                f,
+                "Checksum mismatch for {} - expected sha256 {} but found {}",
+                name, expected, found
+            ),
             CliError::InvalidStashName(n) => {
                 write!(f, "Invalid name '{}' to stash under - must not be an integer", n)
             }
@@ -231,19 +301,68 @@ impl fmt::Display for CliError {
                 write!(f, "No stashed artifact '{}' found in ~/.lal/cache/stash", s)
             }
             CliError::SubprocessFailure(n) => write!(f, "Process exited with {}", n),
+            CliError::HookFailure(ref h, ref e) => write!(f, "The {} hook failed: {}", h, e),
+            CliError::Timeout(n) => write!(f, "Process stopped after exceeding its {}s timeout", n),
             CliError::DockerPermissionSafety(ref s, u, g) => write!(
                 f,
                 "ID mismatch inside and outside docker - {}; UID and GID are {}:{}",
                 s, u, g
             ),
             CliError::DockerImageNotFound(ref s) => write!(f, "Could not find docker image {}", s),
+            CliError::MissingRootfs(ref s) => {
+                write!(f, "{} is not in the cache - run `lal env update` to fetch it", s)
+            }
+            CliError::InvalidRootfs(ref s) => write!(f, "Invalid rootfs - {}", s),
+            CliError::SandboxFailure(ref s) => write!(
+                f,
+                "Failed to set up the build sandbox ({}) - are unprivileged user namespaces enabled?",
+                s
+            ),
             CliError::InstallFailure => write!(f, "Install failed"),
             CliError::BackendFailure(ref s) => write!(f, "Backend - {}", s),
+            CliError::MissingArtifact(ref s) => write!(f, "Backend has no {}", s),
             CliError::NoIntersectedVersion(ref s) => {
                 write!(f, "No version of {} found across all environments", s)
             }
+            CliError::NoMatchingVersion(ref s, ref r) => {
+                write!(
+                    f,
+                    "No version of {} satisfying {} found across all environments",
+                    s, r
+                )
+            }
+            CliError::UnlockedDependency(ref s) => write!(
+                f,
+                "{} is not resolved in .lal/lock.json - run `lal fetch` without --locked",
+                s
+            ),
+            CliError::NotCached(ref c, ref env) => write!(
+                f,
+                "{} is not in the cache for the {} environment - fetch it without --offline first",
+                c, env
+            ),
+            CliError::Offline(ref s) => write!(f, "No {} possible with --offline", s),
+            CliError::IncompleteScan(ref v) => write!(
+                f,
+                "Could not scan {} - their dependants may be missing",
+                v.join(", ")
+            ),
             CliError::MissingReleaseBuild => write!(f, "Missing release build"),
+            CliError::IncompleteMatrix(ref s) => write!(f, "Matrix release can not be published - {}", s),
             CliError::MissingBackendCredentials => write!(f, "Missing backend credentials in ~/.lal/config"),
+            CliError::MissingCheckout(ref s) => write!(
+                f,
+                "No local checkout of {} found - pass it with --repo or --workspace",
+                s
+            ),
+            CliError::PropagationFailures(ref v) => write!(
+                f,
+                "Failed to propagate through {} - fix and continue with --resume",
+                v.join(", ")
+            ),
+            CliError::PropagationStateMismatch(ref saved, ref s) => {
+                write!(f, "Saved propagation state is for {}, not {}", saved, s)
+            }
             CliError::MissingPrefixPermissions(ref s) => write!(
                 f,
                 "No write access in {} - consider chowning: `sudo chown -R $USER {}`",
@@ -257,6 +376,115 @@ impl fmt::Display for CliError {
     }
 }
 
+impl CliError {
+    /// Broad category of the error, for machine readable output
+    ///
+    /// Each category has its own `exit_code`.
+    pub fn category(&self) -> &'static str {
+        match *self {
+            CliError::Io(_)
+            | CliError::Path(_)
+            | CliError::WalkDir(_)
+            | CliError::Parse(_)
+            | CliError::ParseInt(_)
+            | CliError::Hype(_)
+            | CliError::Reqwest(_)
+            | CliError::SemVer(_)
+            | CliError::Log(_)
+            | CliError::UnicodeError => "internal",
+
+            CliError::MissingManifest
+            | CliError::MissingConfig
+            | CliError::MissingComponent(_)
+            | CliError::InvalidComponentName(_)
+            | CliError::ManifestExists
+            | CliError::ExecutableMissing(_)
+            | CliError::OutdatedLal(..)
+            | CliError::MissingSslCerts
+            | CliError::UnmappableRootUser
+            | CliError::MissingMount(_)
+            | CliError::InvalidBackendConfiguration(_) => "config",
+
+            CliError::MissingDependencies
+            | CliError::DependencyCycle(_)
+            | CliError::InvalidVersion(_)
+            | CliError::InvalidVersionRequirement(..)
+            | CliError::MinorVersionRequirement(_)
+            | CliError::ExtraneousDependencies(_)
+            | CliError::MissingLockfile(_)
+            | CliError::MultipleVersions(_)
+            | CliError::MissingExpectedEnvironment(_)
+            | CliError::MultipleEnvironments(_)
+            | CliError::EnvironmentMismatch(..)
+            | CliError::NonGlobalDependencies(_)
+            | CliError::NoSupportedEnvironments
+            | CliError::UnsupportedEnvironment => "verify",
+
+            CliError::MissingEnvironment(_)
+            | CliError::EnvironmentUnspecified
+            | CliError::DockerPermissionSafety(..)
+            | CliError::DockerImageNotFound(_)
+            | CliError::SandboxFailure(_)
+            | CliError::MissingRootfs(_)
+            | CliError::InvalidRootfs(_) => "environment",
+
+            CliError::InvalidBuildConfiguration(_)
+            | CliError::BuildScriptNotExecutable(_)
+            | CliError::MissingBuildScript
+            | CliError::BuildFailures(_) => "build",
+
+            CliError::MissingScript(_)
+            | CliError::SubprocessFailure(_)
+            | CliError::Timeout(_)
+            | CliError::HookFailure(..) => "run",
+
+            CliError::MissingTarball
+            | CliError::MissingBuild
+            | CliError::ChecksumMismatch(..)
+            | CliError::InvalidSignature(..)
+            | CliError::InvalidStashName(_)
+            | CliError::MissingStashArtifact(_)
+            | CliError::InstallFailure
+            | CliError::BackendFailure(_)
+            | CliError::MissingArtifact(_)
+            | CliError::NoIntersectedVersion(_)
+            | CliError::NoMatchingVersion(..)
+            | CliError::UnlockedDependency(_)
+            | CliError::NotCached(..)
+            | CliError::IncompleteScan(_)
+            | CliError::Offline(_) => "fetch",
+
+            CliError::SigningFailure(_)
+            | CliError::MissingReleaseBuild
+            | CliError::IncompleteMatrix(_)
+            | CliError::MissingBackendCredentials
+            | CliError::UploadFailure(_) => "publish",
+
+            CliError::MissingCheckout(_)
+            | CliError::PropagationFailures(_)
+            | CliError::PropagationStateMismatch(..) => "propagate",
+
+            CliError::MissingPrefixPermissions(_) | CliError::UpgradeValidationFailure(_) => "upgrade",
+        }
+    }
+
+    /// Process exit code for the category of the error
+    pub fn exit_code(&self) -> i32 {
+        match self.category() {
+            "internal" => 2,
+            "config" => 3,
+            "verify" => 4,
+            "environment" => 5,
+            "build" => 6,
+            "run" => 7,
+            "fetch" => 8,
+            "publish" => 9,
+            "propagate" => 10,
+            _ => 11, // upgrade
+        }
+    }
+}
+
 // Allow io and json errors to be converted to `CliError` in a try! without map_err
 impl From<io::Error> for CliError {
     fn from(err: io::Error) -> CliError {
@@ -282,6 +510,12 @@ impl From<hyper::Error> for CliError {
     }
 }
 
+impl From<reqwest::Error> for CliError {
+    fn from(err: reqwest::Error) -> CliError {
+        CliError::Reqwest(err)
+    }
+}
+
 impl From<semver::Error> for CliError {
     fn from(err: semver::Error) -> CliError {
         CliError::SemVer(err)
@@ -1,6 +1,6 @@
 #![allow(missing_docs)]
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, fs::File, io::prelude::*, path::Path};
 
 use walkdir::WalkDir;
@@ -53,7 +53,7 @@ pub fn analyze(component_dir: &Path) -> LalResult<BTreeMap<String, String>> {
     Ok(deps)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct InputDependency {
     pub name: String,
     pub missing: bool,
@@ -156,11 +156,11 @@ pub fn verify_global_versions(lf: &Lockfile, m: &Manifest) -> LalResult<()> {
             CliError::NonGlobalDependencies(name.clone())
         })?;
         // also ensure it matches the version in the manifest
-        let vreq = *all_deps.get(name).ok_or_else(|| {
+        let vreq = all_deps.get(name).ok_or_else(|| {
             // This is a first level dependency - it should be in the manifest
             CliError::ExtraneousDependencies(name.clone())
         })?;
-        if v != vreq {
+        if !vreq.matches(v) {
             warn!(
                 "Dependency {} has version {}, but manifest requires {}",
                 name, v, vreq
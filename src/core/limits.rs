@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Resource limits for commands run in an environment
+///
+/// Set per environment name in the `Config` and the `Manifest`, and per
+/// invocation with `--timeout`. Anything left unset is unlimited.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// Memory limit in the format of `docker run --memory`, e.g. `4g`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    /// Number of CPUs, possibly fractional
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<f64>,
+    /// Maximum number of processes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pids: Option<u32>,
+    /// Seconds after which the command is stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+impl Limits {
+    /// Fill in whatever is unset here from less specific limits
+    pub fn or(self, other: Option<&Limits>) -> Limits {
+        let other = match other {
+            Some(o) => o.clone(),
+            None => return self,
+        };
+        Limits {
+            memory: self.memory.or(other.memory),
+            cpus: self.cpus.or(other.cpus),
+            pids: self.pids.or(other.pids),
+            timeout: self.timeout.or(other.timeout),
+        }
+    }
+
+    /// Whether any limit besides the timeout is set
+    pub fn has_resources(&self) -> bool {
+        self.memory.is_some() || self.cpus.is_some() || self.pids.is_some()
+    }
+
+    /// The timeout as a `Duration`
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+}
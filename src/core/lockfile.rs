@@ -29,10 +29,64 @@ pub struct Lockfile {
     pub tool: String,
     /// Built timestamp
     pub built: Option<String>,
+    /// SHA-256 digest of the release tarball (only set in ARTIFACT)
+    pub sha256: Option<String>,
+    /// How the build went, once it has run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildInfo>,
     /// Recursive map of dependencies used
     pub dependencies: BTreeMap<String, Lockfile>,
 }
 
+/// Timing and provenance of the `./BUILD` run, recorded in `lockfile.json`
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BuildInfo {
+    /// Seconds the build script ran for
+    pub wallTime: f64,
+    /// Exit code of the build script, if it ran to completion
+    pub exitCode: Option<i32>,
+    /// Image id or rootfs digest of the environment, if it has one
+    pub image: Option<String>,
+    /// The machine the build ran on
+    pub host: HostInfo,
+}
+
+/// Description of the machine a build ran on
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HostInfo {
+    /// Host name
+    pub name: String,
+    /// Operating system
+    pub os: String,
+    /// CPU architecture
+    pub arch: String,
+    /// Kernel release
+    pub kernel: String,
+}
+
+impl HostInfo {
+    /// Details of the machine lal is running on
+    pub fn current() -> Self {
+        let field = |f: &[libc::c_char]| {
+            let bytes: Vec<u8> = f.iter().take_while(|c| **c != 0).map(|c| *c as u8).collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        };
+        let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+        let (name, kernel) = if unsafe { libc::uname(&mut uts) } == 0 {
+            (field(&uts.nodename), field(&uts.release))
+        } else {
+            Default::default()
+        };
+        HostInfo {
+            name,
+            os: std::env::consts::OS.into(),
+            arch: std::env::consts::ARCH.into(),
+            kernel,
+        }
+    }
+}
+
 /// Generates a temporary empty lockfile for internal analysis
 impl Default for Lockfile {
     fn default() -> Self {
@@ -64,6 +118,8 @@ impl Lockfile {
             envname: envname.into(),
             dependencies: BTreeMap::new(),
             sha: None,
+            sha256: None,
+            build: None,
         }
     }
 
@@ -101,12 +157,21 @@ impl Lockfile {
         let deps = input::analyze(&component_dir)?;
         for name in deps.keys() {
             trace!("Populating lockfile with {}", name);
-            let deplock = Lockfile::from_input_component(name, &component_dir)?;
+            let mut deplock = Lockfile::from_input_component(name, &component_dir)?;
+            deplock.strip_build_info();
             self.dependencies.insert(name.clone(), deplock);
         }
         Ok(self)
     }
 
+    // How dependencies were built is theirs to record, and is not copied downstream
+    fn strip_build_info(&mut self) {
+        self.build = None;
+        for dep in self.dependencies.values_mut() {
+            dep.strip_build_info();
+        }
+    }
+
     /// Attach a default environment to the lockfile
     pub fn set_default_env(mut self, default: String) -> Self {
         self.defaultEnv = Some(default);
@@ -1,13 +1,15 @@
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
+    fmt,
     fs::{self, File},
     io::prelude::*,
     path::{Path, PathBuf},
     vec::Vec,
 };
 
-use super::{CliError, Environment, LalResult};
+use super::{CliError, Environment, LalResult, Limits};
 
 /// A startup helper used in a few places
 pub fn create_lal_subdir(pwd: &Path) -> LalResult<()> {
@@ -37,6 +39,101 @@ impl Default for ComponentConfiguration {
     }
 }
 
+/// A version requirement on a dependency in the manifest
+///
+/// Plain integers pin an exact version like they always have. Strings are semver
+/// requirements such as `">=41, <50"` that get resolved against the backend.
+/// Published versions are plain integers, so version `N` is matched as `N.0.0`,
+/// and ranges may only constrain the major version.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum VersionRequirement {
+    /// An exact version number
+    Exact(u32),
+    /// A semver range that needs resolving
+    Range(String),
+}
+
+// A semver range, as long as it only constrains major versions
+//
+// Anything like `^3.2` or `>=3.2` could never match, or would silently skip `3`.
+fn parse_range(s: &str) -> LalResult<VersionReq> {
+    let req = VersionReq::parse(s)?;
+    if req
+        .comparators
+        .iter()
+        .any(|c| c.minor.is_some() || c.patch.is_some())
+    {
+        return Err(CliError::MinorVersionRequirement(s.into()));
+    }
+    Ok(req)
+}
+
+impl VersionRequirement {
+    /// Parse a requirement from a string - integers become exact versions
+    pub fn parse(s: &str) -> LalResult<Self> {
+        if let Ok(n) = s.parse::<u32>() {
+            return Ok(VersionRequirement::Exact(n));
+        }
+        parse_range(s)?;
+        Ok(VersionRequirement::Range(s.into()))
+    }
+
+    /// Whether a published version satisfies this requirement
+    pub fn matches(&self, version: u32) -> bool {
+        match *self {
+            VersionRequirement::Exact(v) => v == version,
+            VersionRequirement::Range(ref r) => parse_range(r)
+                .map(|req| req.matches(&Version::new(version.into(), 0, 0)))
+                .unwrap_or(false),
+        }
+    }
+
+    /// The highest of the given versions satisfying this requirement
+    pub fn resolve(&self, versions: &[u32]) -> Option<u32> {
+        versions.iter().cloned().filter(|v| self.matches(*v)).max()
+    }
+}
+
+impl fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VersionRequirement::Exact(v) => write!(f, "{}", v),
+            VersionRequirement::Range(ref r) => write!(f, "{}", r),
+        }
+    }
+}
+
+/// Scripts in `.lal/scripts` to run around lal commands
+///
+/// They are run like `lal run` in the environment of the command, with
+/// `LAL_COMPONENT`, and `LAL_CONFIG` and `BUILD_VERSION` where known, set.
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Hooks {
+    /// Before the `./BUILD` script, e.g. for code generation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preBuild: Option<String>,
+    /// After a successful build, once `ARTIFACT` is made for release builds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postBuild: Option<String>,
+    /// Before each release build is published
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prePublish: Option<String>,
+    /// After `INPUT` was fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postFetch: Option<String>,
+}
+
+impl Hooks {
+    fn is_empty(&self) -> bool {
+        self.preBuild.is_none()
+            && self.postBuild.is_none()
+            && self.prePublish.is_none()
+            && self.postFetch.is_none()
+    }
+}
+
 /// Representation of `manifest.json`
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -52,9 +149,15 @@ pub struct Manifest {
     /// Components and their available configurations that are buildable
     pub components: BTreeMap<String, ComponentConfiguration>,
     /// Dependencies that are always needed
-    pub dependencies: BTreeMap<String, u32>,
+    pub dependencies: BTreeMap<String, VersionRequirement>,
     /// Development dependencies
-    pub devDependencies: BTreeMap<String, u32>,
+    pub devDependencies: BTreeMap<String, VersionRequirement>,
+    /// Resource limits the component needs in each environment
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub limits: BTreeMap<String, Limits>,
+    /// Scripts to run around lal commands
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
 
     /// Internal path of this manifest
     #[serde(skip_serializing, skip_deserializing)]
@@ -122,10 +225,10 @@ impl Manifest {
     }
 
     /// Merge dependencies and devDependencies into one convenience map
-    pub fn all_dependencies(&self) -> BTreeMap<String, u32> {
+    pub fn all_dependencies(&self) -> BTreeMap<String, VersionRequirement> {
         let mut deps = self.dependencies.clone();
         for (k, v) in &self.devDependencies {
-            deps.insert(k.clone(), *v);
+            deps.insert(k.clone(), v.clone());
         }
         deps
     }
@@ -174,14 +277,14 @@ impl Manifest {
                 return Err(CliError::InvalidBuildConfiguration(ename));
             }
         }
-        for name in self.dependencies.keys() {
+        for (name, req) in self.dependencies.iter().chain(self.devDependencies.iter()) {
             if &name.to_lowercase() != name {
                 return Err(CliError::InvalidComponentName(name.clone()));
             }
-        }
-        for name in self.devDependencies.keys() {
-            if &name.to_lowercase() != name {
-                return Err(CliError::InvalidComponentName(name.clone()));
+            if let VersionRequirement::Range(ref r) = *req {
+                if parse_range(r).is_err() {
+                    return Err(CliError::InvalidVersionRequirement(name.clone(), r.clone()));
+                }
             }
         }
         if self.supportedEnvironments.is_empty() {
@@ -1,11 +1,15 @@
 pub use self::{
     config::{config_dir, Config, ConfigDefaults, Mount},
-    container::Container,
+    container::{Container, ContainerRuntime},
     ensure::ensure_dir_exists_fresh,
     environment::Environment,
     errors::{CliError, LalResult},
-    lockfile::Lockfile,
-    manifest::{ComponentConfiguration, Manifest, ManifestLocation},
+    limits::Limits,
+    lockfile::{BuildInfo, HostInfo, Lockfile},
+    manifest::{ComponentConfiguration, Hooks, Manifest, ManifestLocation, VersionRequirement},
+    resolved::{ResolvedArtifact, ResolvedDependency, ResolvedLock},
+    rootfs::{Rootfs, RootfsSource},
+    sandbox::Sandbox,
     sticky::StickyOptions,
 };
 
@@ -14,7 +18,11 @@ mod container;
 mod ensure;
 mod environment;
 mod errors;
+mod limits;
 mod lockfile;
+mod resolved;
+mod rootfs;
+mod sandbox;
 mod sticky;
 
 /// Manifest module can be used directly
@@ -25,3 +33,12 @@ pub mod input;
 
 /// Simple OUTPUT folder helper module
 pub mod output;
+
+/// Checksum helpers for verifying artifact integrity
+pub mod checksum;
+
+/// Build cache helpers for skipping unchanged builds
+pub mod buildcache;
+
+/// Signing of releases and verification of published artifacts
+pub mod signing;
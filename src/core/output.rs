@@ -1,10 +1,18 @@
 use flate2::{write::GzEncoder, Compression};
-use std::{fs::File, path::Path};
+use std::{
+    fs::{self, File},
+    path::Path,
+};
 use tar::Builder;
 
 use super::LalResult;
 
+/// Name of the log `lal build` writes in `OUTPUT`, which is never tarred up
+pub const BUILD_LOG: &str = ".lal-build.log";
+
 /// Helper for stash and build
+///
+/// The build log is left out, so that it is never shipped to anyone depending on the component.
 pub fn tar(component_dir: &Path, tarball: &Path) -> LalResult<()> {
     info!("Taring OUTPUT");
 
@@ -18,7 +26,20 @@ pub fn tar(component_dir: &Path, tarball: &Path) -> LalResult<()> {
     // will fail with a NotFound error.
     archive.follow_symlinks(false);
 
-    archive.append_dir_all(".", component_dir.join("OUTPUT"))?;
+    let output = component_dir.join("OUTPUT");
+    archive.append_dir(".", &output)?;
+    for entry in fs::read_dir(&output)? {
+        let entry = entry?;
+        if entry.file_name() == BUILD_LOG {
+            continue;
+        }
+        let name = Path::new(".").join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            archive.append_dir_all(&name, entry.path())?;
+        } else {
+            archive.append_path_with_name(entry.path(), &name)?;
+        }
+    }
 
     Ok(())
 }
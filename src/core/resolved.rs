@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::prelude::*,
+    path::{Path, PathBuf},
+};
+
+use super::{manifest::create_lal_subdir, LalResult, VersionRequirement};
+use crate::storage::Component;
+
+/// Where a resolved dependency was fetched from in one environment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResolvedArtifact {
+    /// Location of the tarball on the backend
+    pub location: String,
+    /// SHA-256 digest of the tarball
+    pub sha256: Option<String>,
+}
+
+/// What a single manifest requirement was resolved to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResolvedDependency {
+    /// Requirement in the manifest at the time of resolution
+    pub requirement: VersionRequirement,
+    /// Version the requirement was pinned to
+    pub version: u32,
+    /// The fetched artifacts of this version by environment name
+    #[serde(default)]
+    pub environments: BTreeMap<String, ResolvedArtifact>,
+}
+
+/// Representation of `.lal/lock.json`
+///
+/// Unlike the `Lockfile` written by builds, this records what `lal fetch` resolved
+/// the requirements in the manifest to, so that ranges resolve the same way next time,
+/// along with where and what was fetched in every environment.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ResolvedLock {
+    /// Pinned versions of the dependencies in the manifest
+    pub dependencies: BTreeMap<String, ResolvedDependency>,
+}
+
+impl ResolvedLock {
+    /// Location of the resolved lock for a component
+    pub fn path(component_dir: &Path) -> PathBuf {
+        component_dir.join(".lal").join("lock.json")
+    }
+
+    /// Read the resolved lock in a component dir (empty if not created yet)
+    pub fn read(component_dir: &Path) -> LalResult<Self> {
+        let pth = ResolvedLock::path(component_dir);
+        if !pth.exists() {
+            return Ok(ResolvedLock::default());
+        }
+        let mut data = String::new();
+        File::open(&pth)?.read_to_string(&mut data)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Write the resolved lock to a component dir
+    pub fn write(&self, component_dir: &Path) -> LalResult<()> {
+        create_lal_subdir(component_dir)?;
+        let pth = ResolvedLock::path(component_dir);
+        let encoded = serde_json::to_string_pretty(self)?;
+        let mut f = File::create(&pth)?;
+        writeln!(f, "{}", encoded)?;
+        debug!("Wrote resolved lock {}: \n{}", pth.display(), encoded);
+        Ok(())
+    }
+
+    /// Pin a dependency requirement to a version
+    ///
+    /// Artifacts recorded for a previously pinned version are dropped.
+    pub fn pin(&mut self, name: &str, requirement: VersionRequirement, version: u32) {
+        match self.dependencies.get_mut(name) {
+            Some(dep) if dep.version == version => dep.requirement = requirement,
+            _ => {
+                self.dependencies.insert(name.into(), ResolvedDependency {
+                    requirement,
+                    version,
+                    environments: BTreeMap::new(),
+                });
+            }
+        }
+    }
+
+    /// Record the artifact fetched for a pinned dependency in an environment
+    pub fn record(&mut self, component: &Component, env: &str) {
+        if let Some(dep) = self.dependencies.get_mut(&component.name) {
+            if dep.version == component.version {
+                dep.environments.insert(env.into(), ResolvedArtifact {
+                    location: component.location.clone(),
+                    sha256: component.sha256.clone(),
+                });
+            }
+        }
+    }
+
+    /// The artifact recorded for a pinned dependency in an environment
+    pub fn artifact(&self, name: &str, env: &str) -> Option<&ResolvedArtifact> {
+        self.dependencies.get(name).and_then(|d| d.environments.get(env))
+    }
+
+    /// The pinned version of a dependency if it was resolved from the same requirement
+    pub fn pinned(&self, name: &str, requirement: &VersionRequirement) -> Option<u32> {
+        self.dependencies
+            .get(name)
+            .filter(|d| &d.requirement == requirement && requirement.matches(d.version))
+            .map(|d| d.version)
+    }
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf};
+
+fn default_rootfs_env() -> String {
+    "rootfs".into()
+}
+
+/// Where the filesystem of a `Rootfs` environment comes from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum RootfsSource {
+    /// A rootfs tarball or an OCI image layout directory on disk
+    Path(PathBuf),
+    /// A component published through the backend with the rootfs as its `OUTPUT`
+    Component {
+        /// Name of the component
+        component: String,
+        /// Version to use - the latest one in the cache if left out
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<u32>,
+        /// Environment the component was published in
+        #[serde(default = "default_rootfs_env")]
+        environment: String,
+    },
+}
+
+/// Representation of an unpacked root filesystem to run commands in
+///
+/// The filesystem is unpacked once into `~/.lal/cache/rootfs/<digest>` and
+/// used through a throwaway overlay, so it is never modified by builds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Rootfs {
+    /// The filesystem to unpack
+    #[serde(rename = "rootfs")]
+    pub source: RootfsSource,
+    /// Keep the host network rather than an isolated loopback only one
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub network: bool,
+}
+
+impl fmt::Display for Rootfs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            RootfsSource::Path(p) => write!(f, "rootfs {}", p.display()),
+            RootfsSource::Component {
+                component,
+                version: Some(v),
+                environment,
+            } => write!(f, "rootfs {}={} ({})", component, v, environment),
+            RootfsSource::Component {
+                component,
+                version: None,
+                environment,
+            } => write!(f, "rootfs {} ({})", component, environment),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Representation of a native environment isolated with Linux namespaces
+///
+/// Only the component directory, its `INPUT` (read-only) and the allowlisted
+/// host paths (read-only) are visible to commands run in it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Sandbox {
+    /// Host paths to expose read-only, e.g. `/usr` and `/lib`
+    #[serde(rename = "sandbox")]
+    pub paths: Vec<String>,
+    /// Keep the host network rather than an isolated loopback only one
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub network: bool,
+}
+
+impl fmt::Display for Sandbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sandbox [{}]", self.paths.join(", "))?;
+        if self.network {
+            write!(f, " with network")?;
+        }
+        Ok(())
+    }
+}
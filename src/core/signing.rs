@@ -0,0 +1,157 @@
+use openssl::{
+    pkey::{HasPublic, PKey, PKeyRef, Public},
+    sign::{Signer, Verifier},
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path};
+
+use super::{CliError, Config, LalResult};
+
+/// Identifier of a key - the hex encoded sha256 of its DER public key
+pub fn key_id<T: HasPublic>(key: &PKeyRef<T>) -> LalResult<String> {
+    let der = key
+        .public_key_to_der()
+        .map_err(|e| CliError::SigningFailure(e.to_string()))?;
+    Ok(format!("{:x}", Sha256::digest(&der)))
+}
+
+/// Read a lockfile as is, so fields unknown to this version are kept
+fn read_lockfile(path: &Path) -> LalResult<Value> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// The bytes that are signed - the name, version, environment and tarball sha256 of a release
+///
+/// Built from those fields alone so that re-serializing the lockfile never changes them.
+fn payload(lock: &Value) -> Option<Vec<u8>> {
+    let field = |key: &str| lock[key].as_str();
+    Some(
+        format!(
+            "lal-release\n{}\n{}\n{}\n{}\n",
+            field("name")?,
+            field("version")?,
+            field("envname")?,
+            field("sha256")?
+        )
+        .into_bytes(),
+    )
+}
+
+/// Sign a release lockfile in place with an ed25519 private key in PEM format
+///
+/// Returns the id of the key used.
+pub fn sign(lockfile: &Path, key: &Path) -> LalResult<String> {
+    let pem = fs::read(key).map_err(|e| CliError::SigningFailure(format!("{}: {}", key.display(), e)))?;
+    let key = PKey::private_key_from_pem(&pem).map_err(|e| {
+        CliError::SigningFailure(format!("{} is not a PEM private key: {}", key.display(), e))
+    })?;
+
+    let mut lock = read_lockfile(lockfile)?;
+    let data = payload(&lock).ok_or_else(|| {
+        CliError::SigningFailure("the release needs a name, version, envname and sha256 to sign".into())
+    })?;
+    let signature = Signer::new_without_digest(&key)
+        .and_then(|mut s| s.sign_oneshot_to_vec(&data))
+        .map_err(|e| CliError::SigningFailure(format!("key must be ed25519: {}", e)))?;
+    let id = key_id(&key)?;
+    lock["signature"] = serde_json::json!({
+        "key": id,
+        "value": base64::encode(&signature),
+    });
+    fs::write(lockfile, serde_json::to_string_pretty(&lock)?)?;
+    Ok(id)
+}
+
+/// Which published artifacts to accept, based on their lockfile signatures
+pub struct SignaturePolicy {
+    keys: Vec<(String, PKey<Public>)>,
+    strict: bool,
+}
+
+impl SignaturePolicy {
+    /// A policy trusting the given PEM public keys
+    ///
+    /// With `strict`, artifacts that are not signed by one of them are refused.
+    pub fn new(trusted_keys: &[String], strict: bool) -> LalResult<Self> {
+        let mut keys = vec![];
+        for pem in trusted_keys {
+            let key = PKey::public_key_from_pem(pem.as_bytes()).map_err(|e| {
+                CliError::InvalidSignature("config".into(), format!("bad trusted key: {}", e))
+            })?;
+            keys.push((key_id(&key)?, key));
+        }
+        Ok(SignaturePolicy { keys, strict })
+    }
+
+    /// The policy set in the config, if it sets one
+    pub fn from_config(cfg: &Config) -> LalResult<Option<Self>> {
+        if cfg.trusted_keys.is_empty() && !cfg.require_signatures {
+            return Ok(None);
+        }
+        Ok(Some(Self::new(&cfg.trusted_keys, cfg.require_signatures)?))
+    }
+
+    /// Check the lockfile published next to a tarball, whose sha256 was already verified
+    ///
+    /// A signed lockfile must also be for the requested name, version and environment.
+    pub fn verify(&self, lockfile: &Path, name: &str, version: u32, env: &str) -> LalResult<()> {
+        let fail = |reason: &str| Err(CliError::InvalidSignature(name.into(), reason.into()));
+        let lock = if lockfile.is_file() {
+            read_lockfile(lockfile)?
+        } else {
+            Value::Null
+        };
+        let (id, value) = match (
+            lock["signature"]["key"].as_str(),
+            lock["signature"]["value"].as_str(),
+        ) {
+            (Some(id), Some(value)) => (id, value),
+            _ if self.strict => return fail("it is not signed"),
+            _ => {
+                debug!("{} is not signed", name);
+                return Ok(());
+            }
+        };
+        let data = match payload(&lock) {
+            Some(data) => data,
+            None => return fail("the signed lockfile has no name, version, envname or sha256"),
+        };
+        let release = (
+            lock["name"].as_str(),
+            lock["version"].as_str(),
+            lock["envname"].as_str(),
+        );
+        if release != (Some(name), Some(version.to_string().as_str()), Some(env)) {
+            return fail(&format!(
+                "the lockfile is for {}={} in {}, not {}={} in {}",
+                release.0.unwrap_or_default(),
+                release.1.unwrap_or_default(),
+                release.2.unwrap_or_default(),
+                name,
+                version,
+                env
+            ));
+        }
+        let key = match self.keys.iter().find(|(k, _)| k == id) {
+            Some((_, key)) => key,
+            None if self.strict => return fail(&format!("key {} is not trusted", id)),
+            None => {
+                warn!("{} is signed by untrusted key {}", name, id);
+                return Ok(());
+            }
+        };
+        let signature = match base64::decode(value) {
+            Ok(s) => s,
+            Err(_) => return fail("the signature is not base64"),
+        };
+        let valid = Verifier::new_without_digest(key)
+            .and_then(|mut v| v.verify_oneshot(&signature, &data))
+            .unwrap_or(false);
+        if !valid {
+            return fail(&format!("bad signature by key {}", id));
+        }
+        debug!("Verified signature of {} by key {}", name, id);
+        Ok(())
+    }
+}
@@ -1,9 +1,20 @@
 use std::{path::Path, process::Command, vec::Vec};
 
-use super::{CliError, Config, Environment, LalResult, Manifest, StickyOptions};
+use super::{
+    prepare_rootfs, CachedBackend, CliError, Config, Environment, LalResult, Manifest, RootfsSource,
+    StickyOptions,
+};
 
 /// Pull the current environment from docker
-pub fn update(component_dir: &Path, environment: &Environment, env: &str) -> LalResult<()> {
+///
+/// Rootfs environments are instead fetched through the backend if published,
+/// and unpacked into the cache.
+pub async fn update(
+    component_dir: &Path,
+    environment: &Environment,
+    env: &str,
+    backend: &dyn CachedBackend,
+) -> LalResult<()> {
     info!("Updating {} container", env);
 
     match environment {
@@ -19,7 +30,20 @@ pub fn update(component_dir: &Path, environment: &Environment, env: &str) -> Lal
                 return Err(CliError::SubprocessFailure(s.code().unwrap_or(1001)));
             }
         }
-        Environment::None => {}
+        Environment::Rootfs(rootfs) => {
+            if let RootfsSource::Component {
+                component,
+                version,
+                environment,
+            } = &rootfs.source
+            {
+                backend
+                    .retrieve_published_component(component, *version, environment)
+                    .await?;
+            }
+            prepare_rootfs(&backend.get_cache_dir(), rootfs)?;
+        }
+        Environment::Sandbox(_) | Environment::None => {}
     }
     Ok(())
 }
@@ -1,6 +1,7 @@
-use std::{fs, path::Path};
+use futures::stream::{self, StreamExt};
+use std::{collections::BTreeMap, fs, path::Path};
 
-use super::{CliError, LalResult, Lockfile, Manifest};
+use super::{CliError, LalResult, Lockfile, Manifest, ResolvedLock};
 use crate::storage::CachedBackend;
 
 fn clean_input(component_dir: &Path) -> LalResult<()> {
@@ -16,12 +17,24 @@ fn clean_input(component_dir: &Path) -> LalResult<()> {
 ///
 /// This will read, and HTTP GET all the dependencies at the specified versions.
 /// If the `core` bool is set, then `devDependencies` are not installed.
+///
+/// Version ranges are resolved against the backend the first time they are seen,
+/// and pinned in `.lal/lock.json` along with the location and digest of what was
+/// fetched, so that later fetches install the same versions.
+///
+/// If `locked` is set, nothing is resolved - everything must already be pinned
+/// in `.lal/lock.json` for `env`, and every tarball must match the pinned digest.
+///
+/// Up to `jobs` dependencies are downloaded and unpacked concurrently.
+/// If any of them fail, every failure is reported and `INPUT` is cleaned out.
 pub async fn fetch(
     component_dir: &Path,
     manifest: &Manifest,
     backend: &dyn CachedBackend,
     core: bool,
     env: &str,
+    jobs: usize,
+    locked: bool,
 ) -> LalResult<()> {
     // first ensure manifest is sane:
     manifest.verify()?;
@@ -32,12 +45,36 @@ pub async fn fetch(
     );
 
     // create the joined hashmap of dependencies and possibly devdependencies
-    let mut deps = manifest.dependencies.clone();
+    let mut reqs = manifest.dependencies.clone();
     if !core {
         for (k, v) in &manifest.devDependencies {
-            deps.insert(k.clone(), *v);
+            reqs.insert(k.clone(), v.clone());
+        }
+    }
+
+    // resolve requirements to versions - reusing what was pinned before
+    let mut lock = ResolvedLock::read(component_dir)?;
+    let mut environments = manifest.supportedEnvironments.clone();
+    if !environments.iter().any(|e| e == env) {
+        environments.push(env.into());
+    }
+    let mut deps = BTreeMap::new();
+    for (name, req) in reqs {
+        let version = match lock.pinned(&name, &req) {
+            Some(v) => v,
+            None if locked => return Err(CliError::UnlockedDependency(name)),
+            None => backend.resolve_version(&name, &req, environments.clone()).await?,
+        };
+        lock.pin(&name, req, version);
+        // the exact artifact must be known up front to be able to verify it
+        if locked && lock.artifact(&name, env).is_none() {
+            return Err(CliError::UnlockedDependency(name));
         }
+        deps.insert(name, version);
     }
+    let all_deps = manifest.all_dependencies();
+    lock.dependencies.retain(|name, _| all_deps.contains_key(name));
+
     let mut extraneous = vec![]; // stuff we should remove
 
     // figure out what we have already
@@ -56,8 +93,10 @@ pub async fn fetch(
         if let Some(&cand) = deps.get(&name) {
             // version found in manifest
             // ignore non-integer versions (stashed things must be overwritten)
+            // and only if we know what was fetched there
+            // NB: never when locked, as INPUT has no digest to check against the lock
             if let Ok(n) = d.version.parse::<u32>() {
-                if n == cand && d.envname == env {
+                if !locked && n == cand && d.envname == env && lock.artifact(&name, env).is_some() {
                     info!("Reuse {} {} {}", env, name, n);
                     deps.remove(&name);
                 }
@@ -67,12 +106,9 @@ pub async fn fetch(
         }
     }
 
-    let mut err = None;
-    for (k, v) in deps {
-        info!("Fetch {} {} {}", env, k, v);
-
-        // first kill the folders we actually need to fetch:
-        let cmponent_dir = component_dir.join("./INPUT").join(&k);
+    // first kill the folders we actually need to fetch:
+    for k in deps.keys() {
+        let cmponent_dir = component_dir.join("./INPUT").join(k);
         if cmponent_dir.is_dir() {
             // Don't think this can fail, but we are dealing with NFS
             fs::remove_dir_all(&cmponent_dir).map_err(|e| {
@@ -81,16 +117,35 @@ pub async fn fetch(
                 e
             })?;
         }
+    }
+
+    // then fetch up to `jobs` components at a time
+    let results = stream::iter(deps.into_iter().map(|(k, v)| async move {
+        info!("Fetch {} {} {}", env, k, v);
+        let res = backend
+            .unpack_published_component(component_dir, &k, Some(v), env)
+            .await;
+        (k, res)
+    }))
+    .buffered(jobs.max(1))
+    .collect::<Vec<_>>()
+    .await;
 
-        let _ = backend
-            .unpack_published_component(&component_dir, &k, Some(v), env)
-            .await
-            .map_err(|e| {
-                warn!("Failed to completely install {} ({})", k, e);
-                // likely symlinks inside tarball that are being dodgy
-                // this is why we clean_input
-                err = Some(e);
-            });
+    let mut failures = vec![];
+    for (k, res) in results {
+        match res {
+            Ok(c) => {
+                if let Some(locked_sha) = lock.artifact(&k, env).and_then(|a| a.sha256.clone()) {
+                    let found = c.sha256.clone().unwrap_or_default();
+                    if locked && locked_sha != found {
+                        failures.push((k, CliError::ChecksumMismatch(c.name, locked_sha, found)));
+                        continue;
+                    }
+                }
+                lock.record(&c, env);
+            }
+            Err(e) => failures.push((k, e)),
+        }
     }
 
     // remove extraneous deps
@@ -102,10 +157,21 @@ pub async fn fetch(
         }
     }
 
-    if err.is_some() {
+    if !failures.is_empty() {
+        warn!("Failed to completely install {} component(s):", failures.len());
+        for (k, e) in &failures {
+            // likely symlinks inside tarball that are being dodgy
+            // this is why we clean_input
+            warn!("- {} ({})", k, e);
+        }
         warn!("Cleaning potentially broken INPUT");
         clean_input(&component_dir)?; // don't want to risk having users in corrupted states
         return Err(CliError::InstallFailure);
     }
+
+    // nothing can have changed when locked
+    if !locked {
+        lock.write(component_dir)?;
+    }
     Ok(())
 }
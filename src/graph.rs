@@ -0,0 +1,205 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use super::{LalResult, Lockfile, Manifest};
+
+/// Output formats of `lal graph`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Nodes and edges as JSON
+    Json,
+    /// A Mermaid flowchart
+    Mermaid,
+}
+
+/// A component in the dependency graph
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GraphNode {
+    /// Component name
+    pub name: String,
+    /// Versions of it found in the tree (empty for the root)
+    pub versions: BTreeSet<String>,
+    /// Environments those versions were built in
+    pub environments: BTreeSet<String>,
+    /// Whether more than one version of it is used
+    pub conflict: bool,
+}
+
+/// A dependency of one component on a version of another
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GraphEdge {
+    /// Name of the dependant
+    pub from: String,
+    /// Name of the dependency
+    pub to: String,
+    /// Version of the dependency used
+    pub version: String,
+    /// Whether the dependency is used in more than one version
+    pub conflict: bool,
+}
+
+/// The full dependency DAG of a component
+#[derive(Serialize, Debug)]
+pub struct DependencyGraph {
+    /// Name of the component at the top
+    pub root: String,
+    /// Every component, sorted by name
+    pub nodes: Vec<GraphNode>,
+    /// Every dependency, sorted by the names and version
+    pub edges: Vec<GraphEdge>,
+}
+
+// Collect every edge under a lockfile
+fn collect_edges(lf: &Lockfile, edges: &mut BTreeSet<(String, String, String)>) {
+    for (name, sublock) in &lf.dependencies {
+        edges.insert((lf.name.clone(), name.clone(), sublock.version.clone()));
+        collect_edges(sublock, edges);
+    }
+}
+
+impl DependencyGraph {
+    /// Build the graph of a populated lockfile
+    pub fn new(lf: &Lockfile) -> Self {
+        let versions = lf.find_all_dependency_versions();
+        let environments = lf.find_all_environments();
+        let conflicted = |name: &str| versions.get(name).map(|v| v.len() > 1).unwrap_or(false);
+
+        let mut nodes = vec![GraphNode {
+            name: lf.name.clone(),
+            versions: BTreeSet::new(),
+            environments: BTreeSet::new(),
+            conflict: false,
+        }];
+        let sorted: BTreeMap<_, _> = versions.iter().collect();
+        for (name, vers) in sorted {
+            if name == &lf.name {
+                continue; // a cycle through the root would not build anyway
+            }
+            nodes.push(GraphNode {
+                name: name.clone(),
+                versions: vers.clone(),
+                environments: environments.get(name).cloned().unwrap_or_default(),
+                conflict: vers.len() > 1,
+            });
+        }
+
+        let mut edge_set = BTreeSet::new();
+        collect_edges(lf, &mut edge_set);
+        let edges = edge_set
+            .into_iter()
+            .map(|(from, to, version)| GraphEdge {
+                conflict: conflicted(&to),
+                from,
+                to,
+                version,
+            })
+            .collect();
+
+        DependencyGraph {
+            root: lf.name.clone(),
+            nodes,
+            edges,
+        }
+    }
+
+    fn label(node: &GraphNode) -> String {
+        if node.versions.is_empty() {
+            return node.name.clone();
+        }
+        let versions: Vec<_> = node.versions.iter().cloned().collect();
+        let envs: Vec<_> = node.environments.iter().cloned().collect();
+        format!("{}\\n{} ({})", node.name, versions.join(", "), envs.join(", "))
+    }
+
+    /// Render as a Graphviz digraph, with conflicts in red
+    pub fn to_dot(&self) -> String {
+        let mut out = format!("digraph \"{}\" {{\n", self.root);
+        for node in &self.nodes {
+            let style = if node.conflict {
+                ", color=red, fontcolor=red"
+            } else {
+                ""
+            };
+            out += &format!(
+                "  \"{}\" [label=\"{}\"{}];\n",
+                node.name,
+                Self::label(node),
+                style
+            );
+        }
+        for edge in &self.edges {
+            let style = if edge.conflict {
+                ", color=red, fontcolor=red"
+            } else {
+                ""
+            };
+            out += &format!(
+                "  \"{}\" -> \"{}\" [label=\"{}\"{}];\n",
+                edge.from, edge.to, edge.version, style
+            );
+        }
+        out += "}\n";
+        out
+    }
+
+    /// Render as a Mermaid flowchart, with conflicts in red
+    pub fn to_mermaid(&self) -> String {
+        // component names are not valid mermaid ids, so number them
+        let ids: BTreeMap<&str, String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.name.as_str(), format!("n{}", i)))
+            .collect();
+        let mut out = String::from("graph TD\n");
+        out += "  classDef conflict stroke:#d00,color:#d00\n";
+        for node in &self.nodes {
+            let label = Self::label(node).replace("\\n", "<br/>");
+            out += &format!("  {}[\"{}\"]\n", ids[node.name.as_str()], label);
+            if node.conflict {
+                out += &format!("  class {} conflict\n", ids[node.name.as_str()]);
+            }
+        }
+        for edge in &self.edges {
+            out += &format!(
+                "  {} -->|{}| {}\n",
+                ids[edge.from.as_str()],
+                edge.version,
+                ids[edge.to.as_str()]
+            );
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            if edge.conflict {
+                out += &format!("  linkStyle {} stroke:#d00,color:#d00\n", i);
+            }
+        }
+        out
+    }
+
+    /// Render in a format
+    pub fn render(&self, format: GraphFormat) -> LalResult<String> {
+        Ok(match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            GraphFormat::Mermaid => self.to_mermaid(),
+        })
+    }
+}
+
+/// Prints the full dependency graph of `./INPUT` to stdout
+///
+/// Unlike `lal status`, every component in the tree is a single node with the
+/// versions and environments it is used in. Dependencies used in more than one
+/// version are highlighted, both as nodes and as the edges leading to them.
+pub fn graph(component_dir: &Path, manifest: &Manifest, format: GraphFormat) -> LalResult<()> {
+    let lf = Lockfile::default()
+        .set_name(&manifest.name)
+        .populate_from_input(component_dir)?;
+    print!("{}", DependencyGraph::new(&lf).render(format)?);
+    Ok(())
+}
@@ -44,20 +44,22 @@ pub mod propagate;
 // lift most other pub functions into our libraries main scope
 // this avoids having to type lal:build in tests and main.rs
 pub use crate::{
-    build::{build, BuildOptions},
+    build::{build, build_all, build_matrix, matrix_builds, BuildAllOptions, BuildOptions},
     clean::clean,
     configure::configure,
     export::export,
     fetch::fetch,
+    graph::{graph, DependencyGraph, GraphEdge, GraphFormat, GraphNode},
     init::init,
     publish::publish,
     query::query,
+    rdeps::{rdeps, reverse_dependencies, Dependant, ReverseDependencies},
     remove::remove,
-    shell::{run, script, shell},
+    shell::{hook, run, script, shell},
     stash::stash,
     status::status,
     update::{update, update_all},
-    verify::verify,
+    verify::{verify, verify_report, VerifyFinding, VerifyReport},
 };
 
 mod build;
@@ -65,9 +67,11 @@ mod clean;
 mod configure;
 mod export;
 mod fetch;
+mod graph;
 mod init;
 mod publish;
 mod query;
+mod rdeps;
 mod remove;
 mod shell;
 mod stash;
@@ -1,52 +1,60 @@
 /// This file contains all the hidden `lal list-*` subcommands
 /// If you are looking for `lal ls` go to status.rs
+///
+/// With `json_output` the names are printed as a JSON array instead,
+/// and environments as the map of their configured settings.
 use super::{Config, LalResult, Manifest};
 
-/// Print the buildable components from the `Manifest`
-pub fn buildables(manifest: &Manifest) -> LalResult<()> {
-    for k in manifest.components.keys() {
-        println!("{}", k);
+/// Print one name per line, or all of them as a JSON array
+fn print_names<'a, I>(names: I, json_output: bool) -> LalResult<()>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    if json_output {
+        let names = names.into_iter().collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&names)?);
+    } else {
+        for k in names {
+            println!("{}", k);
+        }
     }
     Ok(())
 }
 
+/// Print the buildable components from the `Manifest`
+pub fn buildables(manifest: &Manifest, json_output: bool) -> LalResult<()> {
+    print_names(manifest.components.keys(), json_output)
+}
+
 /// Print the supported environments from the `Manifest`
-pub fn supported_environments(manifest: &Manifest) -> LalResult<()> {
-    for env in &manifest.supportedEnvironments {
-        println!("{}", env);
-    }
-    Ok(())
+pub fn supported_environments(manifest: &Manifest, json_output: bool) -> LalResult<()> {
+    print_names(&manifest.supportedEnvironments, json_output)
 }
 
 /// Print the available configurations for a buildable Component
-pub fn configurations(component: &str, manifest: &Manifest) -> LalResult<()> {
-    let component_settings = match manifest.components.get(component) {
-        Some(c) => c,
-        None => return Ok(()), // invalid component - but this is for completion
-    };
-    for c in &component_settings.configurations {
-        println!("{}", c);
+pub fn configurations(component: &str, manifest: &Manifest, json_output: bool) -> LalResult<()> {
+    match manifest.components.get(component) {
+        Some(c) => print_names(&c.configurations, json_output),
+        // invalid component - but this is for completion
+        None => print_names(std::iter::empty(), json_output),
     }
-    Ok(())
 }
 
 /// Print the configured environments from the config
-pub fn environments(cfg: &Config) -> LalResult<()> {
-    for k in cfg.environments.keys() {
-        println!("{}", k);
+pub fn environments(cfg: &Config, json_output: bool) -> LalResult<()> {
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&cfg.environments)?);
+        return Ok(());
     }
-    Ok(())
+    print_names(cfg.environments.keys(), false)
 }
 
 /// Print the dependencies from the manifest
-pub fn dependencies(mf: &Manifest, core: bool) -> LalResult<()> {
+pub fn dependencies(mf: &Manifest, core: bool, json_output: bool) -> LalResult<()> {
     let deps = if core {
         mf.dependencies.clone()
     } else {
         mf.all_dependencies()
     };
-    for k in deps.keys() {
-        println!("{}", k);
-    }
-    Ok(())
+    print_names(deps.keys(), json_output)
 }
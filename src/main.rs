@@ -3,22 +3,75 @@
 
 use clap::ArgMatches;
 use lal::{self, *};
-use std::{env::current_dir, ops::Deref, path::Path, process};
+use std::{
+    collections::BTreeMap,
+    env::current_dir,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-fn result_exit<T>(name: &str, x: LalResult<T>) {
-    let _ = x.map_err(|e| {
-        println!(); // add a separator
+// Set from `--output json` before any command runs
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+fn fail(name: &str, e: CliError) -> ! {
+    if json_output() {
+        // stdout only ever holds json in this mode, the log still goes to stderr
+        let err = serde_json::json!({
+            "error": {
+                "command": name,
+                "category": e.category(),
+                "code": e.exit_code(),
+                "message": e.to_string(),
+            }
+        });
+        println!("{}", serde_json::to_string_pretty(&err).unwrap());
         error!("{} error: {}", name, e);
-        debug!("{}: {:?}", name, e); // in the off-chance that Debug is useful
-        process::exit(1);
-    });
+        process::exit(e.exit_code());
+    }
+    println!(); // add a separator
+    error!("{} error: {}", name, e);
+    debug!("{}: {:?}", name, e); // in the off-chance that Debug is useful
+    process::exit(1);
+}
+
+fn result_exit<T>(name: &str, x: LalResult<T>) {
+    if let Err(e) = x {
+        fail(name, e);
+    }
     process::exit(0);
 }
 
+// Verify signatures of retrieved components when the config asks for it
+fn with_policy<B>(backend: B, policy: Option<signing::SignaturePolicy>) -> Box<dyn CachedBackend>
+where
+    B: Backend + Send + Sync + 'static,
+{
+    match policy {
+        Some(policy) => Box::new(TrustedBackend::new(backend, policy)),
+        None => Box::new(backend),
+    }
+}
+
 fn get_backend(config: &Config) -> LalResult<Box<dyn CachedBackend>> {
-    let backend: Box<dyn CachedBackend> = match config.backend {
-        BackendConfiguration::Artifactory(ref cfg) => Box::new(ArtifactoryBackend::new(&cfg, &config.cache)?),
-        BackendConfiguration::Local(ref cfg) => Box::new(LocalBackend::new(&cfg, &config.cache)?),
+    let policy = signing::SignaturePolicy::from_config(config)?;
+    if config.offline {
+        return Ok(with_policy(OfflineBackend::new(&config.cache), policy));
+    }
+    let backend = match config.backend {
+        BackendConfiguration::Artifactory(ref cfg) => {
+            with_policy(ArtifactoryBackend::new(cfg, &config.cache)?, policy)
+        }
+        BackendConfiguration::Local(ref cfg) => with_policy(LocalBackend::new(cfg, &config.cache)?, policy),
+        BackendConfiguration::S3(ref cfg) => with_policy(S3Backend::new(cfg, &config.cache)?, policy),
+        BackendConfiguration::Http(ref cfg) => with_policy(HttpBackend::new(cfg, &config.cache)?, policy),
+        BackendConfiguration::Oci(ref cfg) => with_policy(OciBackend::new(cfg, &config.cache)?, policy),
+        BackendConfiguration::Chain(ref cfg) => with_policy(ChainBackend::new(cfg, &config.cache)?, policy),
     };
 
     Ok(backend)
@@ -31,28 +84,58 @@ async fn handle_manifest_agnostic_cmds(
     component_dir: &Path,
     backend: &dyn CachedBackend,
     explicit_env: Option<&str>,
-) -> LalResult<()> {
+) {
     let res = if let Some(a) = args.subcommand_matches("export") {
-        let curdir = current_dir()?;
-        lal::export(backend, a.value_of("component").unwrap(), &curdir, explicit_env).await
+        let dir = match a.value_of("output-dir") {
+            Some(dir) => Ok(PathBuf::from(dir)),
+            None => current_dir(),
+        };
+        match dir {
+            Ok(dir) => lal::export(backend, a.value_of("component").unwrap(), &dir, explicit_env).await,
+            Err(e) => Err(e.into()),
+        }
     } else if let Some(a) = args.subcommand_matches("query") {
         lal::query(
             backend,
             explicit_env,
             a.value_of("component").unwrap(),
             a.is_present("latest"),
+            json_output(),
         )
         .await
+    } else if let Some(a) = args.subcommand_matches("rdeps") {
+        let jobs = value_t!(a.value_of("jobs"), usize).unwrap_or(cfg.jobs);
+        lal::rdeps(backend, explicit_env, a.value_of("component").unwrap(), jobs).await
     } else if let Some(a) = args.subcommand_matches("publish") {
         lal::publish(None, &component_dir, a.value_of("component").unwrap(), backend).await
+    } else if let Some(a) = args
+        .subcommand_matches("propagate")
+        .filter(|a| a.is_present("root"))
+    {
+        // validated by clap
+        let (root, version) = a.value_of("root").unwrap().split_once('=').unwrap();
+        match (explicit_env, version.parse()) {
+            (Some(env), Ok(version)) => {
+                lal::propagate::print_published(
+                    backend,
+                    root,
+                    version,
+                    env,
+                    a.value_of("component").unwrap(),
+                    a.is_present("json") || json_output(),
+                    cfg.jobs,
+                )
+                .await
+            }
+            (Some(_), Err(e)) => Err(e.into()),
+            (None, _) => Err(CliError::EnvironmentUnspecified),
+        }
     } else if args.subcommand_matches("list-environments").is_some() {
-        lal::list::environments(cfg)
+        lal::list::environments(cfg, json_output())
     } else {
-        return Ok(());
+        return;
     };
     result_exit(args.subcommand_name().unwrap(), res);
-
-    Ok(())
 }
 
 // functions that need a manifest, but do not depend on environment values
@@ -63,21 +146,39 @@ fn handle_environment_agnostic_cmds(
     backend: &dyn CachedBackend,
 ) {
     let res = if let Some(a) = args.subcommand_matches("status") {
-        lal::status(
-            &component_dir,
+        let res = lal::status(
+            component_dir,
             mf,
             a.is_present("full"),
             a.is_present("origin"),
             a.is_present("time"),
-        )
+            json_output(),
+        );
+        match res {
+            // already part of the printed status, so stdout must not get a second document
+            Err(e @ (CliError::MissingDependencies | CliError::ExtraneousDependencies(_)))
+                if json_output() =>
+            {
+                error!("status error: {}", e);
+                process::exit(e.exit_code());
+            }
+            res => res,
+        }
+    } else if let Some(a) = args.subcommand_matches("graph") {
+        let format = match a.value_of("format") {
+            Some("json") => GraphFormat::Json,
+            Some("mermaid") => GraphFormat::Mermaid,
+            _ => GraphFormat::Dot,
+        };
+        lal::graph(component_dir, mf, format)
     } else if args.subcommand_matches("list-components").is_some() {
-        lal::list::buildables(mf)
+        lal::list::buildables(mf, json_output())
     } else if args.subcommand_matches("list-supported-environments").is_some() {
-        lal::list::supported_environments(mf)
+        lal::list::supported_environments(mf, json_output())
     } else if let Some(a) = args.subcommand_matches("list-configurations") {
-        lal::list::configurations(a.value_of("component").unwrap(), mf)
+        lal::list::configurations(a.value_of("component").unwrap(), mf, json_output())
     } else if let Some(a) = args.subcommand_matches("list-dependencies") {
-        lal::list::dependencies(mf, a.is_present("core"))
+        lal::list::dependencies(mf, a.is_present("core"), json_output())
     } else if let Some(a) = args.subcommand_matches("remove") {
         let xs = a
             .values_of("components")
@@ -93,12 +194,15 @@ fn handle_environment_agnostic_cmds(
         )
     } else if let Some(a) = args.subcommand_matches("stash") {
         lal::stash(&component_dir, backend, mf, a.value_of("name").unwrap())
-    } else if let Some(a) = args.subcommand_matches("propagate") {
+    } else if let Some(a) = args
+        .subcommand_matches("propagate")
+        .filter(|a| !a.is_present("execute"))
+    {
         lal::propagate::print(
             &component_dir,
             mf,
             a.value_of("component").unwrap(),
-            a.is_present("json"),
+            a.is_present("json") || json_output(),
         )
     } else {
         return;
@@ -106,13 +210,45 @@ fn handle_environment_agnostic_cmds(
     result_exit(args.subcommand_name().unwrap(), res);
 }
 
+// Checkouts and state for `lal propagate --execute`
+fn propagate_options(
+    a: &ArgMatches<'_>,
+    component_dir: &Path,
+    mf: &Manifest,
+) -> LalResult<lal::propagate::ExecuteOptions> {
+    let mut checkouts = BTreeMap::new();
+    if let Some(ws) = a.value_of("workspace") {
+        checkouts = lal::propagate::workspace_checkouts(Path::new(ws))?;
+    }
+    checkouts.insert(mf.name.clone(), component_dir.to_path_buf());
+    for repo in a.values_of("repo").unwrap_or_default() {
+        // validated by clap
+        let (name, path) = repo.split_once('=').unwrap();
+        checkouts.insert(name.into(), PathBuf::from(path));
+    }
+    let state = match a.value_of("state") {
+        Some(s) => PathBuf::from(s),
+        None => component_dir.join(lal::propagate::STATE_FILE),
+    };
+    Ok(lal::propagate::ExecuteOptions {
+        checkouts,
+        state,
+        resume: a.is_present("resume"),
+    })
+}
+
 async fn handle_network_cmds(
     args: &ArgMatches<'_>,
     component_dir: &Path,
     mf: &Manifest,
+    cfg: &Config,
     backend: &dyn CachedBackend,
     env: &str,
+    environment: &Environment,
 ) {
+    // --jobs overrides the concurrency limit from the config
+    let jobs = |a: &ArgMatches<'_>| value_t!(a.value_of("jobs"), usize).unwrap_or(cfg.jobs);
+
     let res = if let Some(a) = args.subcommand_matches("update") {
         let xs = a
             .values_of("components")
@@ -127,6 +263,7 @@ async fn handle_network_cmds(
             a.is_present("save"),
             a.is_present("savedev"),
             env,
+            jobs(a),
         )
         .await
     } else if let Some(a) = args.subcommand_matches("update-all") {
@@ -137,39 +274,80 @@ async fn handle_network_cmds(
             a.is_present("save"),
             a.is_present("dev"),
             env,
+            jobs(a),
         )
         .await
     } else if let Some(a) = args.subcommand_matches("fetch") {
-        lal::fetch(&component_dir, mf, backend, a.is_present("core"), env).await
+        lal::fetch(
+            component_dir,
+            mf,
+            backend,
+            a.is_present("core"),
+            env,
+            jobs(a),
+            a.is_present("locked"),
+        )
+        .await
+        .and_then(|_| {
+            let modes = ShellModes {
+                limits: cfg.get_limits(env, mf),
+                ..Default::default()
+            };
+            let vars = [("LAL_COMPONENT", mf.name.clone())];
+            let post_fetch = mf.hooks.postFetch.as_deref();
+            lal::hook(
+                cfg,
+                environment,
+                &modes,
+                "postFetch",
+                post_fetch,
+                &vars,
+                component_dir,
+            )
+        })
+    } else if let Some(a) = args.subcommand_matches("propagate") {
+        match propagate_options(a, component_dir, mf) {
+            Ok(opts) => {
+                let component = a.value_of("component").unwrap();
+                lal::propagate::execute(component_dir, mf, cfg, None, backend, env, component, &opts).await
+            }
+            Err(e) => Err(e),
+        }
     } else {
         return; // not a network cmnd
     };
     result_exit(args.subcommand_name().unwrap(), res)
 }
 
-fn handle_env_command(
+async fn handle_env_command(
     args: &ArgMatches<'_>,
     component_dir: &Path,
     cfg: &Config,
     mf: &Manifest,
     env: &str,
     stickies: &StickyOptions,
+    backend: &dyn CachedBackend,
 ) -> Environment {
     // lookup associated container from
     let environment = mf
         .get_environment(env)
         .or_else(|_| cfg.get_environment(env))
-        .map_err(|e| {
+        .unwrap_or_else(|e| {
+            if json_output() {
+                fail("env", e);
+            }
             error!("Environment error: {}", e);
             println!("Ensure that manifest.environment has a corresponding entry in ~/.lal/config");
             process::exit(1);
-        })
-        .unwrap();
+        });
 
     // resolve env updates and sticky options before main subcommands
     if let Some(a) = args.subcommand_matches("env") {
         if a.subcommand_matches("update").is_some() {
-            result_exit("env update", lal::env::update(&component_dir, &environment, env))
+            result_exit(
+                "env update",
+                lal::env::update(component_dir, &environment, env, backend).await,
+            )
         } else if a.subcommand_matches("reset").is_some() {
             // NB: if .lal/opts.env points at an environment not in config
             // reset will fail.. possible to fix, but complects this file too much
@@ -187,6 +365,11 @@ fn handle_env_command(
                     sa.value_of("environment").unwrap(),
                 ),
             )
+        } else if json_output() {
+            // the current environment along with what it resolved to
+            let current = serde_json::json!({ "name": env, "environment": environment });
+            let res = serde_json::to_string_pretty(&current).map_err(CliError::from);
+            result_exit("env", res.map(|s| println!("{}", s)))
         } else {
             // just print current environment
             println!("{}", env);
@@ -199,20 +382,25 @@ fn handle_env_command(
 }
 
 #[cfg(feature = "upgrade")]
-fn handle_upgrade(args: &ArgMatches, cfg: &Config) {
+async fn handle_upgrade(args: &ArgMatches<'_>, cfg: &Config) {
     // we have a subcommand because SubcommandRequiredElseHelp
     let subname = args.subcommand_name().unwrap();
 
     // Allow lal upgrade without manifest
     if args.subcommand_matches("upgrade").is_some() {
-        result_exit("upgrade", lal::upgrade(false)); // explicit, verbose check
+        result_exit("upgrade", lal::upgrade(false).await); // explicit, verbose check
     }
 
     // Autoupgrade if enabled - runs once daily if enabled
     // also excluding all listers because they are used in autocomplete
-    if cfg.autoupgrade && subname != "upgrade" && !subname.contains("list-") && cfg.upgrade_check_time() {
+    if cfg.autoupgrade
+        && !cfg.offline
+        && subname != "upgrade"
+        && !subname.contains("list-")
+        && cfg.upgrade_check_time()
+    {
         debug!("Performing daily upgrade check");
-        let _ = lal::upgrade(false).map_err(|e| {
+        let _ = lal::upgrade(false).await.map_err(|e| {
             error!("Daily upgrade check failed: {}", e);
             // don't halt here if this ever happens as it could break it for users
         });
@@ -224,17 +412,43 @@ fn handle_upgrade(args: &ArgMatches, cfg: &Config) {
     }
 }
 
-fn handle_docker_cmds(
+/// Per-invocation limits from the command line
+fn timeout(a: &ArgMatches<'_>) -> Limits {
+    Limits {
+        timeout: value_t!(a.value_of("timeout"), u64).ok(),
+        ..Default::default()
+    }
+}
+
+// All findings of `lal verify` as json, exiting with the code of the first one
+fn print_verify_report(report: LalResult<VerifyReport>) -> ! {
+    let report = report.unwrap_or_else(|e| fail("verify", e));
+    match serde_json::to_string_pretty(&report) {
+        Ok(s) => println!("{}", s),
+        Err(e) => fail("verify", e.into()),
+    }
+    for f in &report.findings {
+        error!("verify error: {} check failed: {}", f.check, f.message);
+    }
+    process::exit(report.exit_code());
+}
+
+async fn handle_docker_cmds(
     args: &ArgMatches<'_>,
     component_dir: &Path,
     mf: &Manifest,
     cfg: &Config,
     env: &str,
     environment: &Environment,
+    backend: &dyn CachedBackend,
 ) {
     let res = if let Some(a) = args.subcommand_matches("verify") {
         // not really a docker related command, but it needs
         // the resolved env to verify consistent dependency usage
+        if json_output() {
+            let report = lal::verify_report(component_dir, mf, env, a.is_present("simple"));
+            print_verify_report(report);
+        }
         lal::verify(&component_dir, mf, env, a.is_present("simple"))
     } else if let Some(a) = args.subcommand_matches("build") {
         let bopts = BuildOptions {
@@ -246,14 +460,28 @@ fn handle_docker_cmds(
             environment: environment.clone(),
             force: a.is_present("force"),
             simple_verify: a.is_present("simple-verify"),
+            no_cache: a.is_present("no-cache"),
         };
         let modes = ShellModes {
             printonly: a.is_present("print"),
             x11_forwarding: a.is_present("x11"),
             host_networking: a.is_present("net-host"),
             env_vars: values_t!(a.values_of("env-var"), String).unwrap_or_default(),
+            log: None,
+            limits: timeout(a),
         };
-        lal::build(&component_dir, cfg, mf, &bopts, env.into(), modes)
+        if a.is_present("all") {
+            let all = BuildAllOptions {
+                all_configurations: a.value_of("configs") == Some("all"),
+                keep_going: a.is_present("keep-going"),
+            };
+            lal::build_all(component_dir, cfg, mf, &bopts, &all, env.into(), modes)
+        } else if a.is_present("matrix") {
+            let keep_going = a.is_present("keep-going");
+            lal::build_matrix(component_dir, cfg, mf, &bopts, keep_going, backend, modes).await
+        } else {
+            lal::build(component_dir, cfg, mf, &bopts, env.into(), modes)
+        }
     } else if let Some(a) = args.subcommand_matches("shell") {
         let xs = if a.is_present("cmd") {
             Some(a.values_of("cmd").unwrap().collect::<Vec<_>>())
@@ -265,6 +493,8 @@ fn handle_docker_cmds(
             x11_forwarding: a.is_present("x11"),
             host_networking: a.is_present("net-host"),
             env_vars: values_t!(a.values_of("env-var"), String).unwrap_or_default(),
+            log: None,
+            limits: timeout(a).or(Some(&cfg.get_limits(env, mf))),
         };
         lal::shell(
             cfg,
@@ -285,6 +515,8 @@ fn handle_docker_cmds(
             x11_forwarding: a.is_present("x11"),
             host_networking: a.is_present("net-host"),
             env_vars: values_t!(a.values_of("env-var"), String).unwrap_or_default(),
+            log: None,
+            limits: timeout(a).or(Some(&cfg.get_limits(env, mf))),
         };
         lal::script(
             cfg,
@@ -305,6 +537,7 @@ fn handle_docker_cmds(
 async fn main() -> LalResult<()> {
     let app = lal::app::new();
     let args = app.get_matches();
+    JSON_OUTPUT.store(args.value_of("output") == Some("json"), Ordering::Relaxed);
 
     // by default, always show INFO messages for now (+1)
     loggerv::Logger::new()
@@ -319,12 +552,16 @@ async fn main() -> LalResult<()> {
     }
 
     // Force config to exists before allowing remaining actions
-    let config = Config::read(None).map_err(|e| {
+    let mut config = Config::read(None).map_err(|e| {
+        if json_output() {
+            fail("config", e);
+        }
         error!("Configuration error: {}", e);
         println!();
         println!("If you have just installed or upgraded, run `lal configure`");
         e
     })?;
+    config.offline |= args.is_present("offline");
 
     // Create a storage backend (something that implements storage/traits.rs)
     let backend: Box<dyn CachedBackend> = get_backend(&config)?;
@@ -334,7 +571,7 @@ async fn main() -> LalResult<()> {
 
     // Do upgrade checks or handle explicit `lal upgrade` here
     #[cfg(feature = "upgrade")]
-    handle_upgrade(&args, &config);
+    handle_upgrade(&args, &config).await;
 
     let component_dir = current_dir()?;
     // Allow lal init / clean without manifest existing in PWD
@@ -356,6 +593,9 @@ async fn main() -> LalResult<()> {
 
     // Read .lal/opts if it exists
     let stickies = StickyOptions::read(&component_dir).map_err(|e| {
+        if json_output() {
+            fail("options", e);
+        }
         // Should not happen unless people are mucking with it manually
         error!("Options error: {}", e);
         println!(".lal/opts must be valid json");
@@ -366,20 +606,24 @@ async fn main() -> LalResult<()> {
     let explicit_env = args.value_of("environment");
     if let Some(env) = explicit_env {
         config.get_environment(env).map_err(|e| {
+            if json_output() {
+                fail("env", e);
+            }
             error!("Environment error: {}", e);
             e
         })?;
     }
-    handle_manifest_agnostic_cmds(&args, &config, &component_dir, backend.deref(), explicit_env).await?;
+    handle_manifest_agnostic_cmds(&args, &config, &component_dir, backend.deref(), explicit_env).await;
 
     // Force manifest to exist before allowing remaining actions
-    let manifest = Manifest::read(&component_dir)
-        .map_err(|e| {
-            error!("Manifest error: {}", e);
-            println!("Ensure manifest.json is valid json or run `lal init`");
-            process::exit(1);
-        })
-        .unwrap();
+    let manifest = Manifest::read(&component_dir).unwrap_or_else(|e| {
+        if json_output() {
+            fail("manifest", e);
+        }
+        error!("Manifest error: {}", e);
+        println!("Ensure manifest.json is valid json or run `lal init`");
+        process::exit(1);
+    });
 
     // Subcommands that are environment agnostic
     handle_environment_agnostic_cmds(&args, &component_dir, &manifest, backend.deref());
@@ -393,7 +637,16 @@ async fn main() -> LalResult<()> {
     } else {
         manifest.environment.clone()
     };
-    let environment = handle_env_command(&args, &component_dir, &config, &manifest, &env, &stickies);
+    let environment = handle_env_command(
+        &args,
+        &component_dir,
+        &config,
+        &manifest,
+        &env,
+        &stickies,
+        backend.deref(),
+    )
+    .await;
 
     // Warn users who are using an unsupported environment
     let sub = args.subcommand_name().unwrap();
@@ -409,8 +662,26 @@ async fn main() -> LalResult<()> {
     }
 
     // Main subcommands
-    handle_network_cmds(&args, &component_dir, &manifest, backend.deref(), &env).await;
-    handle_docker_cmds(&args, &component_dir, &manifest, &config, &env, &environment);
+    handle_network_cmds(
+        &args,
+        &component_dir,
+        &manifest,
+        &config,
+        backend.deref(),
+        &env,
+        &environment,
+    )
+    .await;
+    handle_docker_cmds(
+        &args,
+        &component_dir,
+        &manifest,
+        &config,
+        &env,
+        &environment,
+        backend.deref(),
+    )
+    .await;
 
     unreachable!("Subcommand valid, but not implemented");
 }
@@ -1,9 +1,19 @@
-use super::{LalResult, Lockfile, Manifest};
-use serde::Serialize;
-use std::{collections::BTreeSet, path::Path};
+use super::{
+    build, fetch, publish, update, BuildOptions, CachedBackend, CliError, Config, LalResult, Lockfile,
+    Manifest, ShellModes,
+};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+};
 
 /// A single update of of a propagation
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SingleUpdate {
     /// Where to update dependencies
     pub repo: String,
@@ -12,14 +22,14 @@ pub struct SingleUpdate {
 }
 
 /// A parallelizable update stage of a propagation
-#[derive(Serialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct UpdateStage {
     /// Updates to perform at this stage
     pub updates: Vec<SingleUpdate>,
 }
 
 /// A set of sequential update steps that describe a propagation
-#[derive(Serialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct UpdateSequence {
     /// Update stages needed
     pub stages: Vec<UpdateStage>,
@@ -81,12 +91,83 @@ pub fn compute(lf: &Lockfile, component: &str) -> LalResult<UpdateSequence> {
 pub fn print(component_dir: &Path, manifest: &Manifest, component: &str, json_output: bool) -> LalResult<()> {
     debug!("Calculating update path for {}", component);
 
-    // TODO: allow taking a custom lockfile to be used outside a repo.
     let lf = Lockfile::default()
         .set_name(&manifest.name)
         .populate_from_input(&component_dir)?;
 
-    let result = compute(&lf, component)?;
+    print_sequence(&lf, component, json_output)
+}
+
+/// Fetch a published lockfile through the backend, without its tarball
+pub(crate) async fn fetch_lockfile(
+    backend: &dyn CachedBackend,
+    name: &str,
+    version: u32,
+    env: &str,
+) -> LalResult<Lockfile> {
+    let location = backend.get_lockfile_location(name, version, env);
+    let dest = tempfile::NamedTempFile::new()?;
+    backend.raw_fetch(&location, dest.path()).await?;
+    Lockfile::from_path(dest.path(), name)
+}
+
+/// The lockfile of a published component, as if its dependencies were fetched
+///
+/// Like `populate_from_input` in a checkout of it, the dependencies are the
+/// published lockfiles of what it was built against. No tarballs are downloaded.
+pub async fn published_lockfile(
+    backend: &dyn CachedBackend,
+    name: &str,
+    version: u32,
+    env: &str,
+    jobs: usize,
+) -> LalResult<Lockfile> {
+    debug!("Fetching published lockfile of {}={}", name, version);
+    let mut lf = fetch_lockfile(backend, name, version, env).await?;
+
+    let fetched = stream::iter(lf.dependencies.iter().map(|(dep, lock)| async move {
+        // stashed dependencies are not published, so keep what was recorded for them
+        let res = match lock.version.parse::<u32>() {
+            Ok(v) => fetch_lockfile(backend, dep, v, env).await.map(Some),
+            Err(_) => Ok(None),
+        };
+        (dep.clone(), res)
+    }))
+    .buffered(jobs.max(1))
+    .collect::<Vec<_>>()
+    .await;
+    for (dep, res) in fetched {
+        if let Some(deplock) = res? {
+            lf.dependencies.insert(dep, deplock);
+        }
+    }
+    Ok(lf)
+}
+
+/// Outputs the update path to a published component for a specific component
+///
+/// This is `print` for a root component that is not checked out, with the
+/// dependency tree taken from the published lockfiles of `root=version` instead.
+/// Up to `jobs` lockfiles are fetched concurrently.
+pub async fn print_published(
+    backend: &dyn CachedBackend,
+    root: &str,
+    version: u32,
+    env: &str,
+    component: &str,
+    json_output: bool,
+    jobs: usize,
+) -> LalResult<()> {
+    debug!(
+        "Calculating update path for {} from {}={}",
+        component, root, version
+    );
+    let lf = published_lockfile(backend, root, version, env, jobs).await?;
+    print_sequence(&lf, component, json_output)
+}
+
+fn print_sequence(lf: &Lockfile, component: &str, json_output: bool) -> LalResult<()> {
+    let result = compute(lf, component)?;
 
     if json_output {
         let encoded = serde_json::to_string_pretty(&result)?;
@@ -105,3 +186,289 @@ pub fn print(component_dir: &Path, manifest: &Manifest, component: &str, json_ou
 
     Ok(())
 }
+
+/// Where `lal propagate --execute` keeps its progress by default
+pub const STATE_FILE: &str = ".lal/propagate.json";
+
+/// Progress of a propagation, saved after every stage of `execute`
+#[derive(Serialize, Deserialize)]
+pub struct PropagationState {
+    /// Component being propagated
+    pub component: String,
+    /// Environment the propagation builds and publishes in
+    pub environment: String,
+    /// The stages computed when the propagation started
+    pub sequence: UpdateSequence,
+    /// Repositories done so far, with the versions they were published as
+    pub published: BTreeMap<String, u32>,
+}
+
+impl PropagationState {
+    /// Read a saved state
+    pub fn read(path: &Path) -> LalResult<PropagationState> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Save the state, creating its directory if needed
+    pub fn write(&self, path: &Path) -> LalResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Settings for `lal propagate --execute`
+pub struct ExecuteOptions {
+    /// Local checkout of every repository to propagate through
+    pub checkouts: BTreeMap<String, PathBuf>,
+    /// Where to save progress
+    pub state: PathBuf,
+    /// Continue from the saved state instead of starting over
+    pub resume: bool,
+}
+
+/// Find the checkouts in a workspace directory, by the names in their manifests
+pub fn workspace_checkouts(workspace: &Path) -> LalResult<BTreeMap<String, PathBuf>> {
+    let mut checkouts = BTreeMap::new();
+    for entry in fs::read_dir(workspace)? {
+        let path = entry?.path();
+        if let Ok(manifest) = Manifest::read(&path) {
+            checkouts.insert(manifest.name, path);
+        }
+    }
+    Ok(checkouts)
+}
+
+/// A repository that is updated and ready to build
+struct Prepared {
+    repo: String,
+    dir: PathBuf,
+    manifest: Manifest,
+    options: BuildOptions,
+    version: u32,
+}
+
+/// The commit checked out in a repository, if it is a git checkout
+///
+/// Propagation edits `manifest.json` without committing it, so the revision is
+/// suffixed with `-dirty` when tracked files differ from the commit.
+fn revision(dir: &Path) -> Option<String> {
+    let out = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        warn!("Could not find the git revision of {}", dir.display());
+        return None;
+    }
+    let sha = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    let changes = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !changes.stdout.is_empty() {
+        return Some(format!("{}-dirty", sha));
+    }
+    Some(sha)
+}
+
+/// Update the dependencies of a repository and pick the version to publish it as
+async fn prepare(
+    step: &SingleUpdate,
+    dir: &Path,
+    cfg: &Config,
+    backend: &dyn CachedBackend,
+    env: &str,
+) -> LalResult<Prepared> {
+    let manifest = Manifest::read(dir)?;
+    fetch(dir, &manifest, backend, false, env, cfg.jobs, false).await?;
+    let deps = step.dependencies.clone();
+    update(dir, &manifest, backend, deps, true, false, env, cfg.jobs).await?;
+    let manifest = Manifest::read(dir)?;
+
+    let version = backend
+        .get_versions(&manifest.name, env)
+        .await?
+        .into_iter()
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let environment = manifest
+        .get_environment(env)
+        .or_else(|_| cfg.get_environment(env))?;
+    let options = BuildOptions {
+        name: None,
+        configuration: None,
+        environment,
+        release: true,
+        version: Some(version.to_string()),
+        sha: revision(dir),
+        force: false,
+        simple_verify: false,
+        no_cache: false,
+    };
+    Ok(Prepared {
+        repo: step.repo.clone(),
+        dir: dir.to_path_buf(),
+        manifest,
+        options,
+        version,
+    })
+}
+
+/// Performs a propagation in local checkouts of every repository involved
+///
+/// For each stage of the update sequence, every repository in it has its
+/// dependencies fetched, the propagated ones updated with `--save`, and is then
+/// built for release and published as the next version available in `env`.
+/// The repositories of a stage are updated one at a time, as they can share
+/// dependencies in the cache, then built in parallel and published concurrently.
+///
+/// Progress is saved after each stage. If anything fails, the stage is finished
+/// and the propagation stops, to be continued with `resume` once fixed.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    component_dir: &Path,
+    manifest: &Manifest,
+    cfg: &Config,
+    home: Option<&Path>,
+    backend: &dyn CachedBackend,
+    env: &str,
+    component: &str,
+    opts: &ExecuteOptions,
+) -> LalResult<()> {
+    let mut state = if opts.resume {
+        let state = PropagationState::read(&opts.state)?;
+        if state.component != component {
+            return Err(CliError::PropagationStateMismatch(
+                state.component,
+                component.into(),
+            ));
+        }
+        info!(
+            "Resuming propagation of {} after {} repos",
+            component,
+            state.published.len()
+        );
+        state
+    } else {
+        if opts.state.exists() {
+            warn!("Discarding saved propagation state in {}", opts.state.display());
+        }
+        let lf = Lockfile::default()
+            .set_name(&manifest.name)
+            .populate_from_input(component_dir)?;
+        PropagationState {
+            component: component.into(),
+            environment: env.into(),
+            sequence: compute(&lf, component)?,
+            published: BTreeMap::new(),
+        }
+    };
+    let env = state.environment.clone();
+
+    // check every checkout is there before changing anything
+    for stage in &state.sequence.stages {
+        for update in &stage.updates {
+            if !opts.checkouts.contains_key(&update.repo) {
+                return Err(CliError::MissingCheckout(update.repo.clone()));
+            }
+        }
+    }
+    state.write(&opts.state)?;
+
+    for (i, stage) in state.sequence.stages.clone().into_iter().enumerate() {
+        let todo: Vec<_> = stage
+            .updates
+            .iter()
+            .filter(|u| !state.published.contains_key(&u.repo))
+            .collect();
+        if todo.is_empty() {
+            continue;
+        }
+        info!("Stage {}: propagating through {} repos", i + 1, todo.len());
+        let mut failures = vec![];
+
+        // updates share the dependency cache, so they run one at a time
+        let mut ready = vec![];
+        for u in todo {
+            match prepare(u, &opts.checkouts[&u.repo], cfg, backend, &env).await {
+                Ok(p) => ready.push(p),
+                Err(e) => {
+                    error!("Failed to update {}: {}", u.repo, e);
+                    failures.push(u.repo.clone());
+                }
+            }
+        }
+
+        // builds block, so they get a thread each, and none can take the terminal
+        let batch = Config {
+            interactive: false,
+            ..cfg.clone()
+        };
+        let built = thread::scope(|s| {
+            let handles: Vec<_> = ready
+                .iter()
+                .map(|p| {
+                    let (env, cfg) = (&env, &batch);
+                    s.spawn(move || {
+                        info!("Building {}={}", p.repo, p.version);
+                        let modes = ShellModes::default();
+                        build(&p.dir, cfg, &p.manifest, &p.options, env.clone(), modes)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("build thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        let mut done = vec![];
+        for (p, res) in ready.into_iter().zip(built) {
+            match res {
+                Ok(()) => done.push(p),
+                Err(e) => {
+                    error!("Failed to build {}: {}", p.repo, e);
+                    failures.push(p.repo);
+                }
+            }
+        }
+
+        let published = stream::iter(done.into_iter().map(|p| async move {
+            let res = publish(home, &p.dir, &p.manifest.name, backend).await;
+            (p, res)
+        }))
+        .buffered(cfg.jobs.max(1))
+        .collect::<Vec<_>>()
+        .await;
+        for (p, res) in published {
+            match res {
+                Ok(()) => {
+                    info!("Published {}={}", p.repo, p.version);
+                    state.published.insert(p.repo, p.version);
+                }
+                Err(e) => {
+                    error!("Failed to publish {}: {}", p.repo, e);
+                    failures.push(p.repo);
+                }
+            }
+        }
+
+        state.write(&opts.state)?;
+        if !failures.is_empty() {
+            failures.sort();
+            return Err(CliError::PropagationFailures(failures));
+        }
+    }
+
+    println!("Propagated {} through:", component);
+    for (repo, version) in &state.published {
+        println!("- {}={}", repo, version);
+    }
+    fs::remove_file(&opts.state)?;
+    Ok(())
+}
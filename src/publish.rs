@@ -1,19 +1,18 @@
-use std::path::Path;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
 
 // Need both the struct and the trait
-use super::{CliError, LalResult, Lockfile};
+use super::{
+    checksum, hook, matrix_builds, signing, CliError, Config, LalResult, Lockfile, Manifest, ShellModes,
+};
 use crate::storage::CachedBackend;
 
-/// Publish a release build to the storage backend
+/// Check a release build in `component_dir/ARTIFACT` before publishing it
 ///
-/// Meant to be done after a `lal build -r <component>`
-/// and requires publish credentials in the local `Config`.
-pub async fn publish(
-    home: Option<&Path>,
-    component_dir: &Path,
-    name: &str,
-    backend: &dyn CachedBackend,
-) -> LalResult<()> {
+/// Returns the version and environment to publish it as.
+fn check_release(component_dir: &Path, name: &str) -> LalResult<(u32, String)> {
     let artdir = component_dir.join("./ARTIFACT");
     let tarball = artdir.join(format!("{}.tar.gz", name));
     if !artdir.is_dir() || !tarball.exists() {
@@ -33,13 +32,120 @@ pub async fn publish(
         warn!("Release build not done --with-sha=$(git rev-parse HEAD)");
     }
 
+    // make sure ARTIFACT was not touched since the build
+    if let Some(ref sha256) = lock.sha256 {
+        checksum::verify_sha256(&tarball, sha256, name)?;
+    } else {
+        warn!("Release build has no sha256 - consumers will not be able to verify it");
+    }
+
     // always publish to the environment in the lockfile
-    let envname = lock.envname;
+    Ok((version, lock.envname))
+}
 
-    info!("Publishing {}={} to {}", name, version, envname);
-    backend
-        .publish_artifact(home, &component_dir, name, version, &envname)
-        .await?;
+/// Check that matrix releases cover every supported environment at the same version
+///
+/// A `--keep-going` matrix build can leave releases for only some of them.
+fn check_matrix(manifest: &Manifest, releases: &[(PathBuf, u32, String)]) -> LalResult<()> {
+    let missing = manifest
+        .supportedEnvironments
+        .iter()
+        .filter(|e| !releases.iter().any(|(_, _, env)| env == *e))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(CliError::IncompleteMatrix(format!(
+            "no release build for {}",
+            missing.join(", ")
+        )));
+    }
+    let versions = releases.iter().map(|(_, v, _)| *v).collect::<BTreeSet<_>>();
+    if versions.len() > 1 {
+        return Err(CliError::IncompleteMatrix(format!(
+            "release builds have different versions {:?}",
+            versions
+        )));
+    }
+    Ok(())
+}
+
+/// Publish a release build to the storage backend
+///
+/// Meant to be done after a `lal build -r <component>`
+/// and requires publish credentials in the local `Config`.
+/// Without an `./ARTIFACT`, the release builds of a `lal build --matrix`
+/// are published instead, once they have all been checked and found to cover
+/// every supported environment at one version. The `prePublish`
+/// hook of the manifest runs for each of them before that.
+/// With a `signing_key` in the `Config`, each release lockfile is signed
+/// right before its upload.
+pub async fn publish(
+    home: Option<&Path>,
+    component_dir: &Path,
+    name: &str,
+    backend: &dyn CachedBackend,
+) -> LalResult<()> {
+    let mut dirs = vec![component_dir.to_path_buf()];
+    let mut is_matrix = false;
+    if !component_dir.join("ARTIFACT").is_dir() {
+        let matrix = matrix_builds(component_dir, name)?;
+        if !matrix.is_empty() {
+            dirs = matrix;
+            is_matrix = true;
+        }
+    }
+
+    let cfg = Config::read(home)?;
+
+    // hooks go first so that checks catch anything they break
+    if let Ok(manifest) = Manifest::read(component_dir) {
+        if let Some(pre_publish) = manifest.hooks.prePublish.as_deref() {
+            for dir in &dirs {
+                let lock = Lockfile::release_build(dir)?;
+                let environment = manifest
+                    .get_environment(lock.envname.clone())
+                    .or_else(|_| cfg.get_environment(lock.envname.clone()))?;
+                let vars = [
+                    ("LAL_COMPONENT", name.to_string()),
+                    ("LAL_CONFIG", lock.config.clone()),
+                    ("BUILD_VERSION", lock.version.clone()),
+                ];
+                let modes = ShellModes {
+                    limits: cfg.get_limits(&lock.envname, &manifest),
+                    ..Default::default()
+                };
+                hook(
+                    &cfg,
+                    &environment,
+                    &modes,
+                    "prePublish",
+                    Some(pre_publish),
+                    &vars,
+                    dir,
+                )?;
+            }
+        }
+    }
+
+    let mut releases = vec![];
+    for dir in dirs {
+        let (version, envname) = check_release(&dir, name)?;
+        releases.push((dir, version, envname));
+    }
+    if is_matrix {
+        check_matrix(&Manifest::read(component_dir)?, &releases)?;
+    }
+
+    for (dir, version, envname) in releases {
+        if let Some(key) = &cfg.signing_key {
+            let id = signing::sign(&dir.join("ARTIFACT").join("lockfile.json"), key)?;
+            info!("Signed {}={} with key {}", name, version, id);
+        }
+        info!("Publishing {}={} to {}", name, version, envname);
+        backend
+            .publish_artifact(home, &dir, name, version, &envname)
+            .await?;
+    }
 
     Ok(())
 }
@@ -1,14 +1,26 @@
+use serde::Serialize;
 use std::io::{self, Write};
 
 use super::{CliError, LalResult};
 use crate::storage::CachedBackend;
 
+/// Machine readable form of `lal query`
+#[derive(Serialize)]
+struct QueryOutput<'a> {
+    component: &'a str,
+    environment: &'a str,
+    versions: Vec<u32>,
+}
+
 /// Prints a list of versions associated with a component
+///
+/// With `json_output` they are printed as one JSON object, along with what was queried.
 pub async fn query(
     backend: &dyn CachedBackend,
     _env: Option<&str>,
     component: &str,
     last: bool,
+    json_output: bool,
 ) -> LalResult<()> {
     if component.to_lowercase() != component {
         return Err(CliError::InvalidComponentName(component.into()));
@@ -21,7 +33,20 @@ pub async fn query(
         Some(e) => e,
     };
 
-    if last {
+    if json_output {
+        let mut versions = if last {
+            vec![backend.get_latest_version(component, env).await?]
+        } else {
+            backend.get_versions(component, env).await?
+        };
+        versions.sort_unstable();
+        let output = QueryOutput {
+            component,
+            environment: env,
+            versions,
+        };
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else if last {
         let ver = backend.get_latest_version(component, env).await?;
         println!("{}", ver);
     } else {
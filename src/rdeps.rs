@@ -0,0 +1,210 @@
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{propagate::fetch_lockfile, CliError, LalResult};
+use crate::storage::CachedBackend;
+
+/// What the index knows about the latest version of a published component
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexEntry {
+    /// Latest version when it was scanned
+    version: u32,
+    /// Names of its direct dependencies
+    direct: BTreeSet<String>,
+    /// Every dependency in its tree, with the versions used
+    dependencies: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// The dependencies of the latest version of every component in an environment
+///
+/// Kept in `~/.lal/cache/rdeps/<env>.json` so that only the lockfiles of
+/// components with new versions are fetched again.
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    components: BTreeMap<String, IndexEntry>,
+}
+
+impl Index {
+    fn path(cache: &Path, env: &str) -> PathBuf {
+        cache.join("rdeps").join(format!("{}.json", env))
+    }
+
+    // a missing or unreadable index just means scanning everything
+    fn read(path: &Path) -> Index {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, path: &Path) -> LalResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// A published component depending on the component asked about
+#[derive(Debug, PartialEq)]
+pub struct Dependant {
+    /// Name of the dependant
+    pub name: String,
+    /// Its latest version
+    pub version: u32,
+    /// Whether it depends on the component directly
+    pub direct: bool,
+    /// Versions of the component in its dependency tree
+    pub uses: BTreeSet<String>,
+}
+
+/// The dependants of a component, and the components that could not be scanned for it
+#[derive(Debug, Default, PartialEq)]
+pub struct ReverseDependencies {
+    /// Components depending on it
+    pub dependants: Vec<Dependant>,
+    /// Components whose latest lockfile could not be read
+    pub skipped: Vec<String>,
+}
+
+// Scan the latest lockfile of a component, unless the index has it already
+async fn scan(
+    backend: &dyn CachedBackend,
+    name: &str,
+    env: &str,
+    known: Option<&IndexEntry>,
+) -> LalResult<Option<IndexEntry>> {
+    let version = match backend.get_versions(name, env).await?.into_iter().max() {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    if let Some(entry) = known.filter(|e| e.version == version) {
+        return Ok(Some(entry.clone()));
+    }
+    debug!("Scanning lockfile of {}={}", name, version);
+    let lock = fetch_lockfile(backend, name, version, env).await?;
+    Ok(Some(IndexEntry {
+        version,
+        direct: lock.dependencies.keys().cloned().collect(),
+        dependencies: lock.find_all_dependency_versions().into_iter().collect(),
+    }))
+}
+
+/// Find every published component whose latest version depends on a component
+///
+/// The latest lockfile of every component the backend has in `env` is scanned,
+/// `jobs` at a time, and both direct and transitive dependencies count. With a
+/// `version`, only dependants using that version of the component are returned.
+/// What was scanned is kept in a local index, so later queries only fetch the
+/// lockfiles of components that were published since. Components that fail to
+/// scan are returned as `skipped`, rather than hiding every other dependant.
+pub async fn reverse_dependencies(
+    backend: &dyn CachedBackend,
+    component: &str,
+    version: Option<&str>,
+    env: &str,
+    jobs: usize,
+) -> LalResult<ReverseDependencies> {
+    if component.to_lowercase() != component {
+        return Err(CliError::InvalidComponentName(component.into()));
+    }
+    let path = Index::path(&backend.get_cache_dir(), env);
+    let old = Index::read(&path);
+
+    let names = backend.list_components(env).await?;
+    let scanned = stream::iter(names.iter().map(|name| {
+        let known = old.components.get(name);
+        async move { (name, scan(backend, name, env, known).await) }
+    }))
+    .buffered(jobs.max(1))
+    .collect::<Vec<_>>()
+    .await;
+
+    let mut index = Index::default();
+    let mut result = ReverseDependencies::default();
+    for (name, res) in scanned {
+        match res {
+            Ok(Some(entry)) => {
+                index.components.insert(name.clone(), entry);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Skipping {}: {}", name, e);
+                result.skipped.push(name.clone());
+            }
+        }
+    }
+    index.write(&path)?;
+
+    for (name, entry) in index.components {
+        let uses = match entry.dependencies.get(component) {
+            Some(uses) => uses.clone(),
+            None => continue,
+        };
+        if let Some(v) = version {
+            if !uses.contains(v) {
+                continue;
+            }
+        }
+        result.dependants.push(Dependant {
+            direct: entry.direct.contains(component),
+            name,
+            version: entry.version,
+            uses,
+        });
+    }
+    Ok(result)
+}
+
+/// Prints what depends on a component across everything published
+///
+/// The component can be given as `name=version` to only print what uses that version.
+/// Fails after printing if any component could not be scanned.
+pub async fn rdeps(
+    backend: &dyn CachedBackend,
+    env: Option<&str>,
+    component: &str,
+    jobs: usize,
+) -> LalResult<()> {
+    let env = match env {
+        None => {
+            error!("rdeps needs an explicit environment");
+            return Err(CliError::EnvironmentUnspecified);
+        }
+        Some(e) => e,
+    };
+    let (name, version) = match component.split_once('=') {
+        Some((n, v)) => (n, Some(v)),
+        None => (component, None),
+    };
+
+    let result = reverse_dependencies(backend, name, version, env, jobs).await?;
+    if result.dependants.is_empty() {
+        println!("Nothing published in {} depends on {}", env, component);
+    }
+    for d in result.dependants {
+        let uses: Vec<_> = d.uses.into_iter().collect();
+        println!(
+            "{}={} ({}, uses {} {})",
+            d.name,
+            d.version,
+            if d.direct { "direct" } else { "transitive" },
+            name,
+            uses.join(", ")
+        );
+    }
+    if !result.skipped.is_empty() {
+        println!("Could not scan:");
+        for name in &result.skipped {
+            println!("- {}", name);
+        }
+        return Err(CliError::IncompleteScan(result.skipped));
+    }
+    Ok(())
+}
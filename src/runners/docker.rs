@@ -1,7 +1,12 @@
-use std::{path::Path, process::Command, vec::Vec};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    vec::Vec,
+};
 
-use super::COMMAND_LOCK;
-use crate::core::{CliError, Config, Container, LalResult};
+use super::process::{self, Stop};
+use crate::core::{CliError, Config, Container, LalResult, Limits};
 
 /// Flags for docker run that vary for different use cases
 ///
@@ -28,6 +33,10 @@ pub struct ShellModes {
     pub host_networking: bool,
     /// Environment variables
     pub env_vars: Vec<String>,
+    /// Also write everything the command prints to this file, with timestamps
+    pub log: Option<PathBuf>,
+    /// Resource limits and timeout for the command
+    pub limits: Limits,
 }
 
 /// Verifies that `id -u` and `id -g` are sane
@@ -36,14 +45,7 @@ pub struct ShellModes {
 /// so for builds to work with the default containers, user ids and group ids
 /// should match inside and outside of the container.
 fn permission_sanity_check() -> LalResult<(u32, u32)> {
-    let uid_output = Command::new("id").arg("-u").output()?;
-    let uid_str = String::from_utf8_lossy(&uid_output.stdout);
-    let uid = uid_str.trim().parse::<u32>()?; // trust `id -u` is sane
-
-    let gid_output = Command::new("id").arg("-g").output()?;
-    let gid_str = String::from_utf8_lossy(&gid_output.stdout);
-    let gid = gid_str.trim().parse::<u32>()?; // trust `id -g` is sane
-
+    let (uid, gid) = current_ids()?;
     if uid == 0 || gid == 0 {
         return Err(CliError::DockerPermissionSafety(
             "Cannot run container as root user".into(),
@@ -55,6 +57,19 @@ fn permission_sanity_check() -> LalResult<(u32, u32)> {
     Ok((uid, gid))
 }
 
+/// The output of `id -u` and `id -g`
+pub(super) fn current_ids() -> LalResult<(u32, u32)> {
+    let uid_output = Command::new("id").arg("-u").output()?;
+    let uid_str = String::from_utf8_lossy(&uid_output.stdout);
+    let uid = uid_str.trim().parse::<u32>()?; // trust `id -u` is sane
+
+    let gid_output = Command::new("id").arg("-g").output()?;
+    let gid_str = String::from_utf8_lossy(&gid_output.stdout);
+    let gid = gid_str.trim().parse::<u32>()?; // trust `id -g` is sane
+
+    Ok((uid, gid))
+}
+
 /// Gets the ID of a docker container
 ///
 /// Uses the `docker images` command to find the image ID of the specified
@@ -148,6 +163,7 @@ fn fixup_docker_container(container: &Container, u: u32, g: u32) -> LalResult<Co
     let modified_container = Container {
         name: format!("{}-u{}_g{}", container.name, u, g),
         tag: format!("from_{}", image_id),
+        runtime: container.runtime,
     };
 
     info!("Using container {}", modified_container);
@@ -173,25 +189,15 @@ fn fixup_docker_container(container: &Container, u: u32, g: u32) -> LalResult<Co
     Ok(modified_container)
 }
 
-/// Runs an arbitrary command in the configured docker container
+/// Arguments for `run` shared by all container runtimes
 ///
-/// This will mount the current directory as `~/volume` as well as a few conveniences,
-/// and absorb the `Stdio` supplied by this `Command`.
-pub fn docker_run(
+/// Everything up to the user mapping, which is specific to each runtime.
+pub(super) fn common_run_args(
     cfg: &Config,
-    container: &Container,
-    command: Vec<String>,
     flags: &DockerRunFlags,
     modes: &ShellModes,
     component_dir: &Path,
-) -> LalResult<()> {
-    debug!("Performing docker permission sanity check");
-    let (uid, gid) = permission_sanity_check()?;
-    let modified_container: LalResult<Container> = fixup_docker_container(container, uid, gid);
-
-    // Shadow container here
-    let container = modified_container.as_ref().unwrap_or(container);
-
+) -> Vec<String> {
     debug!("Finding home and cwd");
     let home = dirs::home_dir().unwrap(); // crash if no $HOME
 
@@ -236,11 +242,28 @@ pub fn docker_run(
         args.push("--privileged".into())
     }
 
+    if let Some(memory) = &modes.limits.memory {
+        args.push(format!("--memory={}", memory));
+    }
+    if let Some(cpus) = modes.limits.cpus {
+        args.push(format!("--cpus={}", cpus));
+    }
+    if let Some(pids) = modes.limits.pids {
+        args.push(format!("--pids-limit={}", pids));
+    }
+
     args.push("-w".into());
     args.push("/home/lal/volume".into());
-    args.push("--user".into());
-    args.push(format!("{}:{}", uid, gid));
+    args
+}
 
+/// Finish `run` arguments with the image and the command to run in it
+pub(super) fn push_command_args(
+    args: &mut Vec<String>,
+    container: &Container,
+    command: Vec<String>,
+    flags: &DockerRunFlags,
+) {
     // If no command, then override entrypoint to /bin/bash
     // This happens when we use `lal shell` without args
     if command.is_empty() {
@@ -253,10 +276,17 @@ pub fn docker_run(
     for c in command {
         args.push(c);
     }
+}
 
-    // run or print docker command
+/// Run or print a container runtime invocation
+pub(super) fn execute(
+    binary: &str,
+    mut args: Vec<String>,
+    modes: &ShellModes,
+    component_dir: &Path,
+) -> LalResult<()> {
     if modes.printonly {
-        print!("docker");
+        print!("{}", binary);
         for arg in args {
             if arg.contains(' ') {
                 // leave quoted args quoted
@@ -267,24 +297,53 @@ pub fn docker_run(
         }
         println!();
     } else {
-        debug!("Entering docker");
+        debug!("Entering {}", binary);
 
-        // Take hold of the mutex before changing directory, and keep it until the
-        // command has finished executing. This is probably only useful for tests
-        // which are run in threads in the same process. Since a process can only
-        // exist in a single directory, this represents a race condition.
-        // unwrap() will poison the lock on panic, failing the script. This is the
-        // desired behaviour in all cases, whether or not we are in tests.
-        let _guard = COMMAND_LOCK.lock().unwrap();
+        // a named container can be stopped cleanly when it runs out of time
+        let timeout = modes.limits.timeout().map(|t| {
+            let name = format!("lal-{:x}", rand::random::<u64>());
+            args.insert(1, format!("--name={}", name));
+            (t, Stop::Container(binary.into(), name))
+        });
 
-        let s = Command::new("docker")
-            .args(&args)
-            .current_dir(&component_dir)
-            .status()?;
-        debug!("Exited docker");
+        let mut cmd = Command::new(binary);
+        cmd.args(&args).current_dir(component_dir);
+        let s = process::status(&mut cmd, modes.log.as_deref(), timeout).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => CliError::ExecutableMissing(binary.into()),
+            io::ErrorKind::TimedOut => CliError::Timeout(modes.limits.timeout.unwrap_or_default()),
+            _ => e.into(),
+        })?;
+        debug!("Exited {}", binary);
         if !s.success() {
             return Err(CliError::SubprocessFailure(s.code().unwrap_or(1001)));
         }
     }
     Ok(())
 }
+
+/// Runs an arbitrary command in the configured docker container
+///
+/// This will mount the current directory as `~/volume` as well as a few conveniences,
+/// and absorb the `Stdio` supplied by this `Command`.
+pub fn docker_run(
+    cfg: &Config,
+    container: &Container,
+    command: Vec<String>,
+    flags: &DockerRunFlags,
+    modes: &ShellModes,
+    component_dir: &Path,
+) -> LalResult<()> {
+    debug!("Performing docker permission sanity check");
+    let (uid, gid) = permission_sanity_check()?;
+    let modified_container: LalResult<Container> = fixup_docker_container(container, uid, gid);
+
+    // Shadow container here
+    let container = modified_container.as_ref().unwrap_or(container);
+
+    let mut args = common_run_args(cfg, flags, modes, component_dir);
+    args.push("--user".into());
+    args.push(format!("{}:{}", uid, gid));
+    push_command_args(&mut args, container, command, flags);
+
+    execute("docker", args, modes, component_dir)
+}
@@ -1,12 +1,51 @@
 pub use self::{
     docker::{docker_run, DockerRunFlags, ShellModes},
     native::native_run,
+    podman::podman_run,
+    rootfs::{prepare_rootfs, rootfs_run},
+    sandbox::sandbox_run,
 };
-use std::sync::Mutex;
+use std::{path::Path, vec::Vec};
 
-lazy_static! {
-    static ref COMMAND_LOCK: Mutex<()> = Mutex::new(());
+use crate::core::{Config, Container, ContainerRuntime, LalResult};
+
+/// Only container runtimes can enforce limits besides the timeout
+fn warn_unenforced(kind: &str, modes: &ShellModes) {
+    if modes.limits.has_resources() {
+        warn!("Only the timeout is enforced in {} environments", kind);
+    }
+}
+
+/// `KEY=VALUE` pairs of the `env_vars` for runners that set them directly
+///
+/// A bare `KEY` is inherited from the host anyway.
+fn env_vars(modes: &ShellModes) -> Vec<(String, String)> {
+    modes
+        .env_vars
+        .iter()
+        .filter_map(|v| v.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Runs an arbitrary command in a container with its configured runtime
+pub fn container_run(
+    cfg: &Config,
+    container: &Container,
+    command: Vec<String>,
+    flags: &DockerRunFlags,
+    modes: &ShellModes,
+    component_dir: &Path,
+) -> LalResult<()> {
+    match container.runtime {
+        ContainerRuntime::Docker => docker_run(cfg, container, command, flags, modes, component_dir),
+        ContainerRuntime::Podman => podman_run(cfg, container, command, flags, modes, component_dir),
+    }
 }
 
 mod docker;
 mod native;
+mod podman;
+mod process;
+mod rootfs;
+mod sandbox;
@@ -1,22 +1,28 @@
-use super::COMMAND_LOCK;
+use super::{
+    env_vars,
+    process::{self, Stop},
+    warn_unenforced, ShellModes,
+};
 use crate::core::{CliError, LalResult};
-use std::{path::Path, process::Command, vec::Vec};
+use std::{io, path::Path, process::Command, vec::Vec};
 
 /// Runs an arbitrary command natively, without containerization
-pub fn native_run(mut command: Vec<String>, component_dir: &Path) -> LalResult<()> {
+///
+/// Its output is also written to the log of the `ShellModes` when set.
+pub fn native_run(mut command: Vec<String>, component_dir: &Path, modes: &ShellModes) -> LalResult<()> {
     let cmd = command.remove(0);
     let mut script_cmd = Command::new(cmd);
+    warn_unenforced("native", modes);
 
-    // Take hold of the mutex before changing directory, and keep it until the
-    // command has finished executing. This is probably only useful for tests
-    // which are run in threads in the same process. Since a process can only
-    // exist in a single directory, this represents a race condition.
-    // unwrap() will poison the lock on panic, failing the script. This is the
-    // desired behaviour in all cases, whether or not we are in tests.
-    let _guard = COMMAND_LOCK.lock().unwrap();
-
-    script_cmd.args(command).current_dir(&component_dir);
-    let s = script_cmd.status()?;
+    script_cmd
+        .args(command)
+        .envs(env_vars(modes))
+        .current_dir(component_dir);
+    let timeout = modes.limits.timeout().map(|t| (t, Stop::Group));
+    let s = process::status(&mut script_cmd, modes.log.as_deref(), timeout).map_err(|e| match e.kind() {
+        io::ErrorKind::TimedOut => CliError::Timeout(modes.limits.timeout.unwrap_or_default()),
+        _ => e.into(),
+    })?;
 
     if !s.success() {
         return Err(CliError::SubprocessFailure(s.code().unwrap_or(1001)));
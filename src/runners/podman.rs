@@ -0,0 +1,33 @@
+use std::{path::Path, vec::Vec};
+
+use super::docker::{common_run_args, current_ids, execute, push_command_args, DockerRunFlags, ShellModes};
+use crate::core::{Config, Container, LalResult};
+
+/// Runs an arbitrary command in the configured container with podman
+///
+/// Mounts and flags are the same as for `docker_run`, but rather than building a
+/// derived image with a remapped `lal` user, rootless podman keeps the host uid
+/// and gid inside the container through its user namespace.
+pub fn podman_run(
+    cfg: &Config,
+    container: &Container,
+    command: Vec<String>,
+    flags: &DockerRunFlags,
+    modes: &ShellModes,
+    component_dir: &Path,
+) -> LalResult<()> {
+    let (uid, gid) = current_ids()?;
+
+    let mut args = common_run_args(cfg, flags, modes, component_dir);
+    // keep-id only exists for rootless podman - as root the ids match anyway
+    if uid != 0 {
+        args.push("--userns=keep-id".into());
+    }
+    args.push("--user".into());
+    args.push(format!("{}:{}", uid, gid));
+    // keep-id gives the user the host home directory
+    args.push("--env=HOME=/home/lal".into());
+    push_command_args(&mut args, container, command, flags);
+
+    execute("podman", args, modes, component_dir)
+}
@@ -0,0 +1,174 @@
+use chrono::Utc;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long a stopped command gets to exit before it is killed
+const GRACE: Duration = Duration::from_secs(10);
+
+/// How long output of a stopped command is still copied before giving up on it
+const DRAIN: Duration = Duration::from_secs(1);
+
+/// How to stop a command that ran past its timeout
+pub(super) enum Stop {
+    /// Terminate the process group the command leads
+    ///
+    /// Interactive commands stay in the foreground group of the terminal,
+    /// so only the command itself is terminated for them.
+    Group,
+    /// Have the container runtime (binary) stop the named container
+    Container(String, String),
+}
+
+/// Copy lines from `src` to `term` as they come, and timestamped into `log`
+fn tee<R, W>(src: R, mut term: W, stream: &'static str, log: Arc<Mutex<File>>) -> JoinHandle<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut src = BufReader::new(src);
+        let mut line = vec![];
+        while src.read_until(b'\n', &mut line).map(|n| n > 0).unwrap_or(false) {
+            // the terminal matters more than the log, so neither may stop the other
+            let _ = term.write_all(&line).and_then(|_| term.flush());
+            let mut log = log.lock().unwrap();
+            let _ = write!(
+                log,
+                "{} [{}] ",
+                Utc::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                stream
+            );
+            let _ = log.write_all(&line);
+            if !line.ends_with(b"\n") {
+                let _ = log.write_all(b"\n");
+            }
+            line.clear();
+        }
+    })
+}
+
+/// Wait for a child for at most `limit`, returning `None` if it is still running
+fn wait_for(child: &mut Child, limit: Duration) -> io::Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        if let Some(s) = child.try_wait()? {
+            return Ok(Some(s));
+        }
+        if start.elapsed() >= limit {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Whether the command shares our terminal, and so has to stay in its foreground group
+fn interactive() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+/// Run a container runtime command on a container, returning whether it worked
+fn runtime(binary: &str, args: &[&str]) -> bool {
+    Command::new(binary)
+        .args(args)
+        .stdout(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Stop a child cleanly if possible, and kill it otherwise
+///
+/// With `grouped`, the child leads its own process group, which is stopped as a whole.
+fn stop(child: &mut Child, how: &Stop, grouped: bool) -> io::Result<()> {
+    match how {
+        Stop::Group => {
+            let pid = child.id() as libc::pid_t;
+            let target = if grouped { -pid } else { pid };
+            unsafe { libc::kill(target, libc::SIGTERM) };
+            if wait_for(child, GRACE)?.is_none() {
+                unsafe { libc::kill(target, libc::SIGKILL) };
+            }
+        }
+        Stop::Container(binary, name) => {
+            let grace = GRACE.as_secs().to_string();
+            if !runtime(binary, &["stop", "--time", &grace, name]) {
+                // killing the client alone would leave the container running
+                warn!("Failed to stop container {} - killing it", name);
+                if !runtime(binary, &["kill", name]) {
+                    warn!("Failed to kill container {}", name);
+                }
+                child.kill()?;
+            }
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Run a command to completion, teeing its output into `log` if given
+///
+/// Output still reaches the terminal unchanged, but a line at a time. If the
+/// command outlives `timeout`, it is stopped and a `TimedOut` error returned.
+pub(super) fn status(
+    cmd: &mut Command,
+    log: Option<&Path>,
+    timeout: Option<(Duration, Stop)>,
+) -> io::Result<ExitStatus> {
+    // so that whatever the command started can be stopped along with it
+    // NB: leaving the foreground group would stop reads from the terminal and Ctrl-C
+    let grouped = matches!(timeout, Some((_, Stop::Group))) && !interactive();
+    if grouped {
+        cmd.process_group(0);
+    }
+    let mut threads = vec![];
+    let mut child = match log {
+        Some(log) => {
+            let file = Arc::new(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(log)?,
+            ));
+            let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+            if let Some(s) = child.stdout.take() {
+                threads.push(tee(s, io::stdout(), "stdout", file.clone()));
+            }
+            if let Some(s) = child.stderr.take() {
+                threads.push(tee(s, io::stderr(), "stderr", file));
+            }
+            child
+        }
+        None => cmd.spawn()?,
+    };
+
+    let status = match &timeout {
+        None => Some(child.wait()?),
+        Some((limit, how)) => match wait_for(&mut child, *limit)? {
+            Some(s) => Some(s),
+            None => {
+                warn!("Stopping after the {}s timeout", limit.as_secs());
+                stop(&mut child, how, grouped)?;
+                None
+            }
+        },
+    };
+    // NB: anything the command started that survived can still hold the pipes open
+    let deadline = status.is_none().then(|| Instant::now() + DRAIN);
+    for handle in threads {
+        if let Some(deadline) = deadline {
+            while !handle.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(50));
+            }
+            if !handle.is_finished() {
+                continue; // left to copy whatever else comes, until the pipe closes
+            }
+        }
+        let _ = handle.join();
+    }
+    status.ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "timeout exceeded"))
+}
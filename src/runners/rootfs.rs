@@ -0,0 +1,234 @@
+use flate2::read::GzDecoder;
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read},
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+    vec::Vec,
+};
+use tar::Archive;
+
+use super::{
+    sandbox::{run_plan, Plan},
+    warn_unenforced, ShellModes,
+};
+use crate::core::{checksum, CliError, Config, LalResult, Rootfs, RootfsSource};
+
+/// Where the component directory is mounted, as in container environments
+const VOLUME: &str = "/home/lal/volume";
+
+/// The tarball of a published rootfs component in the cache
+fn cached_component(cache: &Path, name: &str, version: Option<u32>, env: &str) -> LalResult<PathBuf> {
+    let dir = cache.join("environments").join(env).join(name);
+    let tarball = |v: u32| dir.join(v.to_string()).join(format!("{}.tar.gz", name));
+    let version = match version {
+        Some(v) => Some(v),
+        None => fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok()?.file_name().to_str()?.parse().ok())
+            .filter(|v| tarball(*v).is_file())
+            .max(),
+    };
+    match version {
+        Some(v) if tarball(v).is_file() => Ok(tarball(v)),
+        Some(v) => Err(CliError::MissingRootfs(format!("{}={}", name, v))),
+        None => Err(CliError::MissingRootfs(name.into())),
+    }
+}
+
+fn read_json(path: &Path) -> LalResult<serde_json::Value> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| CliError::InvalidRootfs(format!("cannot read {}: {}", path.display(), e)))?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn blob_path(layout: &Path, digest: &str) -> LalResult<PathBuf> {
+    match digest.split_once(':') {
+        Some((algo, hex)) if !hex.contains('/') => Ok(layout.join("blobs").join(algo).join(hex)),
+        _ => Err(CliError::InvalidRootfs(format!("bad digest {}", digest))),
+    }
+}
+
+/// Digest of the image manifest and its layers, in order, from an OCI image layout
+fn image_layers(layout: &Path) -> LalResult<(String, Vec<PathBuf>)> {
+    let mut digest = String::new();
+    let mut doc = read_json(&layout.join("index.json"))?;
+    // follow (possibly nested) indexes down to the first manifest
+    while let Some(first) = doc["manifests"].get(0) {
+        digest = first["digest"].as_str().unwrap_or_default().to_string();
+        doc = read_json(&blob_path(layout, &digest)?)?;
+    }
+    let layers = doc["layers"]
+        .as_array()
+        .ok_or_else(|| CliError::InvalidRootfs(format!("no image manifest in {}", layout.display())))?;
+    let mut paths = vec![];
+    for layer in layers {
+        paths.push(blob_path(layout, layer["digest"].as_str().unwrap_or_default())?);
+    }
+    Ok((digest.replace(':', "-"), paths))
+}
+
+/// Remove a file or directory left by an earlier layer
+fn remove_any(path: &Path) -> LalResult<()> {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Whether `path` stays inside `root` once the symlinks in its parents are resolved
+///
+/// Earlier layers can plant symlinks pointing anywhere on the host, so nothing
+/// may be removed or changed through a path before this check. Parents that do
+/// not exist yet cannot be symlinks, so the nearest existing one is checked.
+fn inside(root: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .find_map(|p| p.canonicalize().ok())
+        .is_some_and(|real| real.starts_with(root))
+}
+
+/// Unpack a (possibly gzipped) tar layer on top of what is in `dest`
+///
+/// Whiteout files from OCI layers remove what earlier layers added. Directory
+/// modes are collected in `dirs` and applied once everything is unpacked, so
+/// that read-only directories can still be filled in.
+fn apply_layer(layer: &Path, dest: &Path, dirs: &mut Vec<(PathBuf, u32)>) -> LalResult<()> {
+    let root = dest.canonicalize()?;
+    let mut reader = BufReader::new(fs::File::open(layer)?);
+    let gzipped = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let data: Box<dyn Read> = if gzipped {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+    let mut archive = Archive::new(data);
+    archive.set_preserve_permissions(true);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            warn!("Skipping {} outside of the rootfs", path.display());
+            continue;
+        }
+        let kind = entry.header().entry_type();
+        // devices come from the host
+        if kind.is_character_special() || kind.is_block_special() || kind.is_fifo() {
+            continue;
+        }
+        // NB: without the `.` components, so `./` is `dest` itself
+        let target = dest.join(
+            path.components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .collect::<PathBuf>(),
+        );
+        if target != dest && !inside(&root, &target) {
+            warn!("Skipping {} behind a symlink out of the rootfs", path.display());
+            continue;
+        }
+        if let Some(hidden) = path.file_name().and_then(|n| n.to_str()?.strip_prefix(".wh.")) {
+            let parent = target.parent().unwrap_or(dest);
+            if hidden == ".wh..opq" {
+                for e in fs::read_dir(parent).into_iter().flatten() {
+                    remove_any(&e?.path())?;
+                }
+            } else {
+                remove_any(&parent.join(hidden))?;
+            }
+            continue;
+        }
+        if kind.is_dir() {
+            let mode = entry.header().mode()?;
+            if !fs::symlink_metadata(&target).map(|m| m.is_dir()).unwrap_or(false) {
+                remove_any(&target)?;
+            }
+            entry.unpack_in(dest)?;
+            fs::set_permissions(&target, fs::Permissions::from_mode(0o755))?;
+            dirs.push((target, mode));
+        } else {
+            // replacing files that are not writable needs them gone first
+            remove_any(&target)?;
+            entry.unpack_in(dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Unpack a rootfs into the cache unless it is already there
+///
+/// Tarballs are keyed on their sha256 and image layouts on the digest of their
+/// manifest, so changing the rootfs unpacks it anew under
+/// `cache/rootfs/<digest>`. Published rootfs components must have been fetched
+/// into the cache with `lal env update` first.
+pub fn prepare_rootfs(cache: &Path, rootfs: &Rootfs) -> LalResult<PathBuf> {
+    let (digest, layers) = match &rootfs.source {
+        RootfsSource::Path(p) if p.is_dir() => image_layers(p)?,
+        RootfsSource::Path(p) => (checksum::sha256_file(p)?, vec![p.clone()]),
+        RootfsSource::Component {
+            component,
+            version,
+            environment,
+        } => {
+            let tarball = cached_component(cache, component, *version, environment)?;
+            (checksum::sha256_file(&tarball)?, vec![tarball])
+        }
+    };
+    let root = cache.join("rootfs").join(&digest);
+    if root.is_dir() {
+        debug!("Using unpacked {} in {}", rootfs, root.display());
+        return Ok(root);
+    }
+
+    info!("Unpacking {} into {}", rootfs, root.display());
+    let partial = root.with_extension("partial");
+    remove_any(&partial)?;
+    fs::create_dir_all(&partial)?;
+    let mut dirs = vec![];
+    for layer in layers {
+        apply_layer(&layer, &partial, &mut dirs)?;
+    }
+    // innermost first, so parents are writable until their children are done
+    // NB: later layers may have replaced a directory with a symlink
+    let real = partial.canonicalize()?;
+    for (dir, mode) in dirs.into_iter().rev() {
+        let is_dir = fs::symlink_metadata(&dir).map(|m| m.is_dir()).unwrap_or(false);
+        if is_dir && inside(&real, &dir) {
+            fs::set_permissions(&dir, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    fs::rename(&partial, &root)?;
+    Ok(root)
+}
+
+/// Runs an arbitrary command inside an unpacked root filesystem
+///
+/// The rootfs is used through an overlay that is discarded afterwards. As in
+/// container environments, the component is mounted at `~/volume` of the `lal`
+/// user, but the host uid and gid are kept like in a `Sandbox`.
+pub fn rootfs_run(
+    cfg: &Config,
+    rootfs: &Rootfs,
+    command: Vec<String>,
+    component_dir: &Path,
+    modes: &ShellModes,
+) -> LalResult<()> {
+    warn_unenforced("rootfs", modes);
+    let lower = prepare_rootfs(&cfg.cache, rootfs)?;
+    let base = tempfile::Builder::new().prefix("lal-rootfs").tempdir()?;
+    let component_dir = component_dir.canonicalize()?;
+
+    let mut plan = Plan::new(base.path(), rootfs.network)?;
+    plan.overlay(&lower)?;
+    plan.system_mounts()?;
+    plan.component(&component_dir, Path::new(VOLUME))?;
+    plan.workdir(Path::new(VOLUME))?;
+
+    run_plan(plan, command, &[("HOME", "/home/lal")], modes)
+}
@@ -0,0 +1,479 @@
+use std::{
+    ffi::CString,
+    io,
+    os::unix::{ffi::OsStrExt, process::CommandExt},
+    path::{Path, PathBuf},
+    process::Command,
+    ptr,
+    sync::atomic::{AtomicI32, Ordering},
+    vec::Vec,
+};
+
+use super::{
+    env_vars,
+    process::{self, Stop},
+    warn_unenforced, ShellModes,
+};
+use crate::core::{CliError, LalResult, Sandbox};
+
+/// A mount inside the sandbox root, with every path prepared before forking
+struct MountStep {
+    src: Option<CString>,
+    dest: CString,
+    fstype: Option<CString>,
+    flags: libc::c_ulong,
+    /// Flags to remount a bind with to make it read-only
+    remount: Option<libc::c_ulong>,
+    /// Directories to create before mounting, parents first
+    parents: Vec<CString>,
+    /// Whether the mount point is a file rather than a directory
+    file: bool,
+}
+
+/// Device nodes bound into the otherwise empty `/dev` of the sandbox
+const DEVICES: &[&str] = &["null", "zero", "full", "random", "urandom", "tty"];
+
+/// Symlinks in `/dev` that tools expect, pointing into the sandbox's own `/proc`
+const DEV_LINKS: &[(&str, &str)] = &[
+    ("fd", "/proc/self/fd"),
+    ("stdin", "/proc/self/fd/0"),
+    ("stdout", "/proc/self/fd/1"),
+    ("stderr", "/proc/self/fd/2"),
+];
+
+/// A writable overlay on top of a read-only lower directory
+struct Overlay {
+    dirs: Vec<CString>,
+    options: CString,
+}
+
+/// Everything the child needs to enter the sandbox
+///
+/// Nothing may be allocated between `fork` and `exec`, so this is all built up front.
+pub(super) struct Plan {
+    base: CString,
+    root: PathBuf,
+    root_c: CString,
+    overlay: Option<Overlay>,
+    workdir: CString,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    network: bool,
+    mounts: Vec<MountStep>,
+    /// Symlinks to create once everything is mounted, as (target, path)
+    links: Vec<(CString, CString)>,
+}
+
+fn cstr(p: &Path) -> LalResult<CString> {
+    CString::new(p.as_os_str().as_bytes())
+        .map_err(|_| CliError::SandboxFailure(format!("invalid path {}", p.display())))
+}
+
+/// Mount flags that the kernel locks on a bind from a less privileged namespace
+///
+/// Remounting read-only fails unless these are passed along unchanged.
+fn locked_flags(src: &CString) -> libc::c_ulong {
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(src.as_ptr(), &mut st) } != 0 {
+        return 0;
+    }
+    let pairs = [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ];
+    pairs
+        .iter()
+        .filter(|(st_flag, _)| st.f_flag & st_flag != 0)
+        .fold(0, |acc, (_, ms_flag)| acc | ms_flag)
+}
+
+impl Plan {
+    /// An empty root on a tmpfs mounted over `base`
+    pub(super) fn new(base: &Path, network: bool) -> LalResult<Self> {
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+        Ok(Plan {
+            base: cstr(base)?,
+            root: base.to_path_buf(),
+            root_c: cstr(base)?,
+            overlay: None,
+            workdir: CString::new("/").unwrap(),
+            uid_map: format!("{} {} 1\n", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1\n", gid, gid).into_bytes(),
+            network,
+            mounts: vec![],
+            links: vec![],
+        })
+    }
+
+    /// Use a writable overlay of `lower` as the root instead of an empty one
+    ///
+    /// Writes land in the tmpfs and are thrown away with it.
+    pub(super) fn overlay(&mut self, lower: &Path) -> LalResult<()> {
+        let base = self.root.clone();
+        let (upper, work, root) = (base.join("upper"), base.join("work"), base.join("root"));
+        self.overlay = Some(Overlay {
+            dirs: vec![cstr(&upper)?, cstr(&work)?, cstr(&root)?],
+            options: CString::new(format!(
+                "lowerdir={},upperdir={},workdir={}",
+                lower.display(),
+                upper.display(),
+                work.display()
+            ))
+            .map_err(|_| CliError::SandboxFailure(format!("invalid path {}", lower.display())))?,
+        });
+        self.root_c = cstr(&root)?;
+        self.root = root;
+        Ok(())
+    }
+
+    /// Directory to start the command in, as seen from inside
+    pub(super) fn workdir(&mut self, dir: &Path) -> LalResult<()> {
+        self.workdir = cstr(dir)?;
+        Ok(())
+    }
+
+    /// Directories below the root leading up to `dest`, parents first
+    fn parents(&self, dest: &Path, include_dest: bool) -> LalResult<Vec<CString>> {
+        let mut parents = vec![];
+        let mut dir = self.root.clone();
+        let within = if include_dest {
+            dest
+        } else {
+            dest.parent().unwrap_or(dest)
+        };
+        for c in within.components().skip(1) {
+            dir.push(c);
+            parents.push(cstr(&dir)?);
+        }
+        Ok(parents)
+    }
+
+    /// Bind a host path to `dest` inside the root
+    pub(super) fn bind(&mut self, src: &Path, dest: &Path, readonly: bool) -> LalResult<()> {
+        let file = !src.is_dir();
+        let parents = self.parents(dest, !file)?;
+        let src = cstr(src)?;
+        let remount = if readonly {
+            Some(libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | locked_flags(&src))
+        } else {
+            None
+        };
+        self.mounts.push(MountStep {
+            src: Some(src),
+            dest: cstr(&self.root.join(dest.strip_prefix("/").unwrap_or(dest)))?,
+            fstype: None,
+            flags: libc::MS_BIND | libc::MS_REC,
+            remount,
+            parents,
+            file,
+        });
+        Ok(())
+    }
+
+    /// Mount an empty tmpfs at `dest` inside the root
+    pub(super) fn tmpfs(&mut self, dest: &Path) -> LalResult<()> {
+        let parents = self.parents(dest, true)?;
+        self.mounts.push(MountStep {
+            src: Some(CString::new("tmpfs").unwrap()),
+            dest: cstr(&self.root.join(dest.strip_prefix("/").unwrap_or(dest)))?,
+            fstype: Some(CString::new("tmpfs").unwrap()),
+            flags: libc::MS_NOSUID | libc::MS_NODEV,
+            remount: None,
+            parents,
+            file: false,
+        });
+        Ok(())
+    }
+
+    /// Mount the `/proc` of the sandbox's own pid namespace at `dest`
+    fn procfs(&mut self, dest: &Path) -> LalResult<()> {
+        let parents = self.parents(dest, true)?;
+        self.mounts.push(MountStep {
+            src: Some(CString::new("proc").unwrap()),
+            dest: cstr(&self.root.join(dest.strip_prefix("/").unwrap_or(dest)))?,
+            fstype: Some(CString::new("proc").unwrap()),
+            flags: libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+            remount: None,
+            parents,
+            file: false,
+        });
+        Ok(())
+    }
+
+    /// Devices and processes are needed by most tools, and /tmp is private
+    ///
+    /// Only a few harmless devices are bound into `/dev`, and `/proc` only shows
+    /// the processes of the sandbox, so nothing of the host is visible through either.
+    pub(super) fn system_mounts(&mut self) -> LalResult<()> {
+        let dev = Path::new("/dev");
+        self.tmpfs(dev)?;
+        for name in DEVICES {
+            let device = dev.join(name);
+            if device.exists() {
+                self.bind(&device, &device, false)?;
+            }
+        }
+        self.tmpfs(&dev.join("shm"))?;
+        for (name, target) in DEV_LINKS {
+            let link = self.root.join("dev").join(name);
+            self.links.push((cstr(Path::new(target))?, cstr(&link)?));
+        }
+        self.procfs(Path::new("/proc"))?;
+        self.tmpfs(Path::new("/tmp"))
+    }
+
+    /// Bind the component read-write at `dest`, and its `INPUT` read-only
+    ///
+    /// Done last so that nothing else can shadow it.
+    pub(super) fn component(&mut self, component_dir: &Path, dest: &Path) -> LalResult<()> {
+        self.bind(component_dir, dest, false)?;
+        let input = component_dir.join("INPUT");
+        if input.is_dir() {
+            self.bind(&input, &dest.join("INPUT"), true)?;
+        }
+        Ok(())
+    }
+
+    /// Enter the namespaces and build the root - runs in the forked child
+    ///
+    /// Only async-signal-safe calls are allowed in here. Only children join a new
+    /// pid namespace, so this process forks its first process, which forks the
+    /// command in turn. Both stay behind to pass on signals and the exit status.
+    fn enter(&self) -> io::Result<()> {
+        let mut clone_flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID;
+        if !self.network {
+            clone_flags |= libc::CLONE_NEWNET;
+        }
+        unsafe {
+            check(libc::unshare(clone_flags))?;
+            write_file(b"/proc/self/setgroups\0", b"deny")?;
+            write_file(b"/proc/self/uid_map\0", &self.uid_map)?;
+            write_file(b"/proc/self/gid_map\0", &self.gid_map)?;
+
+            let init = fork()?;
+            if init > 0 {
+                supervise(init);
+            }
+            // init of the namespace - everything in it is killed when it exits
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+            let command = fork()?;
+            if command > 0 {
+                supervise(command);
+            }
+
+            // keep everything below from propagating back to the host
+            check(libc::mount(
+                ptr::null(),
+                b"/\0".as_ptr() as *const _,
+                ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                ptr::null(),
+            ))?;
+            check(libc::mount(
+                b"tmpfs\0".as_ptr() as *const _,
+                self.base.as_ptr(),
+                b"tmpfs\0".as_ptr() as *const _,
+                libc::MS_NOSUID | libc::MS_NODEV,
+                ptr::null(),
+            ))?;
+            if let Some(overlay) = &self.overlay {
+                for dir in &overlay.dirs {
+                    check(libc::mkdir(dir.as_ptr(), 0o755))?;
+                }
+                check(libc::mount(
+                    b"overlay\0".as_ptr() as *const _,
+                    self.root_c.as_ptr(),
+                    b"overlay\0".as_ptr() as *const _,
+                    0,
+                    overlay.options.as_ptr() as *const _,
+                ))?;
+            }
+
+            for m in &self.mounts {
+                // failures are fine when the directory is provided by an earlier mount
+                for dir in &m.parents {
+                    libc::mkdir(dir.as_ptr(), 0o755);
+                }
+                if m.file {
+                    let fd = libc::open(
+                        m.dest.as_ptr(),
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                        0o644,
+                    );
+                    if fd >= 0 {
+                        libc::close(fd);
+                    }
+                }
+                check(libc::mount(
+                    m.src.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                    m.dest.as_ptr(),
+                    m.fstype.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                    m.flags,
+                    ptr::null(),
+                ))?;
+                if let Some(flags) = m.remount {
+                    check(libc::mount(
+                        ptr::null(),
+                        m.dest.as_ptr(),
+                        ptr::null(),
+                        flags,
+                        ptr::null(),
+                    ))?;
+                }
+            }
+
+            for (target, link) in &self.links {
+                check(libc::symlink(target.as_ptr(), link.as_ptr()))?;
+            }
+
+            check(libc::chroot(self.root_c.as_ptr()))?;
+            check(libc::chdir(self.workdir.as_ptr()))?;
+        }
+        Ok(())
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn fork() -> io::Result<libc::pid_t> {
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        pid => Ok(pid),
+    }
+}
+
+/// The child signals are passed on to, per process
+static SUPERVISED: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn pass_on(signal: libc::c_int) {
+    unsafe { libc::kill(SUPERVISED.load(Ordering::Relaxed), signal) };
+}
+
+/// Wait for `child` while passing on termination signals, then exit like it did
+///
+/// Interrupts from the terminal reach the command directly, so they are ignored.
+/// Other children that are reaped on the way are orphans of the namespace.
+unsafe fn supervise(child: libc::pid_t) -> ! {
+    // nothing may hold on to the pipes `Command` uses to report a failed exec
+    if libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0) != 0 {
+        for fd in 3..1024 {
+            libc::close(fd);
+        }
+    }
+    SUPERVISED.store(child, Ordering::Relaxed);
+    libc::signal(libc::SIGINT, libc::SIG_IGN);
+    libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+    let handler = pass_on as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    libc::signal(libc::SIGTERM, handler);
+    libc::signal(libc::SIGHUP, handler);
+    let mut status = 0;
+    loop {
+        let pid = libc::waitpid(-1, &mut status, 0);
+        if pid == child || (pid < 0 && io::Error::last_os_error().raw_os_error() != Some(libc::EINTR)) {
+            break;
+        }
+    }
+    let code = if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else {
+        128 + libc::WTERMSIG(status)
+    };
+    libc::_exit(code)
+}
+
+/// Write a nul terminated path with a single `write`, as procfs requires
+unsafe fn write_file(path: &[u8], contents: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path.as_ptr() as *const _, libc::O_WRONLY | libc::O_CLOEXEC);
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let n = libc::write(fd, contents.as_ptr() as *const _, contents.len());
+    let err = io::Error::last_os_error();
+    libc::close(fd);
+    if n < 0 {
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Run a command inside a prepared plan and wait for it
+pub(super) fn run_plan(
+    plan: Plan,
+    mut command: Vec<String>,
+    envs: &[(&str, &str)],
+    modes: &ShellModes,
+) -> LalResult<()> {
+    if command.is_empty() {
+        command.push("bash".into());
+    }
+    let cmd = command.remove(0);
+    let mut script_cmd = Command::new(&cmd);
+    script_cmd
+        .args(command)
+        .envs(envs.iter().cloned())
+        .envs(env_vars(modes));
+    // the child changes into the working directory itself, after the chroot
+    unsafe {
+        script_cmd.pre_exec(move || plan.enter());
+    }
+
+    debug!("Entering sandbox");
+    let timeout = modes.limits.timeout().map(|t| (t, Stop::Group));
+    let s = process::status(&mut script_cmd, modes.log.as_deref(), timeout).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => CliError::ExecutableMissing(cmd),
+        io::ErrorKind::TimedOut => CliError::Timeout(modes.limits.timeout.unwrap_or_default()),
+        _ => CliError::SandboxFailure(e.to_string()),
+    })?;
+    debug!("Exited sandbox");
+
+    if !s.success() {
+        return Err(CliError::SubprocessFailure(s.code().unwrap_or(1001)));
+    }
+
+    Ok(())
+}
+
+/// Runs an arbitrary command natively, isolated from the host with namespaces
+///
+/// Unprivileged user, mount, pid and (unless `network` is set) network namespaces
+/// are used to run the command in a fresh root that only contains the
+/// allowlisted host paths and `INPUT` read-only, and the component directory.
+/// The host uid and gid are kept, so files written to `OUTPUT` are owned by
+/// the caller as with `native_run`.
+pub fn sandbox_run(
+    sandbox: &Sandbox,
+    command: Vec<String>,
+    component_dir: &Path,
+    modes: &ShellModes,
+) -> LalResult<()> {
+    warn_unenforced("sandbox", modes);
+    let base = tempfile::Builder::new().prefix("lal-sandbox").tempdir()?;
+    let component_dir = component_dir.canonicalize()?;
+
+    let mut plan = Plan::new(base.path(), sandbox.network)?;
+    for path in &sandbox.paths {
+        let path = Path::new(path);
+        if !path.is_absolute() || !path.exists() {
+            return Err(CliError::SandboxFailure(format!(
+                "{} is not an absolute path that exists",
+                path.display()
+            )));
+        }
+        plan.bind(path, path, true)?;
+    }
+    plan.system_mounts()?;
+    plan.component(&component_dir, &component_dir)?;
+    plan.workdir(&component_dir)?;
+
+    run_plan(plan, command, &[], modes)
+}
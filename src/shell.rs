@@ -1,9 +1,12 @@
 use std::{path::Path, vec::Vec};
 
-use super::{docker_run, native_run, CliError, Config, DockerRunFlags, Environment, LalResult, ShellModes};
+use super::{
+    container_run, native_run, rootfs_run, sandbox_run, CliError, Config, DockerRunFlags, Environment,
+    LalResult, ShellModes,
+};
 
 /// Runs an arbitrary command in the configured environment
-/// delegating to docker if needed.
+/// delegating to the container runtime if needed.
 ///
 /// This is the most general function, used by both `lal build` and `lal shell`.
 pub fn run(
@@ -16,9 +19,11 @@ pub fn run(
 ) -> LalResult<()> {
     match environment {
         Environment::Container(container) => {
-            docker_run(&cfg, &container, command, &flags, &modes, &component_dir)
+            container_run(cfg, container, command, flags, modes, component_dir)
         }
-        Environment::None => native_run(command, &component_dir),
+        Environment::Sandbox(sandbox) => sandbox_run(sandbox, command, component_dir, modes),
+        Environment::Rootfs(rootfs) => rootfs_run(cfg, rootfs, command, component_dir, modes),
+        Environment::None => native_run(command, component_dir, modes),
     }
 }
 
@@ -52,14 +57,28 @@ pub fn shell(
                 info!("Entering {}", container);
             }
 
-            docker_run(cfg, container, command, &flags, modes, &component_dir)
+            container_run(cfg, container, command, &flags, modes, component_dir)
+        }
+        Environment::Sandbox(sandbox) => {
+            if !modes.printonly {
+                info!("Entering {}", sandbox);
+            }
+
+            sandbox_run(sandbox, command, component_dir, modes)
+        }
+        Environment::Rootfs(rootfs) => {
+            if !modes.printonly {
+                info!("Entering {}", rootfs);
+            }
+
+            rootfs_run(cfg, rootfs, command, component_dir, modes)
         }
         Environment::None => {
             if command.is_empty() {
                 command.push("bash".into());
             }
 
-            native_run(command, &component_dir)
+            native_run(command, component_dir, modes)
         }
     }
 }
@@ -98,7 +117,7 @@ pub fn script(
                 privileged,
             };
 
-            Ok(docker_run(
+            Ok(container_run(
                 cfg,
                 container,
                 command,
@@ -107,6 +126,35 @@ pub fn script(
                 &component_dir,
             )?)
         }
-        Environment::None => native_run(command, &component_dir),
+        Environment::Sandbox(sandbox) => sandbox_run(sandbox, command, component_dir, modes),
+        Environment::Rootfs(rootfs) => rootfs_run(cfg, rootfs, command, component_dir, modes),
+        Environment::None => native_run(command, component_dir, modes),
+    }
+}
+
+/// Runs a hook from the manifest, if it is set
+///
+/// Hooks are scripts in `.lal/scripts/` that run like `lal run`, with `vars`
+/// added to the environment variables of `modes`.
+pub fn hook(
+    cfg: &Config,
+    environment: &Environment,
+    modes: &ShellModes,
+    hook: &str,
+    script_name: Option<&str>,
+    vars: &[(&str, String)],
+    component_dir: &Path,
+) -> LalResult<()> {
+    let name = match script_name {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    info!("Running {} hook {}", hook, name);
+    let mut modes = modes.clone();
+    modes.log = None;
+    for (k, v) in vars {
+        modes.env_vars.push(format!("{}={}", k, v));
     }
+    script(cfg, environment, name, vec![], &modes, false, component_dir)
+        .map_err(|e| CliError::HookFailure(hook.into(), e.to_string()))
 }
@@ -1,6 +1,7 @@
 use super::{CliError, LalResult, Lockfile, Manifest};
 use crate::core::input;
 use ansi_term::{ANSIString, Colour};
+use serde::Serialize;
 use std::path::Path;
 
 fn version_string(lf: Option<&Lockfile>, show_ver: bool, show_time: bool) -> ANSIString<'static> {
@@ -67,6 +68,17 @@ fn status_recurse(
     }
 }
 
+/// Machine readable form of `lal status`
+#[derive(Serialize)]
+struct StatusOutput<'a> {
+    name: &'a str,
+    dependencies: &'a input::InputMap,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lockfile: Option<&'a Lockfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<serde_json::Value>,
+}
+
 /// Prints a fancy dependency tree of `./INPUT` to stdout.
 ///
 /// This is the quick version information of what you currently have in `./INPUT`.
@@ -77,17 +89,50 @@ fn status_recurse(
 /// from lockfile data.
 ///
 /// It is not intended as a verifier, but will nevertheless produce a summary at the end.
+///
+/// With `json_output`, the analysis of `./INPUT` is printed as JSON instead, along
+/// with the full lockfile tree if the full flag is given. Problems found fail it the same way,
+/// and are also included in the document as its `error`.
 pub fn status(
     component_dir: &Path,
     manifest: &Manifest,
     full: bool,
     show_ver: bool,
     show_time: bool,
+    json_output: bool,
 ) -> LalResult<()> {
     let mut error = None;
 
     let lf = Lockfile::default().populate_from_input(&component_dir)?;
 
+    if json_output {
+        let deps = input::analyze_full(manifest, component_dir)?;
+        for dep in deps.values() {
+            if dep.missing && !dep.development {
+                error = Some(CliError::MissingDependencies);
+            } else if dep.extraneous && !dep.missing && !dep.development {
+                error = Some(CliError::ExtraneousDependencies(dep.name.clone()));
+            }
+        }
+        let output = StatusOutput {
+            name: &manifest.name,
+            dependencies: &deps,
+            lockfile: if full { Some(&lf) } else { None },
+            error: error.as_ref().map(|e| {
+                serde_json::json!({
+                    "category": e.category(),
+                    "code": e.exit_code(),
+                    "message": e.to_string(),
+                })
+            }),
+        };
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        };
+    }
+
     println!("{}", manifest.name);
     let deps = input::analyze_full(manifest, &component_dir)?;
     let len = deps.len();
@@ -1,18 +1,22 @@
 #![allow(missing_docs)]
 
+use rand::Rng;
+use reqwest::header::RANGE;
 use serde::{Deserialize, Serialize};
 use std::{
-    fmt,
+    fmt, fs,
     fs::File,
     io::Read,
     path::{Path, PathBuf},
+    time::Duration,
     vec::Vec,
 };
 use tokio::{fs::File as AsyncFile, io::AsyncWriteExt as _};
 
 #[cfg(feature = "upgrade")] use semver::Version;
 
-use hyper::{body::HttpBody, Body, Client, Method, Request, StatusCode};
+use hyper::{Body, Client, Method, Request, StatusCode};
+use sha2::{Digest, Sha256};
 
 use crate::core::{CliError, LalResult};
 
@@ -35,6 +39,39 @@ impl fmt::Debug for Credentials {
     }
 }
 
+/// How to retry requests that are safe to repeat
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Number of retries after the first attempt
+    pub retries: u32,
+    /// Delay before the first retry in milliseconds (doubled for every further retry)
+    pub backoff_ms: u64,
+    /// Upper limit for the delay in milliseconds
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            retries: 3,
+            backoff_ms: 500,
+            max_backoff_ms: 10_000,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Exponential backoff for a retry, with jitter so clients do not retry in lockstep
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = self
+            .backoff_ms
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16));
+        let ceiling = exp.min(self.max_backoff_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(ceiling / 2..=ceiling))
+    }
+}
+
 /// Static Artifactory locations
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ArtifactoryConfig {
@@ -48,6 +85,9 @@ pub struct ArtifactoryConfig {
     pub vgroup: String,
     /// Optional publish credentials
     pub credentials: Option<Credentials>,
+    /// Retries for version queries and downloads
+    #[serde(default)]
+    pub retry: RetryConfig,
 }
 
 // Need these to query for stored artifacts:
@@ -62,61 +102,153 @@ struct ArtifactoryStorageResponse {
     children: Vec<ArtifactoryVersion>,
 }
 
-// simple request body fetcher
-async fn hyper_req(url: &str) -> LalResult<String> {
-    Ok(reqwest::get(url).await.unwrap().text().await.unwrap().to_string())
+// Outcome of a failed download attempt
+enum Attempt {
+    /// Transient failure - the request is worth repeating
+    Retry(CliError),
+    /// Permanent failure
+    Fail(CliError),
 }
 
-// simple request downloader
-pub async fn http_download_to_path(url: &str, save: &Path) -> LalResult<()> {
-    debug!("GET {}", url);
-    let client = Client::new();
-    let mut res = client.get(url.parse().unwrap()).await?;
-    if !res.status().is_success() {
-        return Err(CliError::BackendFailure(format!(
-            "GET request with {}",
-            res.status()
-        )));
+impl From<reqwest::Error> for Attempt {
+    fn from(e: reqwest::Error) -> Self {
+        // connection problems, timeouts and truncated bodies are all worth retrying
+        Attempt::Retry(e.into())
     }
+}
 
-    if cfg!(feature = "progress") {
-        #[cfg(feature = "progress")]
-        {
-            use indicatif::{ProgressBar, ProgressStyle};
-            let total_size: u64 = res
-                .headers()
-                .get("content-length")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .parse()?;
-            let mut downloaded = 0u64;
-            let pb = ProgressBar::new(total_size);
-
-            pb.set_style(
-                ProgressStyle::default_bar().template("{bar:40.yellow/black} {bytes}/{total_bytes} ({eta})"),
-            );
-
-            let mut f = AsyncFile::create(save).await?;
-            while let Some(chunk) = res.body_mut().data().await {
-                let chunk = chunk?;
-                downloaded += chunk.len() as u64;
-                pb.set_position(downloaded);
-
-                f.write_all(&chunk).await?;
-            }
+impl From<std::io::Error> for Attempt {
+    fn from(e: std::io::Error) -> Self {
+        Attempt::Fail(e.into())
+    }
+}
 
-            f.flush().await?;
+// Keep calling `f` until it succeeds, fails permanently, or runs out of retries
+async fn with_retries<T, F, Fut>(url: &str, retry: &RetryConfig, f: F) -> LalResult<T>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<T, Attempt>>,
+{
+    let mut attempt = 0;
+    loop {
+        match f().await {
+            Ok(res) => return Ok(res),
+            Err(Attempt::Retry(e)) if attempt < retry.retries => {
+                attempt += 1;
+                let delay = retry.delay(attempt);
+                warn!(
+                    "GET {} failed: {} - retrying in {}ms ({}/{})",
+                    url,
+                    e,
+                    delay.as_millis(),
+                    attempt,
+                    retry.retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(Attempt::Retry(e)) | Err(Attempt::Fail(e)) => return Err(e),
         }
+    }
+}
+
+// Whether an unsuccessful status is worth retrying
+fn check_status(url: &str, status: reqwest::StatusCode) -> Result<(), Attempt> {
+    if status.is_success() {
+        return Ok(());
+    }
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(Attempt::Fail(CliError::MissingArtifact(url.into())));
+    }
+    let err = CliError::BackendFailure(format!("GET request with {}", status));
+    let transient = status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT;
+    Err(if transient {
+        Attempt::Retry(err)
     } else {
-        let mut f = AsyncFile::create(save).await?;
+        Attempt::Fail(err)
+    })
+}
 
-        while let Some(chunk) = res.body_mut().data().await {
-            f.write_all(&chunk?).await?;
-        }
+// simple request body fetcher
+async fn hyper_req(url: &str, retry: &RetryConfig) -> LalResult<String> {
+    with_retries(url, retry, || async {
+        let res = reqwest::get(url).await?;
+        check_status(url, res.status())?;
+        Ok(res.text().await?)
+    })
+    .await
+}
 
-        f.flush().await?;
+// One GET of `url` into `part`, continuing from whatever `part` already holds
+async fn download_attempt(client: &reqwest::Client, url: &str, part: &Path) -> Result<(), Attempt> {
+    let existing = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    let mut req = client.get(url);
+    if existing > 0 {
+        debug!("Resuming {} from byte {}", url, existing);
+        req = req.header(RANGE, format!("bytes={}-", existing));
     }
+    let mut res = req.send().await?;
+
+    let status = res.status();
+    let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // the partial file is not a prefix of what is there now - start over
+        fs::remove_file(part)?;
+        return Err(Attempt::Retry(CliError::BackendFailure(format!(
+            "GET request with {}",
+            status
+        ))));
+    }
+    check_status(url, status)?;
+    // servers that ignore the range send everything again
+    let mut f = if resumed {
+        tokio::fs::OpenOptions::new().append(true).open(part).await?
+    } else {
+        AsyncFile::create(part).await?
+    };
+    let offset = if resumed { existing } else { 0 };
+
+    #[cfg(feature = "progress")]
+    let pb = {
+        use super::progress::{labelled_bar, labelled_spinner};
+        let label = part.file_stem().unwrap_or_default().to_string_lossy();
+        let pb = match res.content_length() {
+            Some(len) => labelled_bar(offset + len, &label, "yellow"),
+            None => labelled_spinner(&label, "yellow"),
+        };
+        pb.set_position(offset);
+        pb
+    };
+    #[cfg(not(feature = "progress"))]
+    let _ = offset;
+
+    while let Some(chunk) = res.chunk().await? {
+        #[cfg(feature = "progress")]
+        pb.inc(chunk.len() as u64);
+        f.write_all(&chunk).await?;
+    }
+    f.flush().await?;
+    Ok(())
+}
+
+/// Download a url to a path, retrying transient failures
+///
+/// The download goes to a `.part` file next to `save` which is only renamed into place
+/// once complete, and retries continue from the partial file with a range request.
+pub async fn http_download_to_path(url: &str, save: &Path, retry: &RetryConfig) -> LalResult<()> {
+    debug!("GET {}", url);
+    let client = reqwest::Client::new();
+    let mut part = save.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let _ = fs::remove_file(&part); // never resume from an earlier invocation
+
+    if let Err(e) = with_retries(url, retry, || download_attempt(&client, url, &part)).await {
+        let _ = fs::remove_file(&part);
+        return Err(e);
+    }
+    fs::rename(&part, save)?;
     Ok(())
 }
 
@@ -124,10 +256,21 @@ pub async fn http_download_to_path(url: &str, save: &Path) -> LalResult<()> {
 ///
 /// This will get, then parse all results as u32s, and return this list.
 /// This assumes versoning is done via a single integer.
-async fn get_storage_versions(uri: &str) -> LalResult<Vec<u32>> {
+async fn get_storage_versions(uri: &str, retry: &RetryConfig) -> LalResult<Vec<u32>> {
+    let mut builds: Vec<u32> = get_storage_children(uri, retry)
+        .await?
+        .iter()
+        .filter_map(|b| b.parse().ok())
+        .collect();
+    builds.sort_by(|a, b| b.cmp(a)); // sort by version number descending
+    Ok(builds)
+}
+
+/// Get the names of the children of a folder from the storage api
+async fn get_storage_children(uri: &str, retry: &RetryConfig) -> LalResult<Vec<String>> {
     debug!("GET {}", uri);
 
-    let resp: String = hyper_req(uri).await.map_err(|e| {
+    let resp: String = hyper_req(uri, retry).await.map_err(|e| {
         warn!("Failed to GET {}: {}", uri, e);
         CliError::BackendFailure("No version information found on API".into())
     })?;
@@ -135,15 +278,11 @@ async fn get_storage_versions(uri: &str) -> LalResult<Vec<u32>> {
     trace!("{}", format!("Got body {}", resp));
 
     let res: ArtifactoryStorageResponse = serde_json::from_str(&resp)?;
-    let mut builds: Vec<u32> = res
+    Ok(res
         .children
         .iter()
-        .map(|r| r.uri.as_str())
-        .map(|r| r.trim_matches('/'))
-        .filter_map(|b| b.parse().ok())
-        .collect();
-    builds.sort_by(|a, b| b.cmp(a)); // sort by version number descending
-    Ok(builds)
+        .map(|r| r.uri.trim_matches('/').to_string())
+        .collect())
 }
 
 /// Upload a tarball to artifactory
@@ -160,6 +299,7 @@ async fn upload_artifact(arti: &ArtifactoryConfig, uri: &str, f: &mut File) -> L
 
         let mut sha = sha1::Sha1::new();
         sha.update(&buffer);
+        let sha256 = format!("{:x}", Sha256::digest(&buffer));
 
         let auth = format!("{}:{}", creds.username, creds.password);
         let auth = format!("Basic {}", base64::encode(auth));
@@ -193,6 +333,7 @@ async fn upload_artifact(arti: &ArtifactoryConfig, uri: &str, f: &mut File) -> L
             .uri(&full_uri[..])
             .header("X-Checksum-Deploy", "true")
             .header("X-Checksum-Sha1", sha.digest().to_string())
+            .header("X-Checksum-Sha256", sha256)
             .header("Authorization", auth)
             .body(Body::empty())
             .unwrap();
@@ -212,8 +353,8 @@ async fn upload_artifact(arti: &ArtifactoryConfig, uri: &str, f: &mut File) -> L
 }
 
 /// Get the maximal version number from the storage api
-async fn get_storage_as_u32(uri: &str) -> LalResult<u32> {
-    if let Some(&latest) = get_storage_versions(uri).await?.iter().max() {
+async fn get_storage_as_u32(uri: &str, retry: &RetryConfig) -> LalResult<u32> {
+    if let Some(&latest) = get_storage_versions(uri, retry).await?.iter().max() {
         Ok(latest)
     } else {
         Err(CliError::BackendFailure(
@@ -247,13 +388,14 @@ async fn get_dependency_url_latest(
         "{}/api/storage/{}/{}/{}/{}",
         art_cfg.master, art_cfg.release, "env", env, name
     );
-    let v = get_storage_as_u32(&url).await?;
+    let v = get_storage_as_u32(&url, &art_cfg.retry).await?;
 
     debug!("Found latest version as {}", v);
     Ok(Component {
         location: get_dependency_env_url(art_cfg, name, v, env),
         version: v,
         name: name.into(),
+        sha256: None,
     })
 }
 
@@ -265,7 +407,7 @@ async fn get_latest_versions(art_cfg: &ArtifactoryConfig, name: &str, env: &str)
         art_cfg.master, art_cfg.release, "env", env, name
     );
 
-    get_storage_versions(&url).await
+    get_storage_versions(&url, &art_cfg.retry).await
 }
 
 /// Main entry point for install
@@ -280,6 +422,7 @@ async fn get_tarball_uri(
             location: get_dependency_env_url(art_cfg, name, v, env),
             version: v,
             name: name.into(),
+            sha256: None,
         })
     } else {
         get_dependency_url_latest(art_cfg, name, env).await
@@ -302,11 +445,11 @@ pub struct LatestLal {
 /// This is used regardless of your used backend because we want people to use our
 /// main release of lal on CME-release on cisco artifactory at the moment.
 #[cfg(feature = "upgrade")]
-pub fn get_latest_lal_version() -> LalResult<LatestLal> {
+pub async fn get_latest_lal_version() -> LalResult<LatestLal> {
     // canonical latest url
     let uri = "https://engci-maven-master.cisco.com/artifactory/api/storage/CME-release/lal";
     debug!("GET {}", uri);
-    let resp: String = hyper_req(uri).map_err(|e| {
+    let resp: String = hyper_req(uri, &RetryConfig::default()).await.map_err(|e| {
         warn!("Failed to GET {}: {}", uri, e);
         CliError::BackendFailure("No version information found on API".into())
     })?;
@@ -374,6 +517,16 @@ impl Backend for ArtifactoryBackend {
         Ok(latest.version)
     }
 
+    async fn list_components(&self, loc: &str) -> LalResult<Vec<String>> {
+        let url = format!(
+            "{}/api/storage/{}/env/{}",
+            self.config.master, self.config.release, loc
+        );
+        let mut names = get_storage_children(&url, &self.config.retry).await?;
+        names.sort();
+        Ok(names)
+    }
+
     async fn get_component_info(&self, name: &str, version: Option<u32>, loc: &str) -> LalResult<Component> {
         get_tarball_uri(&self.config, name, version, loc).await
     }
@@ -405,11 +558,18 @@ impl Backend for ArtifactoryBackend {
         Ok(())
     }
 
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String {
+        format!(
+            "{}/{}/env/{}/{}/{}/lockfile.json",
+            self.config.slave, self.config.vgroup, loc, name, version
+        )
+    }
+
     fn get_cache_dir(&self) -> PathBuf {
         self.cache.clone()
     }
 
     async fn raw_fetch(&self, url: &str, dest: &Path) -> LalResult<()> {
-        http_download_to_path(url, dest).await
+        http_download_to_path(url, dest, &self.config.retry).await
     }
 }
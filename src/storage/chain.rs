@@ -0,0 +1,234 @@
+#![allow(missing_docs)]
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    vec::Vec,
+};
+
+use super::{ArtifactoryBackend, BackendConfiguration, HttpBackend, LocalBackend, OciBackend, S3Backend};
+use crate::core::{CliError, LalResult};
+
+/// An ordered list of backends to read from
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChainConfig {
+    /// Backends in the order they are tried for reads
+    pub backends: Vec<BackendConfiguration>,
+    /// Index into `backends` of the backend that publishes go to
+    #[serde(default)]
+    pub primary: usize,
+}
+
+use super::{Backend, Component};
+
+type ChainLink = Box<dyn Backend + Send + Sync>;
+
+fn make_backend(cfg: &BackendConfiguration, cache: &Path) -> LalResult<ChainLink> {
+    let backend: ChainLink = match cfg {
+        BackendConfiguration::Artifactory(cfg) => Box::new(ArtifactoryBackend::new(cfg, cache)?),
+        BackendConfiguration::Local(cfg) => Box::new(LocalBackend::new(cfg, cache)?),
+        BackendConfiguration::S3(cfg) => Box::new(S3Backend::new(cfg, cache)?),
+        BackendConfiguration::Http(cfg) => Box::new(HttpBackend::new(cfg, cache)?),
+        BackendConfiguration::Oci(cfg) => Box::new(OciBackend::new(cfg, cache)?),
+        BackendConfiguration::Chain(cfg) => Box::new(ChainBackend::new(cfg, cache)?),
+    };
+    Ok(backend)
+}
+
+/// A list of backends acting as one, e.g. a LAN mirror in front of Artifactory
+///
+/// Reads try each backend in turn, and publishes go to the primary backend.
+pub struct ChainBackend {
+    /// Chain config
+    pub config: ChainConfig,
+    /// Cache directory (shared by all backends in the chain)
+    pub cache: PathBuf,
+    backends: Vec<ChainLink>,
+    // backend index that served a component, by (name, version, env)
+    picked: Mutex<HashMap<(String, u32, String), usize>>,
+    // backend index that handed out a location
+    routes: Mutex<HashMap<String, usize>>,
+}
+
+impl ChainBackend {
+    pub fn new(cfg: &ChainConfig, cache: &Path) -> LalResult<Self> {
+        if cfg.backends.is_empty() {
+            return Err(CliError::InvalidBackendConfiguration(
+                "chain has no backends".into(),
+            ));
+        }
+        if cfg.primary >= cfg.backends.len() {
+            return Err(CliError::InvalidBackendConfiguration(format!(
+                "chain primary {} out of range for {} backends",
+                cfg.primary,
+                cfg.backends.len()
+            )));
+        }
+        let backends = cfg
+            .backends
+            .iter()
+            .map(|b| make_backend(b, cache))
+            .collect::<LalResult<Vec<_>>>()?;
+
+        let backend = ChainBackend {
+            config: cfg.clone(),
+            cache: cache.to_path_buf(),
+            backends,
+            picked: Mutex::new(HashMap::new()),
+            routes: Mutex::new(HashMap::new()),
+        };
+
+        Ok(backend)
+    }
+
+    // Versions from every backend that answers, in backend order
+    //
+    // Unreachable backends are skipped as long as one of them answers.
+    async fn versions_by_backend(&self, name: &str, loc: &str) -> LalResult<Vec<(usize, Vec<u32>)>> {
+        let mut res = vec![];
+        let mut last_err = None;
+        for (i, b) in self.backends.iter().enumerate() {
+            match b.get_versions(name, loc).await {
+                Ok(vs) => res.push((i, vs)),
+                Err(e) => {
+                    warn!("Skipping backend {} in chain for {}: {}", i, name, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        match last_err {
+            Some(e) if res.is_empty() => Err(e),
+            _ => Ok(res),
+        }
+    }
+}
+
+/// Artifact backend trait for `ChainBackend`
+///
+/// This is intended to be used by the caching trait `CachedBackend`, but for
+/// specific low-level use cases, these methods can be used directly.
+#[async_trait::async_trait]
+impl Backend for ChainBackend {
+    async fn get_versions(&self, name: &str, loc: &str) -> LalResult<Vec<u32>> {
+        let mut versions: Vec<u32> = self
+            .versions_by_backend(name, loc)
+            .await?
+            .into_iter()
+            .flat_map(|(_, vs)| vs)
+            .collect();
+        versions.sort_by(|a, b| b.cmp(a)); // sort by version number descending
+        versions.dedup();
+        Ok(versions)
+    }
+
+    async fn get_latest_version(&self, name: &str, loc: &str) -> LalResult<u32> {
+        if let Some(&last) = self.get_versions(name, loc).await?.first() {
+            return Ok(last);
+        }
+        Err(CliError::BackendFailure(
+            "No versions found in any backend".into(),
+        ))
+    }
+
+    async fn list_components(&self, loc: &str) -> LalResult<Vec<String>> {
+        let mut names = vec![];
+        let mut last_err = None;
+        for (i, b) in self.backends.iter().enumerate() {
+            match b.list_components(loc).await {
+                Ok(ns) => names.extend(ns),
+                Err(e) => {
+                    warn!("Skipping backend {} in chain for listing {}: {}", i, loc, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        if let Some(e) = last_err {
+            if names.is_empty() {
+                return Err(e);
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    async fn get_component_info(&self, name: &str, version: Option<u32>, loc: &str) -> LalResult<Component> {
+        let available = self.versions_by_backend(name, loc).await?;
+        let v = match version {
+            Some(ver) => ver,
+            None => available
+                .iter()
+                .flat_map(|(_, vs)| vs.iter().copied())
+                .max()
+                .ok_or_else(|| CliError::BackendFailure("No versions found in any backend".into()))?,
+        };
+        // first backend in the chain that has it serves it
+        let idx = available
+            .iter()
+            .find(|(_, vs)| vs.contains(&v))
+            .map(|(i, _)| *i)
+            .ok_or_else(|| CliError::BackendFailure(format!("{}={} not found in any backend", name, v)))?;
+        debug!("Using backend {} in chain for {}={}", idx, name, v);
+
+        let component = self.backends[idx].get_component_info(name, Some(v), loc).await?;
+        self.picked
+            .lock()
+            .unwrap()
+            .insert((name.into(), v, loc.into()), idx);
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(component.location.clone(), idx);
+        Ok(component)
+    }
+
+    async fn publish_artifact(
+        &self,
+        home: Option<&Path>,
+        component_dir: &Path,
+        name: &str,
+        version: u32,
+        env: &str,
+    ) -> LalResult<()> {
+        self.backends[self.config.primary]
+            .publish_artifact(home, component_dir, name, version, env)
+            .await
+    }
+
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String {
+        let idx = self
+            .picked
+            .lock()
+            .unwrap()
+            .get(&(name.into(), version, loc.into()))
+            .copied()
+            .unwrap_or(self.config.primary);
+        let location = self.backends[idx].get_lockfile_location(name, version, loc);
+        self.routes.lock().unwrap().insert(location.clone(), idx);
+        location
+    }
+
+    fn get_cache_dir(&self) -> PathBuf {
+        self.cache.clone()
+    }
+
+    async fn raw_fetch(&self, location: &str, dest: &Path) -> LalResult<()> {
+        let route = self.routes.lock().unwrap().get(location).copied();
+        if let Some(idx) = route {
+            return self.backends[idx].raw_fetch(location, dest).await;
+        }
+        // not handed out by us - try everything
+        // NB: only missing if no backend had it, other failures take precedence
+        let mut last_err = None;
+        for b in &self.backends {
+            match b.raw_fetch(location, dest).await {
+                Ok(()) => return Ok(()),
+                Err(CliError::MissingArtifact(_)) if last_err.is_some() => {}
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.expect("chain has backends"))
+    }
+}
@@ -4,12 +4,14 @@ use std::{
 };
 
 use crate::{
-    core::{output, CliError, LalResult},
+    core::{checksum, output, CliError, LalResult, Lockfile, VersionRequirement},
     storage::{Backend, CachedBackend, Component},
 };
 
 fn is_cached(backend: &dyn Backend, name: &str, version: u32, env: &str) -> bool {
-    get_cache_dir(backend, name, version, env).is_dir()
+    get_cache_dir(backend, name, version, env)
+        .join(format!("{}.tar.gz", name))
+        .is_file()
 }
 
 fn get_cache_dir(backend: &dyn Backend, name: &str, version: u32, env: &str) -> PathBuf {
@@ -39,6 +41,47 @@ fn stored_tarball_location(
     Ok(dest)
 }
 
+// Check a cached tarball against the digest recorded in the lockfile published next to it
+//
+// Artifacts published before checksums were recorded have no digest and are trusted as is.
+// Either way the digest of the tarball is returned.
+fn verify_cached_tarball(tarball: &Path, name: &str) -> LalResult<String> {
+    let found = checksum::sha256_file(tarball)?;
+    let lockpath = tarball.with_file_name("lockfile.json");
+    if !lockpath.is_file() {
+        debug!(
+            "No lockfile cached next to {} - skipping checksum",
+            tarball.display()
+        );
+        return Ok(found);
+    }
+    match Lockfile::from_path(&lockpath, name)?.sha256 {
+        Some(expected) if expected != found => Err(CliError::ChecksumMismatch(name.into(), expected, found)),
+        Some(_) => {
+            debug!("Verified sha256 of {} as {}", tarball.display(), found);
+            Ok(found)
+        }
+        None => {
+            debug!("No sha256 recorded for {} - skipping checksum", name);
+            Ok(found)
+        }
+    }
+}
+
+// Check the signature on the lockfile next to a verified tarball, if the backend asks for it
+fn verify_signature(
+    backend: &dyn Backend,
+    lockfile: &Path,
+    name: &str,
+    version: u32,
+    env: &str,
+) -> LalResult<()> {
+    match backend.signature_policy() {
+        Some(policy) => policy.verify(lockfile, name, version, env),
+        None => Ok(()),
+    }
+}
+
 // helper for the unpack_ functions
 fn extract_tarball_to_input(tarname: PathBuf, component_dir: &Path, component: &str) -> LalResult<()> {
     use flate2::read::GzDecoder;
@@ -55,7 +98,7 @@ fn extract_tarball_to_input(tarname: PathBuf, component_dir: &Path, component: &
         {
             use super::progress::ProgressReader;
             let data = fs::File::open(tarname)?;
-            let progdata = ProgressReader::new(data)?;
+            let progdata = ProgressReader::new(data, component)?;
             let decompressed = GzDecoder::new(progdata); // decoder reads data (proxied)
             let mut archive = Archive::new(decompressed); // Archive reads decoded
             archive.unpack(&extract_path)?;
@@ -107,6 +150,26 @@ impl<T: Backend + Sync> CachedBackend for T {
         Ok(result.into_iter().collect())
     }
 
+    /// Resolve a requirement against the versions available in every environment
+    ///
+    /// Exact requirements are returned as is without asking the backend.
+    async fn resolve_version(
+        &self,
+        name: &str,
+        requirement: &VersionRequirement,
+        environments: Vec<String>,
+    ) -> LalResult<u32> {
+        if let VersionRequirement::Exact(v) = *requirement {
+            return Ok(v);
+        }
+        let versions = self.get_latest_supported_versions(name, environments).await?;
+        let ver = requirement
+            .resolve(&versions)
+            .ok_or_else(|| CliError::NoMatchingVersion(name.into(), requirement.to_string()))?;
+        info!("Resolved {} {} to {}", name, requirement, ver);
+        Ok(ver)
+    }
+
     /// Locate a proper component, downloading it and caching if necessary
     async fn retrieve_published_component(
         &self,
@@ -116,12 +179,51 @@ impl<T: Backend + Sync> CachedBackend for T {
     ) -> LalResult<(PathBuf, Component)> {
         trace!("Locate component {}", name);
 
-        let component = self.get_component_info(name, version, env).await?;
+        let mut component = self.get_component_info(name, version, env).await?;
 
         if !is_cached(self, &component.name, component.version, env) {
             // download to PWD then move it to stash immediately
             let tarball_location = stored_tarball_location(self, name, component.version, env)?;
+            let lockfile_location = tarball_location.with_file_name("lockfile.json");
+            let lockfile_url = self.get_lockfile_location(name, component.version, env);
+            // lockfile first so the tarball can be verified as soon as it lands
+            // NB: only a lockfile that is definitely not there marks an artifact from before checksums
+            match self.raw_fetch(&lockfile_url, &lockfile_location).await {
+                Ok(()) => {}
+                Err(CliError::MissingArtifact(_)) => {
+                    warn!("No lockfile published for {} - cannot verify its checksum", name);
+                    let _ = fs::remove_file(&lockfile_location);
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&lockfile_location);
+                    return Err(e);
+                }
+            }
             self.raw_fetch(&component.location, &tarball_location).await?;
+            match verify_cached_tarball(&tarball_location, name).and_then(|digest| {
+                verify_signature(self, &lockfile_location, name, component.version, env)?;
+                Ok(digest)
+            }) {
+                Ok(digest) => component.sha256 = Some(digest),
+                Err(e) => {
+                    // never leave a bad download in the cache
+                    let _ = fs::remove_file(&tarball_location);
+                    let _ = fs::remove_file(&lockfile_location);
+                    return Err(e);
+                }
+            }
+        } else {
+            // NB: the cache is the storage for the local backend, so never remove it here
+            let tarball_location =
+                get_cache_dir(self, name, component.version, env).join(format!("{}.tar.gz", name));
+            component.sha256 = Some(verify_cached_tarball(&tarball_location, name)?);
+            verify_signature(
+                self,
+                &tarball_location.with_file_name("lockfile.json"),
+                name,
+                component.version,
+                env,
+            )?;
         }
         assert!(
             is_cached(self, &component.name, component.version, env),
@@ -0,0 +1,330 @@
+#![allow(missing_docs)]
+
+use regex::Regex;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    vec::Vec,
+};
+
+use super::artifactory::{http_download_to_path, Credentials, RetryConfig};
+use crate::core::{CliError, LalResult};
+
+/// Static HTTP / WebDAV server locations
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HttpConfig {
+    /// Base url that the `env` tree is served under
+    pub url: String,
+    /// Optional publish credentials (sent with basic auth)
+    pub credentials: Option<Credentials>,
+    /// Retries for downloads
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// Representation of the `index.json` kept next to the versions of a component
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct HttpIndex {
+    versions: Vec<u32>,
+}
+
+/// An index as read before publishing, to only replace it if it is unchanged
+struct IndexSnapshot {
+    index: HttpIndex,
+    /// ETag of the `index.json` read, if there was one
+    etag: Option<String>,
+    /// Whether the index exists or was seeded from the listing
+    exists: bool,
+}
+
+/// How many times a publish reads the index again after losing a race to update it
+const INDEX_ATTEMPTS: usize = 5;
+
+use super::{Backend, Component};
+
+/// Artifact storage on a plain HTTP server that accepts PUT (e.g. nginx with WebDAV)
+pub struct HttpBackend {
+    /// Server config
+    pub config: HttpConfig,
+    /// Cache directory
+    pub cache: PathBuf,
+    client: Client,
+}
+
+impl HttpBackend {
+    pub fn new(cfg: &HttpConfig, cache: &Path) -> LalResult<Self> {
+        let backend = HttpBackend {
+            config: cfg.clone(),
+            cache: cache.to_path_buf(),
+            client: Client::new(),
+        };
+
+        Ok(backend)
+    }
+
+    fn component_url(&self, name: &str, env: &str) -> String {
+        format!("{}/env/{}/{}", self.config.url.trim_end_matches('/'), env, name)
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        debug!("{} {}", method, url);
+        let req = self.client.request(method, url);
+        match &self.config.credentials {
+            Some(creds) => req.basic_auth(&creds.username, Some(&creds.password)),
+            None => req,
+        }
+    }
+
+    // Versions from the index with its ETag, or None if the server has no index for the component
+    async fn get_index(&self, name: &str, env: &str) -> LalResult<Option<(HttpIndex, Option<String>)>> {
+        let url = format!("{}/index.json", self.component_url(name, env));
+        let resp = self.request(Method::GET, &url).send().await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            return Err(CliError::BackendFailure(format!(
+                "{} from GET {}",
+                resp.status(),
+                url
+            )));
+        }
+        let etag = resp
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|t| t.to_str().ok())
+            .map(String::from);
+        Ok(Some((serde_json::from_str(&resp.text().await?)?, etag)))
+    }
+
+    // Versions from a directory listing like the one generated by nginx autoindex
+    async fn get_autoindex(&self, name: &str, env: &str, missing_ok: bool) -> LalResult<Vec<u32>> {
+        let url = format!("{}/", self.component_url(name, env));
+        Ok(self
+            .get_listing(&url, missing_ok)
+            .await?
+            .iter()
+            .filter_map(|v| v.parse().ok())
+            .collect())
+    }
+
+    // Names of the directories in an autoindex listing of a url ending in `/`
+    //
+    // With `missing_ok`, a listing that is refused (403) or does not exist (404) is empty.
+    async fn get_listing(&self, url: &str, missing_ok: bool) -> LalResult<Vec<String>> {
+        lazy_static! {
+            static ref HREF: Regex = Regex::new(r#"href="(?:[^"]*/)?([^"/?]+)/""#).unwrap();
+        }
+        let resp = self.request(Method::GET, url).send().await?;
+        let missing = [StatusCode::FORBIDDEN, StatusCode::NOT_FOUND].contains(&resp.status());
+        if missing_ok && missing {
+            debug!("{} from GET {} - nothing listed", resp.status(), url);
+            return Ok(vec![]);
+        }
+        if !resp.status().is_success() {
+            return Err(CliError::BackendFailure(format!(
+                "{} from GET {}",
+                resp.status(),
+                url
+            )));
+        }
+        let body = resp.text().await?;
+        trace!("Got body {}", body);
+        Ok(HREF
+            .captures_iter(&body)
+            .map(|c| c[1].to_string())
+            .filter(|d| !d.starts_with('.'))
+            .collect())
+    }
+
+    async fn put(&self, url: &str, data: Vec<u8>) -> LalResult<()> {
+        let resp = self.request(Method::PUT, url).body(data).send().await?;
+        let respstr = format!("{} from PUT {}", resp.status(), url);
+        if !resp.status().is_success() {
+            return Err(CliError::UploadFailure(respstr));
+        }
+        debug!("{}", respstr);
+        Ok(())
+    }
+
+    // WebDAV servers need collections to exist before PUT - ignore failures as
+    // they already exist, or the server creates them on PUT anyway
+    async fn ensure_collections(&self, url: &str) {
+        let base = self.config.url.trim_end_matches('/');
+        let mut current = base.to_string();
+        for part in url[base.len()..].trim_matches('/').split('/') {
+            current = format!("{}/{}", current, part);
+            let mkcol = Method::from_bytes(b"MKCOL").expect("valid method");
+            let res = self.request(mkcol, &format!("{}/", current)).send().await;
+            trace!("MKCOL {}: {:?}", current, res.map(|r| r.status()));
+        }
+    }
+
+    // The index to add a version to, seeded from the listing if there is none yet
+    //
+    // The index is authoritative once it exists, so it is never seeded from a listing
+    // that failed. A new component, or a server without autoindex, lists nothing.
+    async fn read_index(&self, name: &str, env: &str) -> LalResult<IndexSnapshot> {
+        if let Some((index, etag)) = self.get_index(name, env).await? {
+            return Ok(IndexSnapshot {
+                index,
+                etag,
+                exists: true,
+            });
+        }
+        let versions = self.get_autoindex(name, env, true).await?;
+        Ok(IndexSnapshot {
+            index: HttpIndex { versions },
+            etag: None,
+            exists: false,
+        })
+    }
+
+    // Add a version to the index without readers ever seeing a partial file
+    //
+    // The new index is uploaded next to the old one, then moved over it with WebDAV MOVE,
+    // only if the old one is unchanged: its ETag is checked with an `If` header, and a
+    // missing index must still be missing. When another publish got there first, the
+    // index is read again and the version added to theirs.
+    async fn add_to_index(
+        &self,
+        name: &str,
+        env: &str,
+        version: u32,
+        mut snap: IndexSnapshot,
+    ) -> LalResult<()> {
+        let url = format!("{}/index.json", self.component_url(name, env));
+        let tmp_url = format!("{}.{:x}", url, rand::random::<u32>());
+        for _ in 0..INDEX_ATTEMPTS {
+            let index = &mut snap.index;
+            if !index.versions.contains(&version) {
+                index.versions.push(version);
+            }
+            index.versions.sort_by(|a, b| b.cmp(a)); // sort by version number descending
+            self.put(&tmp_url, serde_json::to_vec_pretty(&index)?).await?;
+
+            let mv = Method::from_bytes(b"MOVE").expect("valid method");
+            let req = self.request(mv, &tmp_url).header("Destination", url.as_str());
+            let req = match (snap.exists, &snap.etag) {
+                (true, Some(etag)) => req
+                    .header("Overwrite", "T")
+                    .header("If", format!("<{}> ([{}])", url, etag)),
+                (true, None) => {
+                    warn!("No ETag for {} - concurrent publishes can drop versions", url);
+                    req.header("Overwrite", "T")
+                }
+                (false, _) => req.header("Overwrite", "F"),
+            };
+            let resp = req.send().await?;
+            if resp.status().is_success() {
+                return Ok(());
+            }
+            if resp.status() != StatusCode::PRECONDITION_FAILED {
+                let _ = self.request(Method::DELETE, &tmp_url).send().await;
+                return Err(CliError::UploadFailure(format!(
+                    "{} from MOVE {} - the server must support WebDAV MOVE to update the index",
+                    resp.status(),
+                    url
+                )));
+            }
+            debug!("{} changed during the publish - reading it again", url);
+            snap = self.read_index(name, env).await?;
+        }
+        let _ = self.request(Method::DELETE, &tmp_url).send().await;
+        Err(CliError::UploadFailure(format!(
+            "{} kept changing during the publish",
+            url
+        )))
+    }
+}
+
+/// Artifact backend trait for `HttpBackend`
+///
+/// This is intended to be used by the caching trait `CachedBackend`, but for
+/// specific low-level use cases, these methods can be used directly.
+#[async_trait::async_trait]
+impl Backend for HttpBackend {
+    async fn get_versions(&self, name: &str, loc: &str) -> LalResult<Vec<u32>> {
+        let mut versions = match self.get_index(name, loc).await? {
+            Some((index, _)) => index.versions,
+            None => self.get_autoindex(name, loc, false).await?,
+        };
+        versions.sort_by(|a, b| b.cmp(a)); // sort by version number descending
+        Ok(versions)
+    }
+
+    async fn get_latest_version(&self, name: &str, loc: &str) -> LalResult<u32> {
+        if let Some(&last) = self.get_versions(name, loc).await?.first() {
+            return Ok(last);
+        }
+        Err(CliError::BackendFailure("No versions found on server".into()))
+    }
+
+    async fn list_components(&self, loc: &str) -> LalResult<Vec<String>> {
+        let url = format!("{}/env/{}/", self.config.url.trim_end_matches('/'), loc);
+        let mut names = self.get_listing(&url, false).await?;
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    async fn get_component_info(&self, name: &str, version: Option<u32>, loc: &str) -> LalResult<Component> {
+        let v = if let Some(ver) = version {
+            ver
+        } else {
+            self.get_latest_version(name, loc).await?
+        };
+        Ok(Component {
+            location: format!("{}/{}/{}.tar.gz", self.component_url(name, loc), v, name),
+            version: v,
+            name: name.into(),
+            sha256: None,
+        })
+    }
+
+    async fn publish_artifact(
+        &self,
+        _home: Option<&Path>,
+        component_dir: &Path,
+        name: &str,
+        version: u32,
+        env: &str,
+    ) -> LalResult<()> {
+        if self.config.credentials.is_none() {
+            return Err(CliError::MissingBackendCredentials);
+        }
+        // this fn basically assumes all the sanity checks have been performed
+        // files must exist and lockfile must be sensible
+        // read the index first, so a server it cannot be read from gets no orphaned uploads
+        let index = self.read_index(name, env).await?;
+        let artdir = component_dir.join("./ARTIFACT");
+        let verdir = format!("{}/{}", self.component_url(name, env), version);
+        self.ensure_collections(&verdir).await;
+
+        let tarball = artdir.join(format!("{}.tar.gz", name));
+        self.put(&format!("{}/{}.tar.gz", verdir, name), fs::read(tarball)?)
+            .await?;
+        self.put(
+            &format!("{}/lockfile.json", verdir),
+            fs::read(artdir.join("lockfile.json"))?,
+        )
+        .await?;
+
+        // only list the version once everything is uploaded
+        self.add_to_index(name, env, version, index).await
+    }
+
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String {
+        format!("{}/{}/lockfile.json", self.component_url(name, loc), version)
+    }
+
+    fn get_cache_dir(&self) -> PathBuf {
+        self.cache.clone()
+    }
+
+    async fn raw_fetch(&self, url: &str, dest: &Path) -> LalResult<()> {
+        http_download_to_path(url, dest, &self.config.retry).await
+    }
+}
@@ -72,6 +72,24 @@ impl Backend for LocalBackend {
         ))
     }
 
+    async fn list_components(&self, loc: &str) -> LalResult<Vec<String>> {
+        let env_dir = self.cache.join("environments").join(loc);
+        let mut names = vec![];
+        // nothing published is not an error here
+        if let Ok(entries) = fs::read_dir(&env_dir) {
+            for entry in entries {
+                let entry = entry?;
+                if entry.path().is_dir() {
+                    if let Some(name) = entry.file_name().to_str() {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
     async fn get_component_info(&self, name: &str, version: Option<u32>, loc: &str) -> LalResult<Component> {
         info!("get_component_info: {} {:?} {}", name, version, loc);
 
@@ -92,6 +110,7 @@ impl Backend for LocalBackend {
             name: name.into(),
             version: v,
             location: loc,
+            sha256: None,
         })
     }
 
@@ -142,13 +161,26 @@ impl Backend for LocalBackend {
         Ok(())
     }
 
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String {
+        format!(
+            "{}/environments/{}/{}/{}/lockfile.json",
+            self.cache.display(),
+            loc,
+            name,
+            version
+        )
+    }
+
     fn get_cache_dir(&self) -> PathBuf {
         self.cache.clone()
     }
 
     async fn raw_fetch(&self, src: &str, dest: &Path) -> LalResult<()> {
         debug!("raw fetch {} -> {}", src, dest.display());
-        fs::copy(src, dest)?;
-        Ok(())
+        match fs::copy(src, dest) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(CliError::MissingArtifact(src.into())),
+            Err(e) => Err(e.into()),
+            Ok(_) => Ok(()),
+        }
     }
 }
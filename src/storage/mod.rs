@@ -1,8 +1,14 @@
 pub use self::traits::{Backend, BackendConfiguration, CachedBackend, Component};
 
 pub use self::{
-    artifactory::{ArtifactoryBackend, ArtifactoryConfig, Credentials},
+    artifactory::{ArtifactoryBackend, ArtifactoryConfig, Credentials, RetryConfig},
+    chain::{ChainBackend, ChainConfig},
+    http::{HttpBackend, HttpConfig},
     local::{LocalBackend, LocalConfig},
+    oci::{OciBackend, OciConfig},
+    offline::OfflineBackend,
+    s3::{S3Backend, S3Config, S3Credentials},
+    trusted::TrustedBackend,
 };
 
 // Some special exports for lal upgrade - canonical releases are on artifactory atm
@@ -10,8 +16,14 @@ pub use self::{
 pub use self::artifactory::{get_latest_lal_version, http_download_to_path, LatestLal};
 
 mod artifactory;
+mod chain;
 mod download;
+mod http;
 mod local;
+mod oci;
+mod offline;
+mod s3;
 mod traits;
+mod trusted;
 
 #[cfg(feature = "progress")] mod progress;
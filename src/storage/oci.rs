@@ -0,0 +1,543 @@
+#![allow(missing_docs)]
+
+use regex::Regex;
+use reqwest::{
+    header::{CONTENT_TYPE, LINK, LOCATION, WWW_AUTHENTICATE},
+    Client, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    vec::Vec,
+};
+use tokio::{fs::File as AsyncFile, io::AsyncWriteExt as _};
+
+use super::artifactory::Credentials;
+use crate::core::{checksum, CliError, LalResult};
+
+const MANIFEST_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const ARTIFACT_TYPE: &str = "application/vnd.lal.component.v1";
+const CONFIG_TYPE: &str = "application/vnd.lal.component.config.v1+json";
+const TARBALL_TYPE: &str = "application/vnd.lal.component.layer.v1.tar+gzip";
+const LOCKFILE_TYPE: &str = "application/vnd.lal.lockfile.v1+json";
+const TITLE: &str = "org.opencontainers.image.title";
+
+/// OCI registry locations
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OciConfig {
+    /// Url of the registry (e.g. `https://registry.example.com`)
+    pub registry: String,
+    /// Repository prefix that every component is stored under
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// Optional credentials (exchanged for a token, or sent with basic auth)
+    pub credentials: Option<Credentials>,
+}
+
+fn default_namespace() -> String {
+    "lal".into()
+}
+
+/// A content descriptor in an OCI manifest
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: BTreeMap<String, String>,
+}
+
+impl Descriptor {
+    fn new(media_type: &str, data: &[u8], title: Option<&str>) -> Self {
+        let mut annotations = BTreeMap::new();
+        if let Some(t) = title {
+            annotations.insert(TITLE.to_string(), t.to_string());
+        }
+        Descriptor {
+            media_type: media_type.into(),
+            digest: format!("sha256:{:x}", Sha256::digest(data)),
+            size: data.len() as u64,
+            annotations,
+        }
+    }
+}
+
+/// An OCI image manifest describing one published component
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct OciManifest {
+    schema_version: u32,
+    #[serde(default)]
+    media_type: Option<String>,
+    #[serde(default)]
+    artifact_type: Option<String>,
+    config: Descriptor,
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Deserialize)]
+struct TagList {
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct Catalog {
+    #[serde(default)]
+    repositories: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+use super::{Backend, Component};
+
+/// Artifact storage as OCI artifacts in a container registry
+///
+/// Every component is a repository under the configured namespace, and every
+/// build is tagged `<env>-<version>` with the tarball and lockfile as layers.
+pub struct OciBackend {
+    /// Registry config
+    pub config: OciConfig,
+    /// Cache directory
+    pub cache: PathBuf,
+    client: Client,
+    // bearer tokens by requested scope
+    tokens: Mutex<HashMap<String, String>>,
+}
+
+impl OciBackend {
+    pub fn new(cfg: &OciConfig, cache: &Path) -> LalResult<Self> {
+        let backend = OciBackend {
+            config: cfg.clone(),
+            cache: cache.to_path_buf(),
+            client: Client::new(),
+            tokens: Mutex::new(HashMap::new()),
+        };
+
+        Ok(backend)
+    }
+
+    fn repository(&self, name: &str) -> String {
+        let ns = self.config.namespace.trim_matches('/');
+        if ns.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", ns, name)
+        }
+    }
+
+    fn repo_url(&self, repo: &str) -> String {
+        format!("{}/v2/{}", self.config.registry.trim_end_matches('/'), repo)
+    }
+
+    fn manifest_url(&self, name: &str, version: u32, env: &str) -> String {
+        format!(
+            "{}/manifests/{}-{}",
+            self.repo_url(&self.repository(name)),
+            env,
+            version
+        )
+    }
+
+    // Exchange credentials for a bearer token as described by a `WWW-Authenticate` challenge
+    async fn fetch_token(&self, challenge: &str, scope: &str) -> LalResult<String> {
+        lazy_static! {
+            static ref PARAM: Regex = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+        }
+        let params: HashMap<String, String> = PARAM
+            .captures_iter(challenge)
+            .map(|c| (c[1].to_string(), c[2].to_string()))
+            .collect();
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| CliError::BackendFailure(format!("No realm in challenge {}", challenge)))?;
+        let mut url =
+            Url::parse(realm).map_err(|e| CliError::BackendFailure(format!("{} ({})", e, realm)))?;
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(service) = params.get("service") {
+                query.append_pair("service", service);
+            }
+            query.append_pair("scope", params.get("scope").map(String::as_str).unwrap_or(scope));
+        }
+        debug!("GET {}", url);
+        let mut req = self.client.get(url.clone());
+        if let Some(creds) = &self.config.credentials {
+            req = req.basic_auth(&creds.username, Some(&creds.password));
+        }
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            return Err(CliError::BackendFailure(format!(
+                "{} from token request to {}",
+                resp.status(),
+                realm
+            )));
+        }
+        let tok: TokenResponse = serde_json::from_str(&resp.text().await?)?;
+        tok.token
+            .or(tok.access_token)
+            .ok_or_else(|| CliError::BackendFailure(format!("No token returned from {}", realm)))
+    }
+
+    // Send a request, answering an authentication challenge from the registry if needed
+    async fn send(&self, req: RequestBuilder, scope: &str) -> LalResult<Response> {
+        let retry = req.try_clone();
+        let token = self.tokens.lock().unwrap().get(scope).cloned();
+        let resp = match token {
+            Some(t) => req.bearer_auth(t),
+            None => req,
+        }
+        .send()
+        .await?;
+        if resp.status() != StatusCode::UNAUTHORIZED {
+            return Ok(resp);
+        }
+        let (retry, challenge) = match (retry, resp.headers().get(WWW_AUTHENTICATE)) {
+            (Some(r), Some(c)) => (r, c.to_str().unwrap_or_default().to_string()),
+            _ => return Ok(resp),
+        };
+        trace!("Challenged with {}", challenge);
+        if challenge.to_lowercase().starts_with("bearer") {
+            let token = self.fetch_token(&challenge, scope).await?;
+            self.tokens
+                .lock()
+                .unwrap()
+                .insert(scope.to_string(), token.clone());
+            Ok(retry.bearer_auth(token).send().await?)
+        } else if let Some(creds) = &self.config.credentials {
+            Ok(retry
+                .basic_auth(&creds.username, Some(&creds.password))
+                .send()
+                .await?)
+        } else {
+            Err(CliError::MissingBackendCredentials)
+        }
+    }
+
+    /// Find all version numbers tagged for an environment
+    ///
+    /// Follows the `Link` header when the registry paginates the tags list.
+    async fn list_versions(&self, name: &str, env: &str) -> LalResult<Vec<u32>> {
+        lazy_static! {
+            static ref NEXT: Regex = Regex::new(r#"<([^>]+)>;\s*rel="next""#).unwrap();
+        }
+        let repo = self.repository(name);
+        let scope = format!("repository:{}:pull", repo);
+        let prefix = format!("{}-", env);
+        let mut versions = vec![];
+        let mut url = format!("{}/tags/list", self.repo_url(&repo));
+        loop {
+            debug!("GET {}", url);
+            let resp = self.send(self.client.get(&url), &scope).await?;
+            if resp.status() == StatusCode::NOT_FOUND {
+                break; // nothing ever published
+            }
+            if !resp.status().is_success() {
+                return Err(CliError::BackendFailure(format!(
+                    "{} from GET {}",
+                    resp.status(),
+                    url
+                )));
+            }
+            let next = resp
+                .headers()
+                .get(LINK)
+                .and_then(|l| l.to_str().ok())
+                .and_then(|l| NEXT.captures(l))
+                .map(|c| c[1].to_string());
+            let list: TagList = serde_json::from_str(&resp.text().await?)?;
+            for tag in list.tags.unwrap_or_default() {
+                if let Some(Ok(n)) = tag.strip_prefix(&prefix).map(str::parse::<u32>) {
+                    versions.push(n);
+                }
+            }
+            match next {
+                Some(n) => url = self.resolve(&url, &n)?,
+                None => break,
+            }
+        }
+        versions.sort_by(|a, b| b.cmp(a)); // sort by version number descending
+        Ok(versions)
+    }
+
+    /// Find all components in the namespace with the catalog API
+    ///
+    /// Environments are only told apart by tags, so this lists components of all of them.
+    async fn list_repositories(&self) -> LalResult<Vec<String>> {
+        lazy_static! {
+            static ref NEXT: Regex = Regex::new(r#"<([^>]+)>;\s*rel="next""#).unwrap();
+        }
+        let ns = self.config.namespace.trim_matches('/');
+        let prefix = if ns.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", ns)
+        };
+        let mut names = vec![];
+        let mut url = format!("{}/v2/_catalog", self.config.registry.trim_end_matches('/'));
+        loop {
+            debug!("GET {}", url);
+            let resp = self.send(self.client.get(&url), "registry:catalog:*").await?;
+            if !resp.status().is_success() {
+                return Err(CliError::BackendFailure(format!(
+                    "{} from GET {}",
+                    resp.status(),
+                    url
+                )));
+            }
+            let next = resp
+                .headers()
+                .get(LINK)
+                .and_then(|l| l.to_str().ok())
+                .and_then(|l| NEXT.captures(l))
+                .map(|c| c[1].to_string());
+            let catalog: Catalog = serde_json::from_str(&resp.text().await?)?;
+            for repo in catalog.repositories.unwrap_or_default() {
+                match repo.strip_prefix(&prefix) {
+                    Some(name) if !name.contains('/') => names.push(name.to_string()),
+                    _ => {}
+                }
+            }
+            match next {
+                Some(n) => url = self.resolve(&url, &n)?,
+                None => break,
+            }
+        }
+        Ok(names)
+    }
+
+    // Registries are free to hand out relative urls for pagination and uploads
+    fn resolve(&self, base: &str, url: &str) -> LalResult<String> {
+        Url::parse(base)
+            .and_then(|b| b.join(url))
+            .map(String::from)
+            .map_err(|e| CliError::BackendFailure(format!("{} ({})", e, url)))
+    }
+
+    async fn get_manifest(&self, url: &str, scope: &str) -> LalResult<OciManifest> {
+        debug!("GET {}", url);
+        let req = self.client.get(url).header("Accept", MANIFEST_TYPE);
+        let resp = self.send(req, scope).await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Err(CliError::MissingArtifact(url.into()));
+        }
+        if !resp.status().is_success() {
+            return Err(CliError::BackendFailure(format!(
+                "{} from GET {}",
+                resp.status(),
+                url
+            )));
+        }
+        Ok(serde_json::from_str(&resp.text().await?)?)
+    }
+
+    // Upload a blob unless the registry already has it
+    async fn push_blob(&self, repo: &str, desc: &Descriptor, data: Vec<u8>) -> LalResult<()> {
+        let scope = format!("repository:{}:pull,push", repo);
+        let blob_url = format!("{}/blobs/{}", self.repo_url(repo), desc.digest);
+        let resp = self.send(self.client.head(&blob_url), &scope).await?;
+        if resp.status().is_success() {
+            debug!("Blob {} already exists", desc.digest);
+            return Ok(());
+        }
+
+        let start_url = format!("{}/blobs/uploads/", self.repo_url(repo));
+        debug!("POST {}", start_url);
+        let resp = self.send(self.client.post(&start_url), &scope).await?;
+        if resp.status() != StatusCode::ACCEPTED {
+            return Err(CliError::UploadFailure(format!(
+                "{} from POST {}",
+                resp.status(),
+                start_url
+            )));
+        }
+        let location = resp
+            .headers()
+            .get(LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .ok_or_else(|| CliError::UploadFailure(format!("No upload location from POST {}", start_url)))?;
+        let mut upload_url = Url::parse(&self.resolve(&start_url, location)?).expect("resolved url");
+        upload_url.query_pairs_mut().append_pair("digest", &desc.digest);
+
+        debug!("PUT {}", upload_url);
+        let req = self
+            .client
+            .put(upload_url.clone())
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(data);
+        let resp = self.send(req, &scope).await?;
+        let respstr = format!("{} from PUT {}", resp.status(), upload_url);
+        if resp.status() != StatusCode::CREATED {
+            return Err(CliError::UploadFailure(respstr));
+        }
+        debug!("{}", respstr);
+        Ok(())
+    }
+}
+
+/// Artifact backend trait for `OciBackend`
+///
+/// This is intended to be used by the caching trait `CachedBackend`, but for
+/// specific low-level use cases, these methods can be used directly.
+#[async_trait::async_trait]
+impl Backend for OciBackend {
+    async fn get_versions(&self, name: &str, loc: &str) -> LalResult<Vec<u32>> {
+        self.list_versions(name, loc).await
+    }
+
+    async fn get_latest_version(&self, name: &str, loc: &str) -> LalResult<u32> {
+        if let Some(&last) = self.list_versions(name, loc).await?.first() {
+            return Ok(last);
+        }
+        Err(CliError::BackendFailure("No versions found in registry".into()))
+    }
+
+    async fn list_components(&self, _loc: &str) -> LalResult<Vec<String>> {
+        let mut names = self.list_repositories().await?;
+        names.sort();
+        Ok(names)
+    }
+
+    async fn get_component_info(&self, name: &str, version: Option<u32>, loc: &str) -> LalResult<Component> {
+        let v = if let Some(ver) = version {
+            ver
+        } else {
+            self.get_latest_version(name, loc).await?
+        };
+        // layers are found through the manifest by their title in `raw_fetch`
+        Ok(Component {
+            location: format!("{}#{}.tar.gz", self.manifest_url(name, v, loc), name),
+            version: v,
+            name: name.into(),
+            sha256: None,
+        })
+    }
+
+    async fn publish_artifact(
+        &self,
+        _home: Option<&Path>,
+        component_dir: &Path,
+        name: &str,
+        version: u32,
+        env: &str,
+    ) -> LalResult<()> {
+        // this fn basically assumes all the sanity checks have been performed
+        // files must exist and lockfile must be sensible
+        let artdir = component_dir.join("./ARTIFACT");
+        let repo = self.repository(name);
+        let scope = format!("repository:{}:pull,push", repo);
+
+        let tarname = format!("{}.tar.gz", name);
+        let tarball = fs::read(artdir.join(&tarname))?;
+        let lockfile = fs::read(artdir.join("lockfile.json"))?;
+        let config = b"{}".to_vec();
+
+        let manifest = OciManifest {
+            schema_version: 2,
+            media_type: Some(MANIFEST_TYPE.into()),
+            artifact_type: Some(ARTIFACT_TYPE.into()),
+            config: Descriptor::new(CONFIG_TYPE, &config, None),
+            layers: vec![
+                Descriptor::new(TARBALL_TYPE, &tarball, Some(&tarname)),
+                Descriptor::new(LOCKFILE_TYPE, &lockfile, Some("lockfile.json")),
+            ],
+        };
+        self.push_blob(&repo, &manifest.config, config).await?;
+        self.push_blob(&repo, &manifest.layers[0], tarball).await?;
+        self.push_blob(&repo, &manifest.layers[1], lockfile).await?;
+
+        // the tag only appears once all blobs are in place
+        let url = self.manifest_url(name, version, env);
+        debug!("PUT {}", url);
+        let req = self
+            .client
+            .put(&url)
+            .header(CONTENT_TYPE, MANIFEST_TYPE)
+            .body(serde_json::to_vec(&manifest)?);
+        let resp = self.send(req, &scope).await?;
+        let respstr = format!("{} from PUT {}", resp.status(), url);
+        if resp.status() != StatusCode::CREATED {
+            return Err(CliError::UploadFailure(respstr));
+        }
+        debug!("{}", respstr);
+        Ok(())
+    }
+
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String {
+        format!("{}#lockfile.json", self.manifest_url(name, version, loc))
+    }
+
+    fn get_cache_dir(&self) -> PathBuf {
+        self.cache.clone()
+    }
+
+    /// Fetch a layer given as `<manifest url>#<layer title>`
+    ///
+    /// Blobs are content addressed, so the download is always checked against its digest.
+    async fn raw_fetch(&self, url: &str, dest: &Path) -> LalResult<()> {
+        let (manifest_url, title) = url
+            .split_once('#')
+            .ok_or_else(|| CliError::BackendFailure(format!("No layer title in {}", url)))?;
+        let repo_url = manifest_url
+            .rsplit_once("/manifests/")
+            .map(|(r, _)| r)
+            .ok_or_else(|| CliError::BackendFailure(format!("Not a manifest url: {}", url)))?;
+        let repo = repo_url
+            .split_once("/v2/")
+            .map(|(_, r)| r)
+            .ok_or_else(|| CliError::BackendFailure(format!("Not a registry url: {}", url)))?;
+        let scope = format!("repository:{}:pull", repo);
+
+        let manifest = self.get_manifest(manifest_url, &scope).await?;
+        let layer = manifest
+            .layers
+            .iter()
+            .find(|l| l.annotations.get(TITLE).map(String::as_str) == Some(title))
+            .ok_or_else(|| CliError::MissingArtifact(format!("{} layer in {}", title, manifest_url)))?;
+
+        let blob_url = format!("{}/blobs/{}", repo_url, layer.digest);
+        debug!("GET {}", blob_url);
+        let mut resp = self.send(self.client.get(&blob_url), &scope).await?;
+        if !resp.status().is_success() {
+            return Err(CliError::BackendFailure(format!(
+                "GET request with {}",
+                resp.status()
+            )));
+        }
+
+        #[cfg(feature = "progress")]
+        let pb = {
+            let label = dest.file_name().unwrap_or_default().to_string_lossy();
+            super::progress::labelled_bar(resp.content_length().unwrap_or(layer.size), &label, "yellow")
+        };
+
+        let mut f = AsyncFile::create(dest).await?;
+        while let Some(chunk) = resp.chunk().await? {
+            #[cfg(feature = "progress")]
+            pb.inc(chunk.len() as u64);
+            f.write_all(&chunk).await?;
+        }
+        f.flush().await?;
+
+        let found = format!("sha256:{}", checksum::sha256_file(dest)?);
+        if found != layer.digest {
+            let _ = fs::remove_file(dest);
+            return Err(CliError::ChecksumMismatch(
+                title.into(),
+                layer.digest.clone(),
+                found,
+            ));
+        }
+        Ok(())
+    }
+}
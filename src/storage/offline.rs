@@ -0,0 +1,131 @@
+#![allow(missing_docs)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    vec::Vec,
+};
+
+use crate::core::{CliError, LalResult};
+
+use super::{Backend, Component};
+
+/// Stand-in for the configured backend that only knows about the cache
+///
+/// Used with `--offline`, so everything is resolved from the layout under
+/// `cache/environments/<env>/<name>/<version>/` without touching the network.
+pub struct OfflineBackend {
+    /// Cache directory
+    pub cache: PathBuf,
+}
+
+impl OfflineBackend {
+    pub fn new(cache: &Path) -> Self {
+        OfflineBackend {
+            cache: cache.to_path_buf(),
+        }
+    }
+
+    fn version_dir(&self, name: &str, loc: &str) -> PathBuf {
+        self.cache.join("environments").join(loc).join(name)
+    }
+
+    fn tarball(&self, name: &str, version: u32, loc: &str) -> PathBuf {
+        self.version_dir(name, loc)
+            .join(version.to_string())
+            .join(format!("{}.tar.gz", name))
+    }
+}
+
+/// Artifact backend trait for `OfflineBackend`
+///
+/// This is intended to be used by the caching trait `CachedBackend`, but for
+/// specific low-level use cases, these methods can be used directly.
+#[async_trait::async_trait]
+impl Backend for OfflineBackend {
+    async fn get_versions(&self, name: &str, loc: &str) -> LalResult<Vec<u32>> {
+        let mut versions = vec![];
+        // nothing cached is not an error here - it just has no versions
+        if let Ok(entries) = fs::read_dir(self.version_dir(name, loc)) {
+            for entry in entries {
+                let entry = entry?;
+                if let Some(v) = entry.file_name().to_str().and_then(|f| f.parse().ok()) {
+                    if self.tarball(name, v, loc).is_file() {
+                        versions.push(v);
+                    }
+                }
+            }
+        }
+        versions.sort_by(|a, b| b.cmp(a)); // sort by version number descending
+        Ok(versions)
+    }
+
+    async fn get_latest_version(&self, name: &str, loc: &str) -> LalResult<u32> {
+        if let Some(&last) = self.get_versions(name, loc).await?.first() {
+            return Ok(last);
+        }
+        Err(CliError::NotCached(name.into(), loc.into()))
+    }
+
+    async fn list_components(&self, loc: &str) -> LalResult<Vec<String>> {
+        let mut names = vec![];
+        if let Ok(entries) = fs::read_dir(self.cache.join("environments").join(loc)) {
+            for entry in entries {
+                let entry = entry?;
+                if let Some(name) = entry.file_name().to_str() {
+                    // only what has a cached version can be used
+                    if !self.get_versions(name, loc).await?.is_empty() {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    async fn get_component_info(&self, name: &str, version: Option<u32>, loc: &str) -> LalResult<Component> {
+        let v = if let Some(ver) = version {
+            ver
+        } else {
+            self.get_latest_version(name, loc).await?
+        };
+        let tarball = self.tarball(name, v, loc);
+        if !tarball.is_file() {
+            return Err(CliError::NotCached(format!("{}={}", name, v), loc.into()));
+        }
+        Ok(Component {
+            location: tarball.to_string_lossy().into(),
+            version: v,
+            name: name.into(),
+            sha256: None,
+        })
+    }
+
+    async fn publish_artifact(
+        &self,
+        _home: Option<&Path>,
+        _component_dir: &Path,
+        _name: &str,
+        _version: u32,
+        _env: &str,
+    ) -> LalResult<()> {
+        Err(CliError::Offline("publish".into()))
+    }
+
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String {
+        self.tarball(name, version, loc)
+            .with_file_name("lockfile.json")
+            .to_string_lossy()
+            .into()
+    }
+
+    fn get_cache_dir(&self) -> PathBuf {
+        self.cache.clone()
+    }
+
+    // Only reached when something is missing from the cache
+    async fn raw_fetch(&self, location: &str, _dest: &Path) -> LalResult<()> {
+        Err(CliError::Offline(format!("download of {}", location)))
+    }
+}
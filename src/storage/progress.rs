@@ -1,9 +1,48 @@
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use std::{
     io,
     io::{Read, Seek, SeekFrom},
 };
 
+lazy_static! {
+    // All bars are drawn through one target so concurrent fetches stack rather than overwrite
+    static ref PROGRESS: MultiProgress = MultiProgress::new();
+}
+
+/// Create a progress bar labelled with the thing being worked on
+///
+/// The bar is attached to a shared display, so several can be active at once.
+pub fn labelled_bar(len: u64, label: &str, colour: &str) -> ProgressBar {
+    let template = format!(
+        "{{prefix:>24}} {{bar:40.{}/black}} {{bytes}}/{{total_bytes}} ({{eta}})",
+        colour
+    );
+    let pb = PROGRESS.add(ProgressBar::new(len));
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&template)
+            .expect("valid progress template"),
+    );
+    pb.set_prefix(label.to_string());
+    pb
+}
+
+/// Create a spinner for work of unknown length, like downloads without a content-length
+pub fn labelled_spinner(label: &str, colour: &str) -> ProgressBar {
+    let template = format!(
+        "{{prefix:>24}} {{spinner:.{}}} {{bytes}} ({{bytes_per_sec}})",
+        colour
+    );
+    let pb = PROGRESS.add(ProgressBar::new_spinner());
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template(&template)
+            .expect("valid progress template"),
+    );
+    pb.set_prefix(label.to_string());
+    pb
+}
+
 /// Wrapper around a `Read` that reports the progress made.
 ///
 /// Used to monitor slow IO readers
@@ -14,13 +53,10 @@ pub struct ProgressReader<R: Read + Seek> {
 }
 
 impl<R: Read + Seek> ProgressReader<R> {
-    pub fn new(mut rdr: R) -> io::Result<ProgressReader<R>> {
+    pub fn new(mut rdr: R, label: &str) -> io::Result<ProgressReader<R>> {
         let len = rdr.seek(SeekFrom::End(0))?;
         rdr.seek(SeekFrom::Start(0))?;
-        let pb = ProgressBar::new(len);
-        pb.set_style(
-            ProgressStyle::default_bar().template("{bar:40.green/black} {bytes}/{total_bytes} ({eta})"),
-        );
+        let pb = labelled_bar(len, label, "green");
         Ok(ProgressReader { rdr, pb })
     }
 }
@@ -1,13 +1,12 @@
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-use super::{ArtifactoryConfig, LocalConfig};
-use crate::core::LalResult;
+use super::{ArtifactoryConfig, ChainConfig, HttpConfig, LocalConfig, OciConfig, S3Config};
+use crate::core::{signing::SignaturePolicy, LalResult, VersionRequirement};
 
 /// An enum struct for the currently configured `Backend`
 ///
 /// Any other implementations should be imported and listed here.
-/// Currently only artifactory is supported.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum BackendConfiguration {
     /// Config for the `ArtifactoryBackend`
@@ -17,6 +16,22 @@ pub enum BackendConfiguration {
     /// Config for the `LocalBackend`
     #[serde(rename = "local")]
     Local(LocalConfig),
+
+    /// Config for the `S3Backend`
+    #[serde(rename = "s3")]
+    S3(S3Config),
+
+    /// Config for the `HttpBackend`
+    #[serde(rename = "http")]
+    Http(HttpConfig),
+
+    /// Config for the `OciBackend`
+    #[serde(rename = "oci")]
+    Oci(OciConfig),
+
+    /// Config for the `ChainBackend`
+    #[serde(rename = "chain")]
+    Chain(ChainConfig),
 }
 
 /// Artifactory is the default backend
@@ -38,6 +53,8 @@ pub struct Component {
     ///
     /// No restriction on how this information is encoded, but it must work with `raw_fetch`
     pub location: String,
+    /// SHA-256 digest of the tarball (only known once it has been retrieved)
+    pub sha256: Option<String>,
 }
 
 /// Properties a storage backend of artifacts should have
@@ -51,6 +68,11 @@ pub trait Backend {
     async fn get_versions(&self, name: &str, loc: &str) -> LalResult<Vec<u32>>;
     /// Get the latest version of a component
     async fn get_latest_version(&self, name: &str, loc: &str) -> LalResult<u32>;
+    /// Get the names of all components published to a location, sorted
+    ///
+    /// Backends that can not tell locations apart may list components that have
+    /// no versions in this one.
+    async fn list_components(&self, loc: &str) -> LalResult<Vec<String>>;
 
     /// Get the version and location information of a component
     ///
@@ -69,6 +91,11 @@ pub trait Backend {
         env: &str,
     ) -> LalResult<()>;
 
+    /// Get the location of the lockfile published next to a component's tarball
+    ///
+    /// This must be fetchable with `raw_fetch` like `Component::location`
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String;
+
     /// Raw fetch of location to a destination
     ///
     /// location can be a HTTPS url / a system path / etc (depending on the backend)
@@ -78,6 +105,13 @@ pub trait Backend {
     ///
     /// This has to be in here for `CachedBackend` to have a straight dependency
     fn get_cache_dir(&self) -> PathBuf;
+
+    /// Which signatures retrieved components must have, if that is checked at all
+    ///
+    /// Only a `TrustedBackend` has one.
+    fn signature_policy(&self) -> Option<&SignaturePolicy> {
+        None
+    }
 }
 
 /// A secondary trait that builds upon the Backend trait
@@ -92,6 +126,14 @@ pub trait CachedBackend: Backend {
         environments: Vec<String>,
     ) -> LalResult<Vec<u32>>;
 
+    /// Resolve a version requirement to the highest version available in all environments
+    async fn resolve_version(
+        &self,
+        name: &str,
+        requirement: &VersionRequirement,
+        environments: Vec<String>,
+    ) -> LalResult<u32>;
+
     /// Retrieve the location to a cached published component (downloading if necessary)
     async fn retrieve_published_component(
         &self,
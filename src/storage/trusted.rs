@@ -0,0 +1,71 @@
+use std::{
+    path::{Path, PathBuf},
+    vec::Vec,
+};
+
+use super::{Backend, Component};
+use crate::core::{signing::SignaturePolicy, LalResult};
+
+/// A backend that only hands out components with signatures its policy accepts
+///
+/// Everything is delegated to the wrapped backend. The signatures are checked by
+/// `CachedBackend` whenever a published component is retrieved, cached or not.
+pub struct TrustedBackend<B> {
+    inner: B,
+    policy: SignaturePolicy,
+}
+
+impl<B: Backend> TrustedBackend<B> {
+    /// Wrap a backend with a signature policy
+    pub fn new(inner: B, policy: SignaturePolicy) -> Self {
+        TrustedBackend { inner, policy }
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: Backend + Send + Sync> Backend for TrustedBackend<B> {
+    async fn get_versions(&self, name: &str, loc: &str) -> LalResult<Vec<u32>> {
+        self.inner.get_versions(name, loc).await
+    }
+
+    async fn get_latest_version(&self, name: &str, loc: &str) -> LalResult<u32> {
+        self.inner.get_latest_version(name, loc).await
+    }
+
+    async fn list_components(&self, loc: &str) -> LalResult<Vec<String>> {
+        self.inner.list_components(loc).await
+    }
+
+    async fn get_component_info(&self, name: &str, ver: Option<u32>, loc: &str) -> LalResult<Component> {
+        self.inner.get_component_info(name, ver, loc).await
+    }
+
+    async fn publish_artifact(
+        &self,
+        home: Option<&Path>,
+        component_dir: &Path,
+        name: &str,
+        version: u32,
+        env: &str,
+    ) -> LalResult<()> {
+        self.inner
+            .publish_artifact(home, component_dir, name, version, env)
+            .await
+    }
+
+    fn get_lockfile_location(&self, name: &str, version: u32, loc: &str) -> String {
+        self.inner.get_lockfile_location(name, version, loc)
+    }
+
+    async fn raw_fetch(&self, location: &str, dest: &Path) -> LalResult<()> {
+        self.inner.raw_fetch(location, dest).await
+    }
+
+    fn get_cache_dir(&self) -> PathBuf {
+        self.inner.get_cache_dir()
+    }
+
+    fn signature_policy(&self) -> Option<&SignaturePolicy> {
+        Some(&self.policy)
+    }
+}
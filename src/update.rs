@@ -1,15 +1,81 @@
-use super::{CliError, LalResult, Manifest};
-use crate::storage::CachedBackend;
+use super::{CliError, LalResult, Manifest, ResolvedLock, VersionRequirement};
+use crate::storage::{CachedBackend, Component};
+use futures::stream::{self, StreamExt};
 use std::{cmp::Ordering, path::Path};
 
+// Fetch a single "component" or "component=version" string into INPUT
+//
+// The version may also be a semver range, which resolves to the highest matching version.
+// Stashed components are unpacked, but yield nothing to save as they cannot go in the manifest.
+async fn update_one(
+    component_dir: &Path,
+    backend: &dyn CachedBackend,
+    comp: &str,
+    env: &str,
+) -> LalResult<Option<(Component, VersionRequirement)>> {
+    info!("Fetch {} {}", env, comp);
+    if comp.contains('=') {
+        let pair: Vec<&str> = comp.split('=').collect();
+        if let Ok(n) = pair[1].parse::<u32>() {
+            // standard fetch with an integer version
+            let c = backend
+                .unpack_published_component(component_dir, pair[0], Some(n), env)
+                .await?;
+            Ok(Some((c, VersionRequirement::Exact(n))))
+        } else if let (Err(_), Ok(req)) = (
+            backend.retrieve_stashed_component(pair[0], pair[1]),
+            VersionRequirement::parse(pair[1]),
+        ) {
+            // not stashed, but a valid range - fetch the best match from this environment
+            let ver = backend
+                .resolve_version(pair[0], &req, vec![env.to_string()])
+                .await?;
+            info!("Fetch {} {}={}", env, pair[0], ver);
+            let c = backend
+                .unpack_published_component(component_dir, pair[0], Some(ver), env)
+                .await?;
+            Ok(Some((c, req)))
+        } else {
+            // fetch from stash - this does not go into `updated` it it succeeds
+            // because we wont and cannot save stashed versions in the manifest
+            backend.unpack_stashed_component(component_dir, pair[0], pair[1])?;
+            Ok(None)
+        }
+    } else {
+        // fetch without a specific version (latest)
+
+        // First, since this potentially goes in the manifest
+        // make sure the version is found for all supported environments:
+        let supported_versions = backend
+            .get_latest_supported_versions(comp, vec![env.to_string()])
+            .await?;
+
+        let ver = supported_versions
+            .into_iter()
+            .max()
+            .ok_or_else(|| CliError::NoIntersectedVersion(comp.into()))?;
+        info!("Fetch {} {}={}", env, comp, ver);
+
+        let c = backend
+            .unpack_published_component(component_dir, comp, Some(ver), env)
+            .await?;
+        Ok(Some((c, VersionRequirement::Exact(ver))))
+    }
+}
+
 /// Update specific dependencies outside the manifest
 ///
 /// Multiple "components=version" strings can be supplied, where the version is optional.
-/// If no version is supplied, latest is fetched.
+/// If no version is supplied, latest is fetched. The version can also be a semver range
+/// like `>=41, <50`, which fetches the highest matching version and saves the range.
 ///
 /// If installation was successful, the fetched tarballs are unpacked into `./INPUT`.
 /// If one `save` or `savedev` was set, the fetched versions are also updated in the
-/// manifest. This provides an easy way to not have to deal with strict JSON manually.
+/// manifest and pinned in `.lal/lock.json`.
+/// This provides an easy way to not have to deal with strict JSON manually.
+///
+/// Up to `jobs` components are fetched concurrently.
+#[allow(clippy::too_many_arguments)]
 pub async fn update(
     component_dir: &Path,
     manifest: &Manifest,
@@ -18,67 +84,37 @@ pub async fn update(
     save: bool,
     savedev: bool,
     env: &str,
+    jobs: usize,
 ) -> LalResult<()> {
     debug!("Update specific deps: {:?}", components);
 
-    let mut error = None;
-    let mut updated = Vec::with_capacity(components.len());
+    // stashed names are free form, but anything from the backend must be lowercase
     for comp in &components {
-        info!("Fetch {} {}", env, comp);
-        if comp.contains('=') {
-            let pair: Vec<&str> = comp.split('=').collect();
-            if let Ok(n) = pair[1].parse::<u32>() {
-                if pair[0].to_lowercase() != pair[0] {
-                    return Err(CliError::InvalidComponentName(pair[0].into()));
-                }
-                // standard fetch with an integer version
-                match backend
-                    .unpack_published_component(&component_dir, pair[0], Some(n), env)
-                    .await
-                {
-                    Ok(c) => updated.push(c),
-                    Err(e) => {
-                        warn!("Failed to update {} ({})", pair[0], e);
-                        error = Some(e);
-                    }
-                }
-            } else {
-                // fetch from stash - this does not go into `updated` it it succeeds
-                // because we wont and cannot save stashed versions in the manifest
-                let _ = backend
-                    .unpack_stashed_component(&component_dir, pair[0], pair[1])
-                    .map_err(|e| {
-                        warn!("Failed to update {} from stash ({})", pair[0], e);
-                        error = Some(e);
-                    });
-            }
-        } else {
-            if &comp.to_lowercase() != comp {
-                return Err(CliError::InvalidComponentName(comp.clone()));
-            }
-            // fetch without a specific version (latest)
-
-            // First, since this potentially goes in the manifest
-            // make sure the version is found for all supported environments:
-            let supported_versions = backend
-                .get_latest_supported_versions(comp, vec![env.to_string()])
-                .await?;
+        let pair: Vec<&str> = comp.split('=').collect();
+        let stashed = pair.len() > 1 && pair[1].parse::<u32>().is_err();
+        if !stashed && pair[0].to_lowercase() != pair[0] {
+            return Err(CliError::InvalidComponentName(pair[0].into()));
+        }
+    }
 
-            let ver = supported_versions
-                .into_iter()
-                .max()
-                .ok_or_else(|| CliError::NoIntersectedVersion(comp.clone()))?;
-            info!("Fetch {} {}={}", env, comp, ver);
+    let results = stream::iter(
+        components
+            .iter()
+            .map(|comp| async move { (comp, update_one(component_dir, backend, comp, env).await) }),
+    )
+    .buffered(jobs.max(1))
+    .collect::<Vec<_>>()
+    .await;
 
-            match backend
-                .unpack_published_component(&component_dir, comp, Some(ver), env)
-                .await
-            {
-                Ok(c) => updated.push(c),
-                Err(e) => {
-                    warn!("Failed to update {} ({})", &comp, e);
-                    error = Some(e);
-                }
+    let mut error = None;
+    let mut updated = Vec::with_capacity(components.len());
+    for (comp, res) in results {
+        match res {
+            Ok(Some(c)) => updated.push(c),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to update {} ({})", comp, e);
+                error = Some(e);
             }
         }
     }
@@ -95,26 +131,42 @@ pub async fn update(
         } else {
             mf.devDependencies.clone()
         };
-        for c in &updated {
+        for (c, req) in &updated {
             debug!("Successfully updated {} at version {}", &c.name, c.version);
-            if hmap.contains_key(&c.name) {
-                let val = hmap.get_mut(&c.name).unwrap();
-                match c.version.cmp(&*val) {
-                    Ordering::Greater => info!("Upgrading {} from {} to {}", c.name, *val, c.version),
-                    Ordering::Less => warn!("Downgrading {} from {} to {}", c.name, *val, c.version),
-                    Ordering::Equal => info!("Maintaining {} at version {}", c.name, c.version),
+            match hmap.get_mut(&c.name) {
+                // keep ranges that still cover what we fetched
+                Some(val) if val.matches(c.version) && *req == VersionRequirement::Exact(c.version) => {
+                    info!("Maintaining {} at {}", c.name, val)
+                }
+                Some(val) => {
+                    match *val {
+                        VersionRequirement::Exact(v) => match c.version.cmp(&v) {
+                            Ordering::Greater => info!("Upgrading {} from {} to {}", c.name, v, req),
+                            Ordering::Less => warn!("Downgrading {} from {} to {}", c.name, v, req),
+                            Ordering::Equal => info!("Changing {} from {} to {}", c.name, v, req),
+                        },
+                        VersionRequirement::Range(_) => info!("Changing {} from {} to {}", c.name, val, req),
+                    }
+                    *val = req.clone();
+                }
+                None => {
+                    hmap.insert(c.name.clone(), req.clone());
                 }
-                *val = c.version;
-            } else {
-                hmap.insert(c.name.clone(), c.version);
             }
         }
+        // keep the resolved lock in line with what was saved
+        let mut lock = ResolvedLock::read(component_dir)?;
+        for (c, _) in &updated {
+            lock.pin(&c.name, hmap[&c.name].clone(), c.version);
+            lock.record(c, env);
+        }
         if save {
             mf.dependencies = hmap;
         } else {
             mf.devDependencies = hmap;
         }
         mf.write()?;
+        lock.write(component_dir)?;
     }
     Ok(())
 }
@@ -131,6 +183,7 @@ pub async fn update_all(
     save: bool,
     dev: bool,
     env: &str,
+    jobs: usize,
 ) -> LalResult<()> {
     let deps: Vec<String> = if dev {
         manifest.devDependencies.keys().cloned().collect()
@@ -145,6 +198,7 @@ pub async fn update_all(
         save && !dev,
         save && dev,
         env,
+        jobs,
     )
     .await
 }
@@ -36,7 +36,7 @@ fn identify_exe() -> LalResult<ExeInfo> {
     let ldd_output = Command::new("ldd").arg(&pth).output()?;
     let ldd_str = String::from_utf8_lossy(&ldd_output.stdout);
     let dynamic = !ldd_str.contains("not a dynamic executable");
-    let path: String = pth.to_str()?.into();
+    let path: String = pth.to_str().ok_or(CliError::UnicodeError)?.into();
     let debug = path.contains("debug"); // cheap check for compiled versions
     let prefix = if path.contains("/bin/") {
         let v: Vec<&str> = path.split("/bin/").collect();
@@ -64,7 +64,7 @@ fn extract_tarball(input: PathBuf, output: &Path) -> LalResult<()> {
     use tar::Archive;
 
     let data = fs::File::open(input)?;
-    let decompressed = GzDecoder::new(data)?; // decoder reads data
+    let decompressed = GzDecoder::new(data); // decoder reads data
     let mut archive = Archive::new(decompressed); // Archive reads decoded
 
     archive.unpack(&output)?;
@@ -82,8 +82,15 @@ fn verify_permissions(exe: &ExeInfo) -> LalResult<()> {
     Ok(())
 }
 
+// Install prefix of a static executable
+fn install_prefix(exe: &ExeInfo) -> LalResult<PathBuf> {
+    exe.prefix
+        .clone()
+        .ok_or_else(|| CliError::UpgradeValidationFailure(format!("no install prefix for {}", exe.path)))
+}
+
 fn overwrite_exe(latest: &LatestLal, exe: &ExeInfo) -> LalResult<()> {
-    let prefix = exe.prefix.clone()?;
+    let prefix = install_prefix(exe)?;
     extract_tarball(prefix.join("lal.tar.gz"), &prefix)?;
     validate_exe(latest, exe)?;
     Ok(())
@@ -102,8 +109,8 @@ fn validate_exe(latest: &LatestLal, exe: &ExeInfo) -> LalResult<()> {
     Ok(())
 }
 
-fn upgrade_exe(latest: &LatestLal, exe: &ExeInfo) -> LalResult<()> {
-    let prefix = exe.prefix.clone()?;
+async fn upgrade_exe(latest: &LatestLal, exe: &ExeInfo) -> LalResult<()> {
+    let prefix = install_prefix(exe)?;
     // 0. sanity - could we actually upgrade if we tried?
     verify_permissions(exe)
         .map_err(|_| CliError::MissingPrefixPermissions(prefix.to_string_lossy().into()))?;
@@ -118,7 +125,7 @@ fn upgrade_exe(latest: &LatestLal, exe: &ExeInfo) -> LalResult<()> {
     // 2. make sure we can download the tarball before starting
     let tar_dest = prefix.join("lal.tar.gz");
     info!("Downloading tarball to {}", tar_dest.display());
-    http_download_to_path(&latest.url, &tar_dest)?;
+    http_download_to_path(&latest.url, &tar_dest, &Default::default()).await?;
     info!("Backing up {} to {}", exe.path, old_file.display());
     fs::rename(&exe.path, &old_file)?; // need to undo this if we fail
 
@@ -146,8 +153,8 @@ fn upgrade_exe(latest: &LatestLal, exe: &ExeInfo) -> LalResult<()> {
 /// This will query for the latest version, and upgrade in the one possible case.
 /// If a newer version found (> in semver), and it's a static executable,
 /// then an executable upgrade is attempted from the new release url.
-pub fn upgrade(silent: bool) -> LalResult<bool> {
-    let latest = get_latest_lal_version()?;
+pub async fn upgrade(silent: bool) -> LalResult<bool> {
+    let latest = get_latest_lal_version().await?;
     let exe = identify_exe()?;
 
     if latest.version > exe.version {
@@ -163,7 +170,7 @@ pub fn upgrade(silent: bool) -> LalResult<bool> {
         } else if exe.prefix.is_some() {
             // install lal in the prefix it's normally in
             info!("Upgrading...");
-            upgrade_exe(&latest, &exe)?;
+            upgrade_exe(&latest, &exe).await?;
             info!("lal upgraded successfully to {} at {}", latest.version, exe.path);
             println!();
         } else {
@@ -1,7 +1,61 @@
-use super::{LalResult, Lockfile, Manifest};
+use super::{CliError, LalResult, Lockfile, Manifest};
 use crate::input;
+use serde::Serialize;
 use std::path::Path;
 
+/// A failed check from `verify_report`
+#[derive(Serialize, Debug)]
+pub struct VerifyFinding {
+    /// Name of the check that failed
+    pub check: &'static str,
+    /// Category of the error, as in `CliError::category`
+    pub category: &'static str,
+    /// Exit code of the error, as in `CliError::exit_code`
+    pub code: i32,
+    /// The error message
+    pub message: String,
+}
+
+/// Every problem `verify` finds, rather than just the first
+#[derive(Serialize, Debug)]
+pub struct VerifyReport {
+    /// Environment the dependencies were verified against
+    pub environment: String,
+    /// Whether only the simple checks were run
+    pub simple: bool,
+    /// Failed checks, in the order they were run
+    pub findings: Vec<VerifyFinding>,
+    /// The error of the first failed check, for `verify` to return
+    #[serde(skip)]
+    first: Option<CliError>,
+}
+
+impl VerifyReport {
+    fn check(&mut self, check: &'static str, res: LalResult<()>) -> bool {
+        if let Err(e) = res {
+            self.findings.push(VerifyFinding {
+                check,
+                category: e.category(),
+                code: e.exit_code(),
+                message: e.to_string(),
+            });
+            self.first.get_or_insert(e);
+            return false;
+        }
+        true
+    }
+
+    /// Whether all checks passed
+    pub fn ok(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Exit code of the first finding, or 0 if there were none
+    pub fn exit_code(&self) -> i32 {
+        self.findings.first().map_or(0, |f| f.code)
+    }
+}
+
 /// Verifies that `./INPUT` satisfies all strictness conditions.
 ///
 /// This first verifies that there are no key mismatches between `defaultConfig` and
@@ -20,33 +74,53 @@ use std::path::Path;
 /// instead of having to use `lal build --force` when just using stashed components.
 /// This avoids problems with different environments going undetected.
 pub fn verify(component_dir: &Path, m: &Manifest, env: &str, simple: bool) -> LalResult<()> {
+    if let Some(e) = verify_report(component_dir, m, env, simple)?.first {
+        return Err(e);
+    }
+    info!("Dependencies fully verified");
+    Ok(())
+}
+
+/// Runs the checks of `verify`, collecting every failure into a report
+///
+/// Only a broken manifest or a missing dependency in `INPUT` stop it early,
+/// as the remaining checks need both. Errors reading `INPUT` are still returned.
+pub fn verify_report(component_dir: &Path, m: &Manifest, env: &str, simple: bool) -> LalResult<VerifyReport> {
+    let mut report = VerifyReport {
+        environment: env.into(),
+        simple,
+        findings: vec![],
+        first: None,
+    };
     // 1. Verify that the manifest is sane
-    m.verify()?;
+    if !report.check("manifest", m.verify()) {
+        return Ok(report);
+    }
 
     // 2. dependencies in `INPUT` match `manifest.json`.
-    if m.dependencies.is_empty() && !input::present(&component_dir) {
+    if m.dependencies.is_empty() && !input::present(component_dir) {
         // special case where lal fetch is not required and so INPUT may not exist
         // nothing needs to be verified in this case, so allow missing INPUT
-        return Ok(());
+        return Ok(report);
+    }
+    if !report.check("present", input::verify_dependencies_present(component_dir, m)) {
+        return Ok(report);
     }
-    input::verify_dependencies_present(&component_dir, m)?;
 
     // get data for big verify steps
-    let lf = Lockfile::default().populate_from_input(&component_dir)?;
+    let lf = Lockfile::default().populate_from_input(component_dir)?;
 
-    // 3. verify the root level dependencies match the manifest
     if !simple {
-        input::verify_global_versions(&lf, m)?;
-    }
-
-    // 4. the dependency tree is flat, and deps use only global deps
-    if !simple {
-        input::verify_consistent_dependency_versions(&lf, m)?;
+        // 3. verify the root level dependencies match the manifest
+        report.check("global-versions", input::verify_global_versions(&lf, m));
+        // 4. the dependency tree is flat, and deps use only global deps
+        report.check(
+            "consistent-versions",
+            input::verify_consistent_dependency_versions(&lf, m),
+        );
     }
 
     // 5. verify all components are built in the same environment
-    input::verify_environment_consistency(&lf, env)?;
-
-    info!("Dependencies fully verified");
-    Ok(())
+    report.check("environment", input::verify_environment_consistency(&lf, env));
+    Ok(report)
 }
@@ -1,16 +1,28 @@
 mod test_backend;
 mod test_build;
+mod test_chain;
 mod test_clean;
 mod test_envs;
 mod test_export;
 mod test_fetch;
+mod test_graph;
+mod test_hooks;
+mod test_http;
 mod test_init;
 mod test_list;
+mod test_oci;
+mod test_offline;
+mod test_output;
 mod test_propagate;
 mod test_publish;
 mod test_query;
+mod test_rdeps;
 mod test_remove;
+mod test_retry;
+mod test_rootfs;
+mod test_s3;
 mod test_shell;
+mod test_signing;
 mod test_stash;
 mod test_status;
 mod test_update;
@@ -170,3 +170,363 @@ fn test_build_with_release_create_tar_compatible_output(env_name: &str) {
         }
     });
 }
+
+#[test]
+fn test_build_cache() {
+    let state = setup();
+    let home = state.tempdir.path();
+    let env_name = "default";
+
+    let mut config = lal::Config::read(Some(home)).expect("read config");
+    config.build_cache = true;
+    config.write(false, Some(home)).expect("enabled build cache");
+
+    // a BUILD that counts its runs outside the component, and stamps its OUTPUT
+    let component_dir = clone_component_dir("heylib", &state);
+    let counter = home.join("builds.count");
+    std::fs::write(
+        component_dir.join("BUILD"),
+        format!(
+            "#!/bin/sh\necho run >> {}\ndate +%s%N > OUTPUT/stamp\ncp hey.h OUTPUT/\n",
+            counter.display()
+        ),
+    )
+    .unwrap();
+    let manifest = lal::Manifest::read(&component_dir).expect("read manifest");
+    let runs = || {
+        std::fs::read_to_string(&counter)
+            .unwrap_or_default()
+            .lines()
+            .count()
+    };
+    let build = |no_cache: bool, release: bool| {
+        let mut opts = build::options(Some(home), env_name, &manifest).expect("build options");
+        opts.no_cache = no_cache;
+        opts.release = release;
+        build::build_with_options(&component_dir, &manifest, env_name, home, &opts)
+    };
+    let read = |p: &str| std::fs::read(component_dir.join(p)).expect("read build result");
+
+    assert!(build(false, false).is_ok());
+    let stamp = read("OUTPUT/stamp");
+    assert!(build(false, false).is_ok());
+    assert_eq!(runs(), 1, "second build came from the cache");
+    assert_eq!(read("OUTPUT/stamp"), stamp, "OUTPUT restored");
+
+    assert!(build(true, false).is_ok());
+    assert_eq!(runs(), 2, "--no-cache bypasses the cache");
+
+    std::fs::write(component_dir.join("hey.h"), "// changed\n").unwrap();
+    assert!(build(false, false).is_ok());
+    assert_eq!(runs(), 3, "source changes invalidate the cache");
+
+    // release builds cache their ARTIFACT too
+    assert!(build(false, true).is_ok());
+    assert_eq!(runs(), 3, "OUTPUT reused for the release");
+    let tarball = read("ARTIFACT/heylib.tar.gz");
+    std::fs::remove_dir_all(component_dir.join("ARTIFACT")).unwrap();
+    assert!(build(false, true).is_ok());
+    assert_eq!(runs(), 3);
+    assert_eq!(read("ARTIFACT/heylib.tar.gz"), tarball, "ARTIFACT restored");
+}
+
+#[test]
+fn test_build_all() {
+    let state = setup();
+    let home = state.tempdir.path();
+    let env_name = "default";
+    let component_dir = clone_component_dir("heylib", &state);
+
+    // two components, one of them with a configuration that fails to build
+    let mut manifest = lal::Manifest::read(&component_dir).expect("read manifest");
+    let heylib = manifest.components["heylib"].clone();
+    manifest
+        .components
+        .insert("heylib".into(), lal::ComponentConfiguration {
+            defaultConfig: "release".into(),
+            configurations: vec!["broken".into(), "release".into()],
+        });
+    manifest.components.insert("heytests".into(), heylib);
+    manifest.write().expect("wrote manifest");
+    std::fs::write(
+        component_dir.join("BUILD"),
+        "#!/bin/sh\necho \"$1 $2\" > OUTPUT/built\ntest \"$2\" != broken\n",
+    )
+    .unwrap();
+
+    let opts = build::options(Some(home), env_name, &manifest).expect("build options");
+    let config = lal::Config::read(Some(home)).expect("read config");
+    let build_all = |all: lal::BuildAllOptions| {
+        lal::build_all(
+            &component_dir,
+            &config,
+            &manifest,
+            &lal::BuildOptions {
+                release: false,
+                ..opts.clone()
+            },
+            &all,
+            env_name.into(),
+            Default::default(),
+        )
+    };
+    let built = |c: &str| std::fs::read_to_string(component_dir.join("OUTPUT").join(c).join("built")).ok();
+
+    let r = build_all(Default::default());
+    assert!(r.is_ok(), "built default configurations: {:?}", r);
+    assert_eq!(built("heylib/release").as_deref(), Some("heylib release\n"));
+    assert_eq!(built("heytests/release").as_deref(), Some("heytests release\n"));
+
+    let r = build_all(lal::BuildAllOptions {
+        all_configurations: true,
+        keep_going: false,
+    });
+    match r {
+        Err(lal::CliError::BuildFailures(f)) => assert_eq!(f, vec!["heylib/broken".to_string()]),
+        x => panic!("heylib/broken should fail: {:?}", x),
+    }
+    assert!(built("heylib/broken").is_some(), "failed OUTPUT kept");
+    assert!(built("heylib/release").is_none(), "stopped after the failure");
+    assert!(built("heytests/release").is_none(), "stopped after the failure");
+
+    let r = build_all(lal::BuildAllOptions {
+        all_configurations: true,
+        keep_going: true,
+    });
+    assert!(r.is_err(), "failures reported with --keep-going");
+    assert!(built("heylib/release").is_some(), "kept going");
+    assert!(built("heytests/release").is_some(), "kept going");
+}
+
+#[test]
+fn test_build_matrix() {
+    use lal::Backend;
+
+    let state = setup();
+    let home = state.tempdir.path();
+    let component_dir = clone_component_dir("heylib", &state);
+
+    // two native environments, and one that is not defined anywhere
+    let mut manifest = lal::Manifest::read(&component_dir).expect("read manifest");
+    manifest
+        .environments
+        .insert("native".into(), lal::Environment::None);
+    manifest.supportedEnvironments = vec!["default".into(), "native".into()];
+    manifest.write().expect("wrote manifest");
+
+    let mut opts = build::options(Some(home), "default", &manifest).expect("build options");
+    opts.version = Some("3".into());
+    let config = lal::Config::read(Some(home)).expect("read config");
+    let build_matrix = |manifest: &lal::Manifest, keep_going: bool| {
+        state.rt.block_on(lal::build_matrix(
+            &component_dir,
+            &config,
+            manifest,
+            &opts,
+            keep_going,
+            &state.backend,
+            Default::default(),
+        ))
+    };
+
+    let r = build_matrix(&manifest, false);
+    assert!(r.is_ok(), "built in every environment: {:?}", r);
+    for env in &["default", "native"] {
+        let lock = lal::Lockfile::release_build(&component_dir.join("MATRIX").join(env)).expect("lockfile");
+        assert_eq!(&lock.envname, env, "envname of the {} build", env);
+    }
+    assert!(!component_dir.join("ARTIFACT").exists());
+
+    let r = state
+        .rt
+        .block_on(publish::publish_release(&component_dir, &state.backend, home));
+    assert!(r.is_ok(), "published the matrix: {:?}", r);
+    for env in &["default", "native"] {
+        let v = state.rt.block_on(state.backend.get_latest_version("heylib", env));
+        assert_eq!(v.ok(), Some(3), "heylib=3 published to {}", env);
+    }
+
+    let mut broken = manifest.clone();
+    broken.supportedEnvironments = vec!["missing".into(), "default".into()];
+    let r = build_matrix(&broken, false);
+    match r {
+        Err(lal::CliError::BuildFailures(f)) => assert_eq!(f, vec!["missing".to_string()]),
+        x => panic!("missing environment should fail: {:?}", x),
+    }
+    assert!(
+        !component_dir.join("MATRIX/default/ARTIFACT").exists(),
+        "stopped after the failure"
+    );
+
+    let r = build_matrix(&broken, true);
+    assert!(r.is_err(), "failures reported with --keep-going");
+    assert!(
+        component_dir.join("MATRIX/default/ARTIFACT").is_dir(),
+        "kept going"
+    );
+
+    // the native release is missing from the matrix
+    let r = state
+        .rt
+        .block_on(publish::publish_release(&component_dir, &state.backend, home));
+    assert!(
+        matches!(r, Err(lal::CliError::IncompleteMatrix(_))),
+        "refused to publish a partial matrix: {:?}",
+        r
+    );
+}
+
+#[test]
+fn test_build_log() {
+    let state = setup();
+    let home = state.tempdir.path();
+    let env_name = "default";
+    let component_dir = clone_component_dir("heylib", &state);
+
+    let r = build::build_for_release(&component_dir, env_name, home, "1");
+    assert!(r.is_ok(), "built heylib: {:?}", r);
+    let log = std::fs::read_to_string(component_dir.join("OUTPUT/.lal-build.log")).expect("build log");
+    let compile = log
+        .lines()
+        .find(|l| l.ends_with("cc -O -c hey.c"))
+        .expect("build output in the log");
+    assert!(
+        compile.contains(" [stdout] "),
+        "log marks the stream: {}",
+        compile
+    );
+    assert!(
+        compile.starts_with(char::is_numeric),
+        "log is timestamped: {}",
+        compile
+    );
+    assert!(component_dir.join("ARTIFACT/build.log").is_file(), "log released");
+    let tarball = std::fs::File::open(component_dir.join("ARTIFACT/heylib.tar.gz")).unwrap();
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball));
+    let paths = archive
+        .entries()
+        .unwrap()
+        .map(|e| e.unwrap().path().unwrap().display().to_string())
+        .collect::<Vec<_>>();
+    assert!(
+        paths.iter().any(|p| p.ends_with("lockfile.json")),
+        "tarred OUTPUT: {:?}",
+        paths
+    );
+    assert!(
+        !paths.iter().any(|p| p.ends_with(".lal-build.log")),
+        "log not in the tarball: {:?}",
+        paths
+    );
+
+    let lock = lal::Lockfile::release_build(&component_dir).expect("release lockfile");
+    let info = lock.build.expect("build section in the lockfile");
+    assert_eq!(info.exitCode, Some(0));
+    assert_eq!(info.host.os, std::env::consts::OS);
+    assert!(info.image.is_none(), "nothing to inspect natively");
+
+    // failed builds are recorded in OUTPUT
+    std::fs::write(
+        component_dir.join("BUILD"),
+        "#!/bin/sh\necho going wrong >&2\nexit 3\n",
+    )
+    .unwrap();
+    let r = build::build_for_release(&component_dir, env_name, home, "2");
+    assert!(
+        matches!(r, Err(lal::CliError::SubprocessFailure(3))),
+        "build failed: {:?}",
+        r
+    );
+    let log = std::fs::read_to_string(component_dir.join("OUTPUT/.lal-build.log")).expect("build log");
+    assert!(
+        log.trim_end().ends_with("[stderr] going wrong"),
+        "stderr logged: {}",
+        log
+    );
+    let lock =
+        lal::Lockfile::from_path(&component_dir.join("OUTPUT/lockfile.json"), "heylib").expect("lockfile");
+    assert_eq!(lock.build.and_then(|b| b.exitCode), Some(3));
+
+    // dependencies keep their build section to themselves
+    state.rt.block_on(async {
+        publish_component(&state, env_name, "heylib", "1")
+            .await
+            .expect("published heylib=1");
+        let helloworld = clone_component_dir("helloworld", &state);
+        let r = fetch::fetch_input(&helloworld, env_name, &state.backend).await;
+        assert!(r.is_ok(), "fetched heylib: {:?}", r);
+        let heylib = lal::Lockfile::from_path(&helloworld.join("INPUT/heylib/lockfile.json"), "heylib")
+            .expect("heylib lockfile");
+        assert!(heylib.build.is_some(), "heylib records its build");
+        let lock = lal::Lockfile::default()
+            .populate_from_input(&helloworld)
+            .expect("lockfile from INPUT");
+        assert!(
+            lock.dependencies["heylib"].build.is_none(),
+            "build not copied downstream"
+        );
+    });
+}
+
+#[test]
+fn test_build_limits() {
+    let state = setup();
+    let home = state.tempdir.path();
+    let env_name = "default";
+    let component_dir = clone_component_dir("heylib", &state);
+    std::fs::write(component_dir.join("BUILD"), "#!/bin/sh\nsleep 30\n").unwrap();
+
+    let limits = |timeout: u64, memory: &str| lal::Limits {
+        timeout: Some(timeout),
+        memory: Some(memory.into()),
+        ..Default::default()
+    };
+    let mut manifest = lal::Manifest::read(&component_dir).expect("read manifest");
+    manifest.limits.insert(env_name.into(), limits(600, "2g"));
+    manifest.write().expect("wrote manifest");
+    let mut config = lal::Config::read(Some(home)).expect("read config");
+    config.limits.insert(env_name.into(), lal::Limits {
+        timeout: Some(1),
+        ..Default::default()
+    });
+    config.write(true, Some(home)).expect("wrote config");
+
+    // the machine wins over the component, which fills in the rest
+    let resolved = config.get_limits(env_name, &manifest);
+    assert_eq!(resolved, limits(1, "2g"));
+    assert_eq!(
+        limits(5, "1g").or(Some(&resolved)),
+        limits(5, "1g"),
+        "invocation wins"
+    );
+
+    let r = build::build_for_release(&component_dir, env_name, home, "1");
+    assert!(
+        matches!(r, Err(lal::CliError::Timeout(1))),
+        "build timed out: {:?}",
+        r
+    );
+    let lock =
+        lal::Lockfile::from_path(&component_dir.join("OUTPUT/lockfile.json"), "heylib").expect("lockfile");
+    let info = lock.build.expect("build section");
+    assert_eq!(info.exitCode, None, "no exit code after a timeout");
+    assert!(info.wallTime < 10.0);
+
+    // what escapes the timeout cannot keep lal waiting on its output
+    std::fs::write(
+        component_dir.join("BUILD"),
+        "#!/bin/sh
+setsid sleep 30 &
+sleep 30
+",
+    )
+    .unwrap();
+    let start = std::time::Instant::now();
+    let r = build::build_for_release(&component_dir, env_name, home, "1");
+    assert!(
+        matches!(r, Err(lal::CliError::Timeout(1))),
+        "build timed out: {:?}",
+        r
+    );
+    assert!(start.elapsed().as_secs() < 10, "returned despite the survivor");
+}
@@ -0,0 +1,80 @@
+use crate::common::*;
+
+use lal::{Backend, BackendConfiguration, ChainBackend, ChainConfig, HttpConfig};
+
+#[test]
+fn test_chain_reads_through_mirror() {
+    let state = setup();
+    let env_name = "default";
+
+    state.rt.block_on(async {
+        let (mirror_addr, mirror_files) = webdav::mock_server();
+        let (central_addr, _) = webdav::mock_server();
+        let mirror = webdav::backend(mirror_addr, &state.tempdir.path().join("mirrorcache"));
+        let central = webdav::backend(central_addr, &state.tempdir.path().join("centralcache"));
+
+        let offline = HttpConfig {
+            url: "http://127.0.0.1:1/lal".into(),
+            ..Default::default()
+        };
+        let cfg = ChainConfig {
+            backends: vec![
+                BackendConfiguration::Http(offline),
+                BackendConfiguration::Http(mirror.config.clone()),
+                BackendConfiguration::Http(central.config.clone()),
+            ],
+            primary: 2,
+        };
+        let chain = ChainBackend::new(&cfg, &state.tempdir.path().join("chaincache")).expect("chain backend");
+
+        // heylib=1 is published through the chain, heylib=2 only exists on the mirror
+        let component_dir = clone_component_dir("heylib", &state);
+        let r = build::build_for_release(&component_dir, env_name, state.tempdir.path(), "1");
+        assert!(r.is_ok(), "built heylib=1: {:?}", r);
+        let r = publish::publish_release(&component_dir, &chain, state.tempdir.path()).await;
+        assert!(r.is_ok(), "published heylib=1 through the chain: {:?}", r);
+        assert!(
+            mirror_files.lock().unwrap().is_empty(),
+            "published to primary only"
+        );
+
+        let r = build::build_for_release(&component_dir, env_name, state.tempdir.path(), "2");
+        assert!(r.is_ok(), "built heylib=2: {:?}", r);
+        let r = publish::publish_release(&component_dir, &mirror, state.tempdir.path()).await;
+        assert!(r.is_ok(), "published heylib=2 to the mirror: {:?}", r);
+
+        let versions = chain.get_versions("heylib", env_name).await;
+        assert_eq!(
+            versions.ok(),
+            Some(vec![2, 1]),
+            "merged versions across the chain"
+        );
+        let latest = chain.get_component_info("heylib", None, env_name).await;
+        assert!(
+            latest
+                .map(|c| c.location.starts_with(&mirror.config.url))
+                .unwrap_or(false),
+            "latest served by the mirror"
+        );
+
+        // helloworld depends on heylib=1 which only the primary has
+        let component_dir = clone_component_dir("helloworld", &state);
+        let r = fetch::fetch_input(&component_dir, env_name, &chain).await;
+        assert!(
+            r.is_ok(),
+            "installed helloworld dependencies through the chain: {:?}",
+            r
+        );
+    });
+}
+
+#[test]
+fn test_chain_without_backends() {
+    let state = setup();
+    let cfg = ChainConfig {
+        backends: vec![],
+        primary: 0,
+    };
+    let r = ChainBackend::new(&cfg, state.tempdir.path());
+    assert!(r.is_err(), "empty chain refused");
+}
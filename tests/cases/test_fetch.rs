@@ -1,5 +1,8 @@
 use crate::common::*;
 use parameterized_macro::parameterized;
+use std::fs;
+
+use lal::Backend;
 
 #[parameterized(env_name = {"default", "alpine"})]
 fn test_fetch_no_deps(env_name: &str) {
@@ -72,3 +75,185 @@ fn test_fetch_with_dev_deps(env_name: &str) {
         assert!(r.is_ok(), "installed helloworld dev dependencies");
     });
 }
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_fetch_concurrently(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        // prop-base depends on both mid points, which both depend on prop-leaf
+        publish_components(
+            &state,
+            env_name,
+            vec!["prop-leaf", "prop-mid-1", "prop-mid-2"],
+            "1",
+        )
+        .await
+        .expect("publish prop-base dependencies");
+
+        let component_dir = clone_component_dir("prop-base", &state);
+        let r = fetch::fetch_input_with_jobs(&component_dir, env_name, &state.backend, 2).await;
+        assert!(r.is_ok(), "installed prop-base dependencies concurrently");
+
+        assert!(component_dir.join("INPUT/prop-mid-1/lockfile.json").is_file());
+        assert!(component_dir.join("INPUT/prop-mid-2/lockfile.json").is_file());
+    });
+}
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_fetch_failure_cleans_input(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        // only one of the two dependencies of prop-base is available
+        publish_components(&state, env_name, vec!["prop-leaf", "prop-mid-1"], "1")
+            .await
+            .expect("publish prop-mid-1");
+
+        let component_dir = clone_component_dir("prop-base", &state);
+        let r = fetch::fetch_input_with_jobs(&component_dir, env_name, &state.backend, 2).await;
+        match r {
+            Err(lal::CliError::InstallFailure) => {}
+            _ => panic!("expected an install failure, got {:?}", r),
+        }
+        assert!(!component_dir.join("INPUT").exists(), "INPUT was cleaned");
+    });
+}
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_fetch_corrupted_cache(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        publish_component(&state, env_name, "heylib", "1")
+            .await
+            .expect("publish heylib=1");
+
+        // corrupt the cached tarball behind lal's back
+        let tarball = state
+            .backend
+            .get_cache_dir()
+            .join("environments")
+            .join(env_name)
+            .join("heylib/1/heylib.tar.gz");
+        fs::write(&tarball, "not a tarball").expect("overwrite cached tarball");
+
+        let component_dir = clone_component_dir("helloworld", &state);
+        let r = fetch::fetch_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_err(), "refused to install corrupted heylib: {:?}", r);
+        assert!(
+            !component_dir.join("INPUT/heylib").exists(),
+            "corrupted heylib not left in INPUT"
+        );
+    });
+}
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_fetch_range_is_pinned(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        publish_component_versions(&state, env_name, "heylib", vec!["1", "2"])
+            .await
+            .expect("publish heylib=1 and heylib=2");
+
+        // helloworld depends on any heylib from 1 upwards
+        let component_dir = clone_component_dir("helloworld", &state);
+        let mut manifest = lal::Manifest::read(&component_dir).expect("read manifest");
+        manifest
+            .dependencies
+            .insert("heylib".into(), lal::VersionRequirement::Range(">=1".into()));
+        manifest.write().expect("write manifest");
+
+        let r = fetch::fetch_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_ok(), "installed helloworld dependencies: {:?}", r);
+
+        let lock = lal::ResolvedLock::read(&component_dir).expect("read resolved lock");
+        assert_eq!(lock.dependencies["heylib"].version, 2, "pinned highest match");
+
+        // a newer release does not move the pin
+        publish_component(&state, env_name, "heylib", "3")
+            .await
+            .expect("publish heylib=3");
+        fs::remove_dir_all(component_dir.join("INPUT")).expect("clean INPUT");
+
+        let r = fetch::fetch_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_ok(), "installed helloworld dependencies again: {:?}", r);
+
+        let lf = lal::Lockfile::from_path(&component_dir.join("INPUT/heylib/lockfile.json"), "heylib")
+            .expect("read heylib lockfile");
+        assert_eq!(lf.version, "2", "fetched pinned version");
+    });
+}
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_fetch_locked(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        publish_component(&state, env_name, "heylib", "1")
+            .await
+            .expect("publish heylib=1");
+
+        let component_dir = clone_component_dir("helloworld", &state);
+
+        // nothing resolved yet
+        let r = fetch::fetch_locked_input(&component_dir, env_name, &state.backend).await;
+        assert!(
+            matches!(r, Err(lal::CliError::UnlockedDependency(_))),
+            "refused to resolve when locked: {:?}",
+            r
+        );
+
+        let r = fetch::fetch_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_ok(), "installed helloworld dependencies: {:?}", r);
+
+        // the lock records what was fetched in this environment
+        let mut lock = lal::ResolvedLock::read(&component_dir).expect("read resolved lock");
+        let artifact = lock
+            .artifact("heylib", env_name)
+            .expect("heylib artifact")
+            .clone();
+        let tarball = state
+            .backend
+            .get_cache_dir()
+            .join("environments")
+            .join(env_name)
+            .join("heylib/1/heylib.tar.gz");
+        assert_eq!(artifact.location, tarball.display().to_string());
+        assert_eq!(artifact.sha256, lal::checksum::sha256_file(&tarball).ok());
+
+        fs::remove_dir_all(component_dir.join("INPUT")).expect("clean INPUT");
+        let r = fetch::fetch_locked_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_ok(), "installed locked dependencies: {:?}", r);
+
+        // a digest that does not match what the backend serves is refused
+        lock.dependencies.get_mut("heylib").unwrap().environments.insert(
+            env_name.into(),
+            lal::ResolvedArtifact {
+                sha256: Some("0".repeat(64)),
+                ..artifact
+            },
+        );
+        lock.write(&component_dir).expect("write resolved lock");
+        // even when the pinned version is already in INPUT
+        assert!(component_dir.join("INPUT/heylib").is_dir(), "heylib in INPUT");
+        let r = fetch::fetch_locked_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_err(), "refused mismatching digest: {:?}", r);
+    });
+}
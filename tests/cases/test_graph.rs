@@ -0,0 +1,82 @@
+use crate::common::*;
+
+use lal::{DependencyGraph, GraphFormat, VersionRequirement};
+
+#[test]
+fn test_graph() {
+    let state = setup();
+    let env_name = "default";
+    let home = state.tempdir.path();
+
+    state.rt.block_on(async {
+        // prop-mid-2 is built against a newer prop-leaf than prop-mid-1
+        let r = publish_component_versions(&state, env_name, "prop-leaf", vec!["1", "2"]).await;
+        assert!(r.is_ok(), "published prop-leaf: {:?}", r);
+        let r = publish_component(&state, env_name, "prop-mid-1", "1").await;
+        assert!(r.is_ok(), "published prop-mid-1: {:?}", r);
+        let mid2 = clone_component_dir("prop-mid-2", &state);
+        let mut manifest = lal::Manifest::read(&mid2).unwrap();
+        manifest
+            .dependencies
+            .insert("prop-leaf".into(), VersionRequirement::Exact(2));
+        manifest.write().unwrap();
+        fetch::fetch_input(&mid2, env_name, &state.backend).await.unwrap();
+        build::build_for_release(&mid2, env_name, home, "1").unwrap();
+        publish::publish_release(&mid2, &state.backend, home)
+            .await
+            .unwrap();
+
+        let component_dir = clone_component_dir("prop-base", &state);
+        let r = fetch::fetch_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_ok(), "installed prop-base dependencies: {:?}", r);
+
+        let lf = lal::Lockfile::default()
+            .set_name("prop-base")
+            .populate_from_input(&component_dir)
+            .unwrap();
+        let graph = DependencyGraph::new(&lf);
+        let nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|n| (n.name.as_str(), n.conflict))
+            .collect();
+        assert_eq!(nodes, vec![
+            ("prop-base", false),
+            ("prop-leaf", true),
+            ("prop-mid-1", false),
+            ("prop-mid-2", false),
+        ]);
+        assert_eq!(graph.nodes[1].versions.len(), 2, "both prop-leaf versions");
+        assert!(graph.nodes[2].environments.contains(env_name));
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.version.as_str(), e.conflict))
+            .collect();
+        assert_eq!(edges, vec![
+            ("prop-base", "prop-mid-1", "1", false),
+            ("prop-base", "prop-mid-2", "1", false),
+            ("prop-mid-1", "prop-leaf", "1", true),
+            ("prop-mid-2", "prop-leaf", "2", true),
+        ]);
+
+        let dot = graph.render(GraphFormat::Dot).unwrap();
+        assert!(dot.starts_with("digraph \"prop-base\" {"));
+        assert!(dot.contains("\"prop-mid-2\" -> \"prop-leaf\" [label=\"2\", color=red, fontcolor=red];"));
+        let mermaid = graph.render(GraphFormat::Mermaid).unwrap();
+        assert!(
+            mermaid.contains("n3 -->|2| n1"),
+            "numbered mermaid edges: {}",
+            mermaid
+        );
+        assert!(mermaid.contains("class n1 conflict"));
+        assert!(mermaid.contains("linkStyle 3 stroke:#d00"));
+        let json: serde_json::Value =
+            serde_json::from_str(&graph.render(GraphFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["edges"][3]["conflict"], true);
+
+        let manifest = lal::Manifest::read(&component_dir).unwrap();
+        let r = lal::graph(&component_dir, &manifest, GraphFormat::Mermaid);
+        assert!(r.is_ok(), "printed graph: {:?}", r);
+    });
+}
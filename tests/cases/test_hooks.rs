@@ -0,0 +1,76 @@
+use crate::common::*;
+
+use lal::CliError;
+use std::fs;
+
+#[test]
+fn test_build_and_publish_hooks() {
+    let state = setup();
+    let home = state.tempdir.path();
+    let env_name = "default";
+    let component_dir = clone_component_dir("heylib", &state);
+    let scripts = component_dir.join(".lal/scripts");
+    let script = |name: &str, body: &str| {
+        fs::write(scripts.join(name), format!("main() {{\n  {}\n}}\n", body)).unwrap()
+    };
+    script(
+        "generate",
+        "echo \"$LAL_COMPONENT $LAL_CONFIG $BUILD_VERSION\" > generated",
+    );
+    script(
+        "sign",
+        "test -f ARTIFACT/heylib.tar.gz && echo signed > ARTIFACT/heylib.sig",
+    );
+    script("announce", "echo \"$BUILD_VERSION\" > announced");
+
+    let mut manifest = lal::Manifest::read(&component_dir).expect("read manifest");
+    manifest.hooks = lal::Hooks {
+        preBuild: Some("generate".into()),
+        postBuild: Some("sign".into()),
+        prePublish: Some("announce".into()),
+        postFetch: None,
+    };
+    manifest.write().expect("wrote manifest");
+
+    let r = build::build_for_release(&component_dir, env_name, home, "7");
+    assert!(r.is_ok(), "built with hooks: {:?}", r);
+    assert_eq!(
+        fs::read_to_string(component_dir.join("generated"))
+            .ok()
+            .as_deref(),
+        Some("heylib release 7\n"),
+        "preBuild ran with the build variables"
+    );
+    assert!(
+        component_dir.join("ARTIFACT/heylib.sig").is_file(),
+        "postBuild ran after ARTIFACT"
+    );
+
+    let r = state
+        .rt
+        .block_on(publish::publish_release(&component_dir, &state.backend, home));
+    assert!(r.is_ok(), "published with hooks: {:?}", r);
+    assert_eq!(
+        fs::read_to_string(component_dir.join("announced"))
+            .ok()
+            .as_deref(),
+        Some("7\n"),
+        "prePublish ran"
+    );
+
+    // a failing hook fails the command
+    script("sign", "exit 4");
+    let r = build::build_for_release(&component_dir, env_name, home, "8");
+    match r {
+        Err(CliError::HookFailure(hook, _)) => assert_eq!(hook, "postBuild"),
+        x => panic!("postBuild should fail the build: {:?}", x),
+    }
+    manifest.hooks.preBuild = Some("missing".into());
+    manifest.write().expect("wrote manifest");
+    let r = build::build_for_release(&component_dir, env_name, home, "8");
+    assert!(
+        matches!(r, Err(CliError::HookFailure(ref h, _)) if h == "preBuild"),
+        "missing preBuild script: {:?}",
+        r
+    );
+}
@@ -0,0 +1,161 @@
+use crate::common::*;
+
+use lal::{Backend, CachedBackend};
+
+#[test]
+fn test_http_publish_and_fetch() {
+    let state = setup();
+    let env_name = "default";
+
+    state.rt.block_on(async {
+        let (addr, files) = webdav::mock_server();
+        let backend = webdav::backend(addr, &state.tempdir.path().join("httpcache"));
+
+        // publish two releases of heylib to the server
+        let component_dir = clone_component_dir("heylib", &state);
+        for version in &["1", "2"] {
+            let r = build::build_for_release(&component_dir, env_name, state.tempdir.path(), version);
+            assert!(r.is_ok(), "built heylib={}: {:?}", version, r);
+            let r = publish::publish_release(&component_dir, &backend, state.tempdir.path()).await;
+            assert!(r.is_ok(), "published heylib={} over http: {:?}", version, r);
+        }
+
+        // the index was moved into place, leaving no temporary files behind
+        let index_path = "/lal/env/default/heylib/index.json";
+        let index = files.lock().unwrap()[index_path].clone();
+        let index: serde_json::Value = serde_json::from_slice(&index).expect("parse index");
+        assert_eq!(index["versions"], serde_json::json!([2, 1]));
+        assert_eq!(
+            files
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|k| k.contains("index.json"))
+                .count(),
+            1
+        );
+
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(versions.ok(), Some(vec![2, 1]), "listed versions from index");
+
+        // helloworld depends on heylib=1
+        let component_dir = clone_component_dir("helloworld", &state);
+        let r = fetch::fetch_input(&component_dir, env_name, &backend).await;
+        assert!(r.is_ok(), "installed helloworld dependencies over http: {:?}", r);
+
+        // a lockfile that cannot be fetched does not skip the checksum of heylib=2
+        let lockfile_path = "/lal/env/default/heylib/2/lockfile.json";
+        let status_path = format!("{}#status", lockfile_path);
+        files.lock().unwrap().insert(status_path.clone(), b"403".to_vec());
+        let r = backend
+            .retrieve_published_component("heylib", Some(2), env_name)
+            .await;
+        assert!(
+            r.is_err(),
+            "refused heylib=2 without its lockfile: {:?}",
+            r.map(|r| r.0)
+        );
+
+        // but artifacts published before lockfiles were are still trusted
+        files.lock().unwrap().remove(&status_path);
+        files.lock().unwrap().remove(lockfile_path);
+        let r = backend
+            .retrieve_published_component("heylib", Some(2), env_name)
+            .await;
+        assert!(
+            r.is_ok(),
+            "fetched heylib=2 without a lockfile: {:?}",
+            r.map(|r| r.0)
+        );
+
+        // servers without an index fall back to the directory listing
+        files.lock().unwrap().remove(index_path);
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(versions.ok(), Some(vec![2, 1]), "listed versions from autoindex");
+        let names = backend.list_components(env_name).await;
+        assert_eq!(names.ok(), Some(vec!["heylib".to_string()]), "listed components");
+
+        // the index is only seeded from a listing that could be read
+        let component_dir = clone_component_dir("heylib", &state);
+        let r = build::build_for_release(&component_dir, env_name, state.tempdir.path(), "3");
+        assert!(r.is_ok(), "built heylib=3: {:?}", r);
+        let listing_status = "/lal/env/default/heylib/#status".to_string();
+        files
+            .lock()
+            .unwrap()
+            .insert(listing_status.clone(), b"500".to_vec());
+        let r = publish::publish_release(&component_dir, &backend, state.tempdir.path()).await;
+        assert!(r.is_err(), "publish fails without a listing: {:?}", r);
+        assert!(
+            !files.lock().unwrap().contains_key(index_path),
+            "no partial index"
+        );
+        assert!(
+            !files
+                .lock()
+                .unwrap()
+                .contains_key("/lal/env/default/heylib/3/heylib.tar.gz"),
+            "nothing uploaded before the index could be read"
+        );
+
+        files.lock().unwrap().remove(&listing_status);
+        let r = publish::publish_release(&component_dir, &backend, state.tempdir.path()).await;
+        assert!(r.is_ok(), "published heylib=3 over http: {:?}", r);
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(
+            versions.ok(),
+            Some(vec![3, 2, 1]),
+            "kept older versions in the index"
+        );
+
+        // concurrent publishes do not drop each others versions
+        let (r4, r5) = tokio::join!(
+            backend.publish_artifact(None, &component_dir, "heylib", 4, env_name),
+            backend.publish_artifact(None, &component_dir, "heylib", 5, env_name)
+        );
+        assert!(
+            r4.is_ok() && r5.is_ok(),
+            "published concurrently: {:?} {:?}",
+            r4,
+            r5
+        );
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(versions.ok(), Some(vec![5, 4, 3, 2, 1]), "kept both versions");
+
+        // a server without autoindex refuses the listing of a new component
+        let (addr, files) = webdav::mock_server();
+        let backend = webdav::backend(addr, &state.tempdir.path().join("httpcache2"));
+        files
+            .lock()
+            .unwrap()
+            .insert("/lal/env/default/heylib/#status".into(), b"403".to_vec());
+        let r = backend
+            .publish_artifact(None, &component_dir, "heylib", 3, env_name)
+            .await;
+        assert!(r.is_ok(), "published the first heylib without a listing: {:?}", r);
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(versions.ok(), Some(vec![3]), "seeded an empty index");
+
+        // and one without MOVE cannot update the index safely
+        files
+            .lock()
+            .unwrap()
+            .insert("MOVE#status".into(), b"405".to_vec());
+        let r = backend
+            .publish_artifact(None, &component_dir, "heylib", 4, env_name)
+            .await;
+        assert!(r.is_err(), "publish fails without MOVE: {:?}", r);
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(versions.ok(), Some(vec![3]), "left the index alone");
+        assert_eq!(
+            files
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|k| k.contains("index.json"))
+                .count(),
+            1,
+            "removed the temporary index"
+        );
+    });
+}
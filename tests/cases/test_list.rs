@@ -13,7 +13,7 @@ fn test_list_environments(env_name: &str) {
         assert!(r.is_ok(), "published heylib=1 helloworld=1");
 
         // TODO: Assert output
-        let r = list::list_environments(&state.tempdir.path());
+        let r = list::list_environments(state.tempdir.path(), false);
         assert!(r.is_ok(), "list environments");
     });
 }
@@ -32,7 +32,7 @@ fn test_list_core_dependencies(env_name: &str) {
 
         // TODO: Assert output
         let core = true;
-        let r = list::list_dependencies(&component_dir, core);
+        let r = list::list_dependencies(&component_dir, core, false);
         assert!(r.is_ok(), "list core dependencies");
     });
 }
@@ -51,7 +51,7 @@ fn test_list_all_dependencies(env_name: &str) {
 
         // TODO: Assert output
         let core = false;
-        let r = list::list_dependencies(&component_dir, core);
+        let r = list::list_dependencies(&component_dir, core, false);
         assert!(r.is_ok(), "list all dependencies");
     });
 }
@@ -68,7 +68,7 @@ fn test_list_configurations(env_name: &str) {
             .await
             .expect("published heylib=1 helloworld=1");
 
-        let r = list::list_configurations(&component_dir);
+        let r = list::list_configurations(&component_dir, false);
         assert!(r.is_ok(), "list configurations");
     });
 }
@@ -85,7 +85,7 @@ fn test_list_buildables(env_name: &str) {
             .await
             .expect("published heylib=1 helloworld=1");
 
-        let r = list::list_buildables(&component_dir);
+        let r = list::list_buildables(&component_dir, false);
         assert!(r.is_ok(), "list buildables");
     });
 }
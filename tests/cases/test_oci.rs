@@ -0,0 +1,59 @@
+use crate::common::*;
+
+use lal::Backend;
+
+#[test]
+fn test_oci_publish_and_fetch() {
+    let state = setup();
+    let env_name = "default";
+
+    state.rt.block_on(async {
+        let (addr, registry) = oci::mock_server();
+        let backend = oci::backend(addr, &state.tempdir.path().join("ocicache"));
+
+        // publish enough releases of heylib for the tags list to be paginated
+        let component_dir = clone_component_dir("heylib", &state);
+        for version in &["1", "2", "3"] {
+            let r = build::build_for_release(&component_dir, env_name, state.tempdir.path(), version);
+            assert!(r.is_ok(), "built heylib={}: {:?}", version, r);
+            let r = publish::publish_release(&component_dir, &backend, state.tempdir.path()).await;
+            assert!(r.is_ok(), "published heylib={} to registry: {:?}", version, r);
+        }
+
+        // tagged as <env>-<version> with the tarball and lockfile as layers
+        let manifest = registry.lock().unwrap().manifests["lal/heylib:default-1"].clone();
+        let manifest: serde_json::Value = serde_json::from_slice(&manifest).expect("parse manifest");
+        let titles: Vec<_> = manifest["layers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| {
+                l["annotations"]["org.opencontainers.image.title"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(titles, vec!["heylib.tar.gz", "lockfile.json"]);
+
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(versions.ok(), Some(vec![3, 2, 1]), "listed versions from tags");
+        let versions = backend.get_versions("heylib", "alpine").await;
+        assert_eq!(versions.ok(), Some(vec![]), "no versions in other environments");
+        let names = backend.list_components(env_name).await;
+        assert_eq!(
+            names.ok(),
+            Some(vec!["heylib".to_string()]),
+            "listed components from catalog"
+        );
+
+        // helloworld depends on heylib=1
+        let component_dir = clone_component_dir("helloworld", &state);
+        let r = fetch::fetch_input(&component_dir, env_name, &backend).await;
+        assert!(
+            r.is_ok(),
+            "installed helloworld dependencies from registry: {:?}",
+            r
+        );
+    });
+}
@@ -0,0 +1,56 @@
+use crate::common::*;
+
+use lal::{Backend, CliError, OfflineBackend};
+
+#[test]
+fn test_offline_uses_cache() {
+    let state = setup();
+    let env_name = "default";
+
+    state.rt.block_on(async {
+        let (addr, _) = webdav::mock_server();
+        let cache = state.tempdir.path().join("httpcache");
+        let backend = webdav::backend(addr, &cache);
+
+        let heylib_dir = clone_component_dir("heylib", &state);
+        for version in &["1", "2"] {
+            let r = build::build_for_release(&heylib_dir, env_name, state.tempdir.path(), version);
+            assert!(r.is_ok(), "built heylib={}: {:?}", version, r);
+            let r = publish::publish_release(&heylib_dir, &backend, state.tempdir.path()).await;
+            assert!(r.is_ok(), "published heylib={}: {:?}", version, r);
+        }
+
+        // only heylib=1 ends up in the cache
+        let component_dir = clone_component_dir("helloworld", &state);
+        let r = fetch::fetch_input(&component_dir, env_name, &backend).await;
+        assert!(r.is_ok(), "installed helloworld dependencies: {:?}", r);
+
+        let offline = OfflineBackend::new(&cache);
+        let versions = offline.get_versions("heylib", env_name).await;
+        assert_eq!(versions.ok(), Some(vec![1]), "only cached versions are listed");
+
+        let r = fetch::fetch_input(&component_dir, env_name, &offline).await;
+        assert!(r.is_ok(), "installed helloworld dependencies offline: {:?}", r);
+
+        let export_dir = state.tempdir.path().join("export");
+        std::fs::create_dir(&export_dir).unwrap();
+        let r = lal::export(&offline, "heylib", &export_dir, Some(env_name)).await;
+        assert!(r.is_ok(), "exported latest cached heylib: {:?}", r);
+
+        let r = lal::export(&offline, "heylib=2", &export_dir, Some(env_name)).await;
+        match r {
+            Err(CliError::NotCached(c, env)) => {
+                assert_eq!(c, "heylib=2");
+                assert_eq!(env, env_name);
+            }
+            x => panic!("uncached heylib=2 should not export offline: {:?}", x),
+        }
+
+        let r = publish::publish_release(&heylib_dir, &offline, state.tempdir.path()).await;
+        assert!(
+            matches!(r, Err(CliError::Offline(_))),
+            "cannot publish offline: {:?}",
+            r
+        );
+    });
+}
@@ -0,0 +1,95 @@
+use crate::common::*;
+use parameterized_macro::parameterized;
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_json_output(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        let component_dir = publish_components(&state, env_name, vec!["heylib", "helloworld"], "1")
+            .await
+            .expect("published heylib=1 helloworld=1");
+
+        let r = status::json_status(&component_dir, true);
+        assert!(r.is_ok(), "printed full status as json: {:?}", r);
+
+        let r = list::list_environments(state.tempdir.path(), true);
+        assert!(r.is_ok(), "listed environments as json");
+        let r = list::list_dependencies(&component_dir, false, true);
+        assert!(r.is_ok(), "listed dependencies as json");
+        let r = list::list_configurations(&component_dir, true);
+        assert!(r.is_ok(), "listed configurations as json");
+        let r = list::list_buildables(&component_dir, true);
+        assert!(r.is_ok(), "listed buildables as json");
+
+        let r = lal::query(&state.backend, Some(env_name), "hello", false, true).await;
+        assert!(r.is_ok(), "queried hello as json");
+    });
+}
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_json_status_errors(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        publish_component(&state, env_name, "heylib", "1")
+            .await
+            .expect("published heylib=1");
+
+        let component_dir = clone_component_dir("helloworld", &state);
+        std::fs::create_dir_all(component_dir.join("INPUT")).expect("created empty INPUT");
+
+        // still fails on missing dependencies, with a category and exit code
+        match status::json_status(&component_dir, false) {
+            Err(e @ lal::CliError::MissingDependencies) => {
+                assert_eq!(e.category(), "verify");
+                assert_eq!(e.exit_code(), 4);
+            }
+            r => panic!("status did not fail on missing heylib: {:?}", r),
+        }
+    });
+}
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_verify_report(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        publish_component(&state, env_name, "heylib", "1")
+            .await
+            .expect("published heylib=1");
+
+        let component_dir = clone_component_dir("helloworld", &state);
+
+        // missing INPUT stops the report early
+        let report = verify::verify_report(&component_dir, env_name, false).expect("verify report");
+        assert!(!report.ok(), "verify reports missing INPUT");
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].check, "present");
+        assert_eq!(report.findings[0].category, "verify");
+        assert_eq!(report.exit_code(), 4);
+
+        let r = fetch::fetch_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_ok(), "installed core dependencies");
+
+        let report = verify::verify_report(&component_dir, env_name, false).expect("verify report");
+        assert!(report.ok(), "verified INPUT consistency: {:?}", report);
+        assert_eq!(report.exit_code(), 0);
+
+        // the environment check is reported against the given environment
+        let report = verify::verify_report(&component_dir, "xenial", true).expect("verify report");
+        assert_eq!(report.environment, "xenial");
+        assert!(report.simple);
+        let checks = report.findings.iter().map(|f| f.check).collect::<Vec<_>>();
+        assert_eq!(checks, vec!["environment"]);
+    });
+}
@@ -1,4 +1,5 @@
 use crate::common::*;
+use lal::Backend;
 use parameterized_macro::parameterized;
 use std::path::PathBuf;
 
@@ -91,3 +92,106 @@ fn test_propagate_print_json(env_name: &str) {
         assert!(r.is_ok(), "pretty printed propagation tree");
     });
 }
+
+#[test]
+fn test_propagate_execute() {
+    let state = setup();
+    let env_name = "default";
+    let home = state.tempdir.path();
+
+    state.rt.block_on(async {
+        let component_dir = publish_components(&state, env_name)
+            .await
+            .expect("published components");
+        let r = publish_component(&state, env_name, "prop-leaf", "2").await;
+        assert!(r.is_ok(), "published prop-leaf=2: {:?}", r);
+
+        // releases record the revision of git checkouts
+        let mid = home.join("prop-mid-1");
+        let git = |args: &[&str]| {
+            let out = std::process::Command::new("git")
+                .args(["-c", "user.name=lal", "-c", "user.email=lal@localhost"])
+                .args(args)
+                .current_dir(&mid)
+                .output()
+                .expect("ran git");
+            assert!(out.status.success(), "git {:?} failed", args);
+            String::from_utf8(out.stdout).unwrap().trim().to_string()
+        };
+        git(&["init", "-q"]);
+        git(&["add", "manifest.json"]);
+        git(&["commit", "-qm", "init"]);
+        let head = git(&["rev-parse", "HEAD"]);
+
+        // a broken mid point stops the propagation after its stage
+        let broken = home.join("prop-mid-2/BUILD");
+        let script = std::fs::read_to_string(&broken).unwrap();
+        std::fs::write(&broken, "#!/bin/bash\nexit 1\n").unwrap();
+        let r = propagate::execute(&component_dir, home, &state.backend, env_name, "prop-leaf", false).await;
+        match r {
+            Err(lal::CliError::PropagationFailures(repos)) => assert_eq!(repos, vec!["prop-mid-2"]),
+            x => panic!("propagation should fail in prop-mid-2: {:?}", x),
+        }
+        let saved = lal::propagate::PropagationState::read(&component_dir.join(lal::propagate::STATE_FILE))
+            .expect("saved propagation state");
+        assert_eq!(saved.published.get("prop-mid-1"), Some(&2));
+        let lock = lal::Lockfile::from_path(&mid.join("ARTIFACT/lockfile.json"), "prop-mid-1").unwrap();
+        // the updated manifest.json is not committed
+        assert_eq!(lock.sha, Some(format!("{}-dirty", head)));
+        assert!(
+            !saved.published.contains_key("prop-base"),
+            "stopped before prop-base"
+        );
+
+        std::fs::write(&broken, script).unwrap();
+        let r = propagate::execute(&component_dir, home, &state.backend, env_name, "prop-leaf", true).await;
+        assert!(r.is_ok(), "resumed propagation: {:?}", r);
+        assert!(
+            !component_dir.join(lal::propagate::STATE_FILE).exists(),
+            "state removed when done"
+        );
+
+        for (repo, versions) in &[
+            ("prop-mid-1", vec![1, 2]),
+            ("prop-mid-2", vec![1, 2]),
+            ("prop-base", vec![1, 2]),
+        ] {
+            let mut published = state.backend.get_versions(repo, env_name).await.unwrap();
+            published.sort();
+            assert_eq!(&published, versions, "{} published once more", repo);
+        }
+        let manifest = lal::Manifest::read(&component_dir).unwrap();
+        assert_eq!(manifest.dependencies["prop-mid-1"].to_string(), "2");
+        assert_eq!(manifest.dependencies["prop-mid-2"].to_string(), "2");
+    });
+}
+
+#[test]
+fn test_propagate_published() {
+    let state = setup();
+    let env_name = "default";
+
+    state.rt.block_on(async {
+        let component_dir = publish_components(&state, env_name)
+            .await
+            .expect("published components");
+        let local = propagate::compute(&component_dir, "prop-leaf").expect("computed from INPUT");
+
+        // nothing needs to be checked out or fetched to plan from published lockfiles
+        std::fs::remove_dir_all(component_dir.join("INPUT")).unwrap();
+        let remote = propagate::compute_published(&state.backend, "prop-base", 1, env_name, "prop-leaf")
+            .await
+            .expect("computed from published lockfiles");
+        assert_eq!(
+            serde_json::to_value(&remote).unwrap(),
+            serde_json::to_value(&local).unwrap(),
+            "same propagation as from INPUT"
+        );
+
+        let r = propagate::print_published(&state.backend, "prop-base", 1, env_name, "prop-leaf").await;
+        assert!(r.is_ok(), "printed propagation of a published component: {:?}", r);
+
+        let r = propagate::print_published(&state.backend, "prop-base", 2, env_name, "prop-leaf").await;
+        assert!(r.is_err(), "unpublished root version fails");
+    });
+}
@@ -1,6 +1,6 @@
 use crate::common::*;
 use parameterized_macro::parameterized;
-use std::path::Path;
+use std::{fs, path::Path};
 
 use lal::Backend;
 
@@ -106,3 +106,36 @@ fn test_publish_without_version(env_name: &str) {
         assert!(r.is_err(), "can't publish without version: {:?}", r);
     });
 }
+
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_publish_records_checksum(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        let component_dir = clone_component_dir("heylib", &state);
+
+        let r = fetch::fetch_input(&component_dir, env_name, &state.backend).await;
+        assert!(r.is_ok(), "installed heylib dependencies: {:?}", r);
+
+        let r = build::build_for_release(&component_dir, env_name, state.tempdir.path(), "1");
+        assert!(r.is_ok(), "built heylib release: {:?}", r);
+
+        // ARTIFACT lockfile records the digest of the tarball next to it
+        let lock = lal::Lockfile::release_build(&component_dir).expect("read release lockfile");
+        let tarball = component_dir.join("ARTIFACT/heylib.tar.gz");
+        let digest = lal::checksum::sha256_file(&tarball).expect("hash tarball");
+        assert_eq!(lock.sha256, Some(digest), "release lockfile has tarball sha256");
+
+        // tampering with ARTIFACT after the build is caught before publishing
+        fs::write(&tarball, "not a tarball").expect("overwrite tarball");
+        let r = publish::publish_release(&component_dir, &state.backend, state.tempdir.path()).await;
+        assert!(
+            matches!(r, Err(lal::CliError::ChecksumMismatch(..))),
+            "refused to publish modified tarball: {:?}",
+            r
+        );
+    });
+}
@@ -13,7 +13,7 @@ fn test_query(env_name: &str) {
             .await
             .expect("published heylib=1 helloworld=1");
 
-        let r = lal::query(&state.backend, Some(&env_name), "hello", false).await;
+        let r = lal::query(&state.backend, Some(env_name), "hello", false, false).await;
         assert!(r.is_ok(), "could query for hello");
     });
 }
@@ -30,7 +30,7 @@ fn test_query_last(env_name: &str) {
             .await
             .expect("published heylib=1 helloworld=1");
 
-        let r = lal::query(&state.backend, Some(&env_name), "hello", true).await;
+        let r = lal::query(&state.backend, Some(env_name), "hello", true, false).await;
         assert!(r.is_ok(), "could query for hello");
     });
 }
@@ -0,0 +1,98 @@
+use crate::common::*;
+
+use lal::{Backend, Dependant};
+use std::collections::BTreeSet;
+
+fn dependant(name: &str, version: u32, direct: bool, uses: &str) -> Dependant {
+    Dependant {
+        name: name.into(),
+        version,
+        direct,
+        uses: vec![uses.to_string()].into_iter().collect::<BTreeSet<_>>(),
+    }
+}
+
+#[test]
+fn test_rdeps() {
+    let state = setup();
+    let env_name = "default";
+
+    state.rt.block_on(async {
+        let components = vec!["heylib", "prop-leaf", "prop-mid-1", "prop-mid-2", "prop-base"];
+        let r = publish_components(&state, env_name, components, "1").await;
+        assert!(r.is_ok(), "published components: {:?}", r);
+
+        let names = state.backend.list_components(env_name).await.unwrap();
+        assert_eq!(names, vec![
+            "heylib",
+            "prop-base",
+            "prop-leaf",
+            "prop-mid-1",
+            "prop-mid-2"
+        ]);
+
+        let r = lal::reverse_dependencies(&state.backend, "prop-leaf", None, env_name, 2).await;
+        let r = r.expect("found reverse dependencies");
+        assert!(r.skipped.is_empty(), "scanned everything: {:?}", r.skipped);
+        assert_eq!(r.dependants, vec![
+            dependant("prop-base", 1, false, "1"),
+            dependant("prop-mid-1", 1, true, "1"),
+            dependant("prop-mid-2", 1, true, "1"),
+        ]);
+        let r = lal::reverse_dependencies(&state.backend, "prop-leaf", Some("2"), env_name, 2).await;
+        assert_eq!(
+            r.ok().map(|r| r.dependants),
+            Some(vec![]),
+            "nothing uses prop-leaf=2"
+        );
+        let r = lal::reverse_dependencies(&state.backend, "heylib", None, env_name, 2).await;
+        assert_eq!(r.ok().map(|r| r.dependants), Some(vec![]), "nothing uses heylib");
+
+        // unchanged components are answered from the index
+        let index = state.backend.get_cache_dir().join("rdeps").join("default.json");
+        let mut json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&index).unwrap()).unwrap();
+        json["components"]["prop-mid-2"]["direct"] = serde_json::json!([]);
+        json["components"]["prop-mid-2"]["dependencies"] = serde_json::json!({});
+        std::fs::write(&index, json.to_string()).unwrap();
+        let r = lal::reverse_dependencies(&state.backend, "prop-leaf", Some("1"), env_name, 2).await;
+        let names: Vec<_> = r.unwrap().dependants.into_iter().map(|d| d.name).collect();
+        assert_eq!(names, vec!["prop-base", "prop-mid-1"], "used the index");
+
+        // and new versions are scanned again
+        let r = publish_component(&state, env_name, "prop-mid-2", "2").await;
+        assert!(r.is_ok(), "published prop-mid-2=2: {:?}", r);
+        let r = lal::reverse_dependencies(&state.backend, "prop-leaf", Some("1"), env_name, 2).await;
+        assert_eq!(
+            r.unwrap().dependants.pop(),
+            Some(dependant("prop-mid-2", 2, true, "1")),
+            "rescanned prop-mid-2"
+        );
+
+        let r = lal::rdeps(&state.backend, Some(env_name), "prop-leaf=1", 2).await;
+        assert!(r.is_ok(), "printed reverse dependencies: {:?}", r);
+        let r = lal::rdeps(&state.backend, None, "prop-leaf", 2).await;
+        assert!(
+            matches!(r, Err(lal::CliError::EnvironmentUnspecified)),
+            "needs an environment: {:?}",
+            r
+        );
+
+        // components that fail to scan are listed and fail the query
+        let lockfile = state
+            .backend
+            .get_cache_dir()
+            .join("environments/default/prop-base/1/lockfile.json");
+        std::fs::write(&lockfile, "not json").unwrap();
+        std::fs::remove_file(&index).unwrap();
+        let r = lal::reverse_dependencies(&state.backend, "prop-leaf", Some("1"), env_name, 2).await;
+        let r = r.expect("scanned the rest");
+        assert_eq!(r.skipped, vec!["prop-base"]);
+        let names: Vec<_> = r.dependants.into_iter().map(|d| d.name).collect();
+        assert_eq!(names, vec!["prop-mid-1", "prop-mid-2"], "found the others");
+        match lal::rdeps(&state.backend, Some(env_name), "prop-leaf", 2).await {
+            Err(e @ lal::CliError::IncompleteScan(_)) => assert_eq!(e.exit_code(), 8),
+            r => panic!("rdeps should fail on prop-base: {:?}", r),
+        }
+    });
+}
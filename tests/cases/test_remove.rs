@@ -11,7 +11,7 @@ fn test_remove_dependencies_no_save() {
     assert_eq!(manifest.dependencies.len(), 1);
     assert_eq!(
         manifest.dependencies.get_key_value("heylib"),
-        Some((&"heylib".to_string(), &1))
+        Some((&"heylib".to_string(), &lal::VersionRequirement::Exact(1)))
     );
 
     let save = false;
@@ -34,7 +34,7 @@ fn test_remove_dependencies_no_save() {
     assert_eq!(manifest.dependencies.len(), 1);
     assert_eq!(
         manifest.dependencies.get_key_value("heylib"),
-        Some((&"heylib".to_string(), &1))
+        Some((&"heylib".to_string(), &lal::VersionRequirement::Exact(1)))
     );
 }
 
@@ -49,7 +49,7 @@ pub fn test_remove_dependencies_with_save() {
     assert_eq!(manifest.dependencies.len(), 1);
     assert_eq!(
         manifest.dependencies.get_key_value("heylib"),
-        Some((&"heylib".to_string(), &1))
+        Some((&"heylib".to_string(), &lal::VersionRequirement::Exact(1)))
     );
 
     let save = true;
@@ -0,0 +1,73 @@
+use crate::common::*;
+
+use lal::{Backend, HttpBackend, HttpConfig, RetryConfig};
+
+#[test]
+fn test_download_retries_and_resumes() {
+    let state = setup();
+
+    state.rt.block_on(async {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let (addr, requests) = flaky::mock_server(data.clone());
+
+        let cfg = HttpConfig {
+            url: format!("http://{}/lal", addr),
+            retry: RetryConfig {
+                retries: 3,
+                backoff_ms: 10,
+                max_backoff_ms: 50,
+            },
+            ..Default::default()
+        };
+        let backend = HttpBackend::new(&cfg, state.tempdir.path()).expect("http backend");
+
+        let dest = state.tempdir.path().join("heylib.tar.gz");
+        let r = backend
+            .raw_fetch(&format!("http://{}/heylib.tar.gz", addr), &dest)
+            .await;
+        assert!(r.is_ok(), "downloaded through failures: {:?}", r);
+        assert_eq!(std::fs::read(&dest).unwrap(), data, "complete download");
+        assert!(
+            !state.tempdir.path().join("heylib.tar.gz.part").exists(),
+            "partial file renamed into place"
+        );
+
+        // the last request picked up where the dropped connection left off
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3, "failed twice then succeeded");
+        assert_eq!(requests[0], None);
+        assert_eq!(requests[1], None);
+        assert_eq!(requests[2], Some(format!("bytes={}-", data.len() / 2)));
+    });
+}
+
+#[test]
+fn test_download_gives_up() {
+    let state = setup();
+
+    state.rt.block_on(async {
+        let (addr, requests) = flaky::mock_server(vec![0; 10]);
+        let cfg = HttpConfig {
+            url: format!("http://{}/lal", addr),
+            retry: RetryConfig {
+                retries: 0,
+                backoff_ms: 10,
+                max_backoff_ms: 50,
+            },
+            ..Default::default()
+        };
+        let backend = HttpBackend::new(&cfg, state.tempdir.path()).expect("http backend");
+
+        let dest = state.tempdir.path().join("heylib.tar.gz");
+        let r = backend
+            .raw_fetch(&format!("http://{}/heylib.tar.gz", addr), &dest)
+            .await;
+        assert!(r.is_err(), "no retries configured");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(!dest.exists(), "nothing left behind");
+        assert!(
+            !state.tempdir.path().join("heylib.tar.gz.part").exists(),
+            "nothing left behind"
+        );
+    });
+}
@@ -0,0 +1,188 @@
+use crate::common::*;
+
+use lal::{Backend, CliError};
+use std::{fs, path::Path, process::Command};
+
+// A rootfs tarball with just /bin/sh and the libraries it links to
+fn minimal_rootfs(dir: &Path) -> std::path::PathBuf {
+    let output = dir.join("OUTPUT");
+    let ldd = Command::new("ldd").arg("/bin/sh").output().expect("ldd /bin/sh");
+    let libs = String::from_utf8_lossy(&ldd.stdout)
+        .split_whitespace()
+        .filter(|w| w.starts_with('/'))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    for file in libs.iter().map(String::as_str).chain(vec!["/bin/sh"]) {
+        let dest = output.join(&file[1..]);
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::copy(file, &dest).expect("copy into rootfs");
+    }
+    let tarball = dir.join("rootfs.tar.gz");
+    lal::output::tar(dir, &tarball).expect("tar rootfs");
+    tarball
+}
+
+#[test]
+fn test_rootfs_tarball() {
+    let state = setup();
+    let component_dir = clone_component_dir("heylib", &state);
+    fs::create_dir_all(component_dir.join("OUTPUT")).unwrap();
+    let cfg = lal::Config::read(Some(state.tempdir.path())).expect("read config");
+
+    let tarball = minimal_rootfs(&state.tempdir.path().join("minroot"));
+    let environment: lal::Environment =
+        serde_json::from_value(serde_json::json!({ "rootfs": tarball })).expect("parse rootfs environment");
+    assert!(matches!(environment, lal::Environment::Rootfs(_)));
+
+    // nothing from the host but the component is visible, and / is writable
+    let script = "test \"$PWD\" = /home/lal/volume && test \"$HOME\" = /home/lal && ! test -e /etc && echo \
+                  hi > /scratch && echo built > OUTPUT/done";
+    for _ in 0..2 {
+        let r = lal::shell(
+            &cfg,
+            &environment,
+            &Default::default(),
+            Some(vec!["/bin/sh", "-c", script]),
+            false,
+            &component_dir,
+        );
+        assert!(r.is_ok(), "ran in the rootfs: {:?}", r);
+    }
+    assert!(
+        component_dir.join("OUTPUT/done").is_file(),
+        "wrote to the component"
+    );
+
+    let unpacked: Vec<_> = fs::read_dir(cfg.cache.join("rootfs"))
+        .expect("rootfs cache")
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(unpacked.len(), 1, "unpacked once: {:?}", unpacked);
+    assert!(unpacked[0].join("bin/sh").is_file());
+    assert!(
+        !unpacked[0].join("scratch").exists(),
+        "cached rootfs left untouched"
+    );
+}
+
+#[test]
+fn test_rootfs_component() {
+    let state = setup();
+    let env_name = "default";
+
+    // any published component can be a rootfs, its OUTPUT being the filesystem
+    let heylib_dir = clone_component_dir("heylib", &state);
+    let r = build::build_for_release(&heylib_dir, env_name, state.tempdir.path(), "1");
+    assert!(r.is_ok(), "built heylib=1: {:?}", r);
+    let r = state.rt.block_on(publish::publish_release(
+        &heylib_dir,
+        &state.backend,
+        state.tempdir.path(),
+    ));
+    assert!(r.is_ok(), "published heylib=1: {:?}", r);
+
+    let environment: lal::Environment =
+        serde_json::from_str(r#"{"rootfs": {"component": "heylib", "environment": "default"}}"#)
+            .expect("parse rootfs environment");
+    let rootfs = match &environment {
+        lal::Environment::Rootfs(r) => r.clone(),
+        _ => panic!("parsed as a rootfs environment"),
+    };
+    let cache = state.backend.get_cache_dir();
+
+    let unpublished = lal::Rootfs {
+        source: lal::RootfsSource::Component {
+            component: "heylib".into(),
+            version: Some(2),
+            environment: env_name.into(),
+        },
+        network: false,
+    };
+    let r = lal::prepare_rootfs(&cache, &unpublished);
+    assert!(
+        matches!(r, Err(CliError::MissingRootfs(_))),
+        "heylib=2 not fetched: {:?}",
+        r
+    );
+
+    let r = state.rt.block_on(lal::env::update(
+        &heylib_dir,
+        &environment,
+        "heylib",
+        &state.backend,
+    ));
+    assert!(r.is_ok(), "fetched and unpacked the rootfs: {:?}", r);
+
+    let root = lal::prepare_rootfs(&cache, &rootfs).expect("unpacked rootfs");
+    assert!(root.starts_with(cache.join("rootfs")));
+    assert!(root.join("libhey.a").is_file(), "rootfs is the published OUTPUT");
+}
+
+// An uncompressed layer from (path, entry type, mode, link name or contents) tuples
+fn layer(path: &Path, entries: &[(&str, tar::EntryType, u32, &str)]) {
+    let mut builder = tar::Builder::new(fs::File::create(path).unwrap());
+    for (name, kind, mode, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(*kind);
+        header.set_mode(*mode);
+        if kind.is_symlink() {
+            header.set_link_name(data).unwrap();
+            header.set_size(0);
+            builder.append_data(&mut header, name, std::io::empty()).unwrap();
+        } else {
+            header.set_size(data.len() as u64);
+            builder.append_data(&mut header, name, data.as_bytes()).unwrap();
+        }
+    }
+    builder.finish().unwrap();
+}
+
+#[test]
+fn test_rootfs_layers_stay_inside() {
+    use std::os::unix::fs::PermissionsExt;
+    use tar::EntryType;
+
+    let state = setup();
+    let cache = state.tempdir.path().join("cache");
+    let host = state.tempdir.path().join("host");
+    fs::create_dir_all(&host).unwrap();
+    fs::write(host.join("keep"), "keep").unwrap();
+    fs::write(host.join("foo"), "foo").unwrap();
+    let host_link = host.to_str().unwrap();
+
+    // the first layer plants symlinks to the host, the second tries to go through them
+    let layout = state.tempdir.path().join("image");
+    let blobs = layout.join("blobs/sha256");
+    fs::create_dir_all(&blobs).unwrap();
+    layer(&blobs.join("layer1"), &[
+        ("usr", EntryType::Symlink, 0o777, host_link),
+        ("etc", EntryType::Directory, 0o500, ""),
+    ]);
+    layer(&blobs.join("layer2"), &[
+        ("usr/.wh..opq", EntryType::Regular, 0o644, ""),
+        ("usr/.wh.foo", EntryType::Regular, 0o644, ""),
+        ("usr/bar", EntryType::Regular, 0o644, "bar"),
+        ("etc", EntryType::Symlink, 0o777, host_link),
+        ("ok", EntryType::Regular, 0o644, "ok"),
+    ]);
+    let manifest = serde_json::json!({
+        "layers": [{ "digest": "sha256:layer1" }, { "digest": "sha256:layer2" }]
+    });
+    fs::write(blobs.join("manifest"), manifest.to_string()).unwrap();
+    let index = serde_json::json!({ "manifests": [{ "digest": "sha256:manifest" }] });
+    fs::write(layout.join("index.json"), index.to_string()).unwrap();
+
+    let rootfs = lal::Rootfs {
+        source: lal::RootfsSource::Path(layout),
+        network: false,
+    };
+    let root = lal::prepare_rootfs(&cache, &rootfs).expect("unpacked rootfs");
+    assert!(root.join("ok").is_file(), "unpacked the safe entries");
+
+    // nothing on the host was removed, added or changed
+    assert!(host.join("keep").is_file(), "opaque whiteout stayed inside");
+    assert!(host.join("foo").is_file(), "whiteout stayed inside");
+    assert!(!host.join("bar").exists(), "file stayed inside");
+    let mode = fs::metadata(&host).unwrap().permissions().mode() & 0o777;
+    assert_ne!(mode, 0o500, "directory mode stayed inside");
+}
@@ -0,0 +1,49 @@
+use crate::common::*;
+
+use lal::Backend;
+
+#[test]
+fn test_s3_publish_and_fetch() {
+    let state = setup();
+    let env_name = "default";
+
+    state.rt.block_on(async {
+        let (addr, objects) = s3::mock_server();
+        let backend = s3::backend(addr, &state.tempdir.path().join("s3cache"), true);
+
+        // publish two releases of heylib to s3
+        let component_dir = clone_component_dir("heylib", &state);
+        for version in &["1", "2"] {
+            let r = build::build_for_release(&component_dir, env_name, state.tempdir.path(), version);
+            assert!(r.is_ok(), "built heylib={}: {:?}", version, r);
+            let r = publish::publish_release(&component_dir, &backend, state.tempdir.path()).await;
+            assert!(r.is_ok(), "published heylib={} to s3: {:?}", version, r);
+        }
+        assert!(objects
+            .lock()
+            .unwrap()
+            .contains_key("env/default/heylib/2/lockfile.json"));
+
+        let versions = backend.get_versions("heylib", env_name).await;
+        assert_eq!(
+            versions.ok(),
+            Some(vec![2, 1]),
+            "listed versions in descending order"
+        );
+        let names = backend.list_components(env_name).await;
+        assert_eq!(names.ok(), Some(vec!["heylib".to_string()]), "listed components");
+
+        // helloworld depends on heylib=1
+        let component_dir = clone_component_dir("helloworld", &state);
+        let r = fetch::fetch_input(&component_dir, env_name, &backend).await;
+        assert!(r.is_ok(), "installed helloworld dependencies from s3: {:?}", r);
+        let lf = lal::Lockfile::from_path(&component_dir.join("INPUT/heylib/lockfile.json"), "heylib")
+            .expect("read heylib lockfile");
+        assert_eq!(lf.version, "1");
+
+        // unsigned requests are refused by the mock
+        let anonymous = s3::backend(addr, &state.tempdir.path().join("s3cache-anon"), false);
+        let r = anonymous.get_versions("heylib", env_name).await;
+        assert!(r.is_err(), "anonymous listing refused: {:?}", r);
+    });
+}
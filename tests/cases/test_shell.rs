@@ -54,3 +54,148 @@ fn test_run_scripts(env_name: &str) {
     );
     assert!(r.is_ok(), "could run `subroutine` script");
 }
+
+#[test]
+fn test_podman_runtime() {
+    let state = setup();
+    let component_dir = clone_component_dir("heylib", &state);
+    let cfg = lal::Config::read(Some(state.tempdir.path())).expect("read config");
+
+    // environments pick their runtime in the config or the manifest
+    let environment: lal::Environment =
+        serde_json::from_str(r#"{"name": "clux/lal-alpine", "tag": "3.6", "runtime": "podman"}"#)
+            .expect("parse podman environment");
+    match &environment {
+        lal::Environment::Container(c) => assert_eq!(c.runtime, lal::ContainerRuntime::Podman),
+        _ => panic!("parsed as a container environment"),
+    }
+
+    // docker stays the default, and is left out of written configs and lockfiles
+    let docker = serde_json::to_value(lal::Container::new("clux/lal-alpine:3.6")).unwrap();
+    assert!(docker.get("runtime").is_none(), "default runtime not serialized");
+
+    let modes = lal::ShellModes {
+        printonly: true,
+        ..Default::default()
+    };
+    let r = lal::shell(
+        &cfg,
+        &environment,
+        &modes,
+        Some(vec!["echo", "hi"]),
+        false,
+        &component_dir,
+    );
+    assert!(r.is_ok(), "printed podman command: {:?}", r);
+}
+
+#[test]
+fn test_sandbox_environment() {
+    let state = setup();
+    let component_dir = clone_component_dir("heylib", &state);
+    std::fs::create_dir_all(component_dir.join("INPUT")).unwrap();
+    let cfg = lal::Config::read(Some(state.tempdir.path())).expect("read config");
+
+    let paths: Vec<&str> = ["/usr", "/bin", "/lib", "/lib64", "/etc"]
+        .iter()
+        .filter(|p| std::path::Path::new(p).exists())
+        .cloned()
+        .collect();
+    let environment: lal::Environment =
+        serde_json::from_value(serde_json::json!({ "sandbox": paths })).expect("parse sandbox environment");
+    match &environment {
+        lal::Environment::Sandbox(s) => assert!(!s.network, "network is off by default"),
+        _ => panic!("parsed as a sandbox environment"),
+    }
+
+    let config = state.tempdir.path().join(".lal").join("config");
+    assert!(config.exists(), "config written by setup");
+    // host processes and devices are not visible either
+    let script = format!(
+        "test ! -e {} && ! touch INPUT/probe 2>/dev/null && touch OUTPUT && test $(grep -c : /proc/net/dev) = 1 \
+         && test ! -e /proc/{} && test -c /dev/null && test -L /dev/fd && test $(ls /dev | wc -l) -le 11",
+        config.display(),
+        std::process::id()
+    );
+    let r = lal::shell(
+        &cfg,
+        &environment,
+        &Default::default(),
+        Some(vec!["sh", "-c", &script]),
+        false,
+        &component_dir,
+    );
+    assert!(r.is_ok(), "ran isolated from the host: {:?}", r);
+    assert!(component_dir.join("OUTPUT").exists(), "component dir is writable");
+    assert!(!component_dir.join("INPUT/probe").exists(), "INPUT is read-only");
+
+    // the exit code makes it out of the namespace, and timeouts still stop everything
+    let r = lal::shell(
+        &cfg,
+        &environment,
+        &Default::default(),
+        Some(vec!["sh", "-c", "exit 3"]),
+        false,
+        &component_dir,
+    );
+    assert!(
+        matches!(r, Err(lal::CliError::SubprocessFailure(3))),
+        "exit code kept: {:?}",
+        r
+    );
+    let modes = lal::ShellModes {
+        limits: lal::Limits {
+            timeout: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let start = std::time::Instant::now();
+    let r = lal::shell(
+        &cfg,
+        &environment,
+        &modes,
+        Some(vec!["sh", "-c", "sleep 30 & sleep 30"]),
+        false,
+        &component_dir,
+    );
+    assert!(matches!(r, Err(lal::CliError::Timeout(1))), "timed out: {:?}", r);
+    assert!(start.elapsed().as_secs() < 10, "stopped without the grace period");
+}
+
+#[test]
+fn test_shell_timeout() {
+    let state = setup();
+    let component_dir = clone_component_dir("heylib", &state);
+    let cfg = lal::Config::read(Some(state.tempdir.path())).expect("read config");
+
+    let modes = lal::ShellModes {
+        limits: lal::Limits {
+            timeout: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    // the background sleep has to be stopped along with the shell
+    let start = std::time::Instant::now();
+    let r = lal::shell(
+        &cfg,
+        &lal::Environment::None,
+        &modes,
+        Some(vec!["sh", "-c", "sleep 30 & sleep 30"]),
+        false,
+        &component_dir,
+    );
+    assert!(matches!(r, Err(lal::CliError::Timeout(1))), "timed out: {:?}", r);
+    assert!(start.elapsed().as_secs() < 10, "stopped without the grace period");
+
+    let r = lal::shell(
+        &cfg,
+        &lal::Environment::None,
+        &modes,
+        Some(vec!["true"]),
+        false,
+        &component_dir,
+    );
+    assert!(r.is_ok(), "quick commands are unaffected: {:?}", r);
+}
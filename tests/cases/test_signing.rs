@@ -0,0 +1,146 @@
+use crate::common::*;
+
+use lal::{
+    signing::SignaturePolicy, BackendConfiguration, CachedBackend, CliError, LocalBackend, TrustedBackend,
+};
+use openssl::pkey::PKey;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Write a fresh ed25519 private key and return its public key in PEM
+fn generate_key(path: &Path) -> String {
+    let key = PKey::generate_ed25519().unwrap();
+    fs::write(path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+    String::from_utf8(key.public_key_to_pem().unwrap()).unwrap()
+}
+
+// Retrieve a published heylib, returning where its tarball is cached
+async fn retrieve(backend: &TrustedBackend<LocalBackend>, version: u32) -> lal::LalResult<PathBuf> {
+    let (tarball, _) = backend
+        .retrieve_published_component("heylib", Some(version), "default")
+        .await?;
+    Ok(tarball)
+}
+
+// The local backend of the test config, trusting a single key
+fn trusted_backend(home: &Path, key: &str, strict: bool) -> TrustedBackend<LocalBackend> {
+    let config = lal::Config::read(Some(home)).expect("read config");
+    let backend = match &config.backend {
+        BackendConfiguration::Local(cfg) => LocalBackend::new(cfg, &config.cache).unwrap(),
+        _ => unreachable!(),
+    };
+    TrustedBackend::new(backend, SignaturePolicy::new(&[key.to_string()], strict).unwrap())
+}
+
+#[test]
+fn test_signed_publish_and_fetch() {
+    let state = setup();
+    let home = state.tempdir.path();
+    let env_name = "default";
+    let trusted = generate_key(&home.join("trusted.pem"));
+    let untrusted = generate_key(&home.join("untrusted.pem"));
+
+    let mut config = lal::Config::read(Some(home)).expect("read config");
+    config.signing_key = Some(home.join("trusted.pem"));
+    config.write(false, Some(home)).expect("set signing key");
+
+    let heylib_dir = clone_component_dir("heylib", &state);
+    let release = |version: &str| {
+        let r = build::build_for_release(&heylib_dir, env_name, home, version);
+        assert!(r.is_ok(), "built heylib={}: {:?}", version, r);
+        let r = state
+            .rt
+            .block_on(publish::publish_release(&heylib_dir, &state.backend, home));
+        assert!(r.is_ok(), "published heylib={}: {:?}", version, r);
+    };
+    release("1");
+
+    let lock: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(heylib_dir.join("ARTIFACT/lockfile.json")).unwrap())
+            .unwrap();
+    assert!(
+        lock["signature"]["value"].is_string(),
+        "release lockfile is signed"
+    );
+
+    state.rt.block_on(async {
+        let strict = trusted_backend(home, &trusted, true);
+        let r = retrieve(&strict, 1).await;
+        assert!(r.is_ok(), "trusted signature accepted: {:?}", r);
+
+        let r = retrieve(&trusted_backend(home, &untrusted, true), 1).await;
+        assert!(
+            matches!(r, Err(CliError::InvalidSignature(..))),
+            "untrusted key refused in strict mode: {:?}",
+            r
+        );
+        let r = retrieve(&trusted_backend(home, &untrusted, false), 1).await;
+        assert!(r.is_ok(), "untrusted key only warns otherwise: {:?}", r);
+    });
+
+    // an unsigned release
+    let mut config = lal::Config::read(Some(home)).expect("read config");
+    config.signing_key = None;
+    config.write(false, Some(home)).expect("unset signing key");
+    release("2");
+
+    state.rt.block_on(async {
+        let r = retrieve(&trusted_backend(home, &trusted, true), 2).await;
+        assert!(
+            matches!(r, Err(CliError::InvalidSignature(..))),
+            "unsigned release refused in strict mode: {:?}",
+            r
+        );
+        let r = retrieve(&trusted_backend(home, &trusted, false), 2).await;
+        assert!(r.is_ok(), "unsigned release allowed otherwise: {:?}", r);
+
+        // the signature survives the lockfile being re-serialized
+        let strict = trusted_backend(home, &trusted, true);
+        let tarball = retrieve(&strict, 1).await.unwrap();
+        let lockfile = tarball.with_file_name("lockfile.json");
+        let read_lock = |path: &Path| -> serde_json::Value {
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        let signed = read_lock(&lockfile);
+        fs::write(&lockfile, serde_json::to_string(&signed).unwrap()).unwrap();
+        let r = retrieve(&strict, 1).await;
+        assert!(r.is_ok(), "compact lockfile still verifies: {:?}", r);
+
+        // a signed release served as another version is refused
+        let unsigned_tarball = retrieve(&trusted_backend(home, &trusted, false), 2)
+            .await
+            .unwrap();
+        let unsigned_copy = home.join("heylib-2.tar.gz");
+        fs::copy(&unsigned_tarball, &unsigned_copy).unwrap();
+        fs::copy(&tarball, &unsigned_tarball).unwrap();
+        fs::copy(&lockfile, unsigned_tarball.with_file_name("lockfile.json")).unwrap();
+        let r = retrieve(&strict, 2).await;
+        match r {
+            Err(CliError::InvalidSignature(name, reason)) => {
+                assert_eq!(name, "heylib");
+                assert!(
+                    reason.contains("heylib=1"),
+                    "names the signed version: {}",
+                    reason
+                );
+            }
+            x => panic!("replayed release should fail verification: {:?}", x),
+        }
+
+        // tampering with a signed lockfile breaks its signature
+        fs::copy(&unsigned_copy, &tarball).unwrap();
+        let mut lock = signed.clone();
+        lock["sha256"] = lal::checksum::sha256_file(&tarball).unwrap().into();
+        fs::write(&lockfile, serde_json::to_string_pretty(&lock).unwrap()).unwrap();
+        let r = retrieve(&strict, 1).await;
+        match r {
+            Err(CliError::InvalidSignature(name, reason)) => {
+                assert_eq!(name, "heylib");
+                assert!(reason.contains("bad signature"), "signature mismatch: {}", reason);
+            }
+            x => panic!("tampered lockfile should fail verification: {:?}", x),
+        }
+    });
+}
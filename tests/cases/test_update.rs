@@ -21,7 +21,7 @@ fn assert_manifest(component_dir: &Path, name: &str, version: u32) {
     let manifest = lal::Manifest::read(&component_dir).expect("read manifest");
     assert_eq!(
         manifest.dependencies.get_key_value(name),
-        Some((&name.to_string(), &version))
+        Some((&name.to_string(), &lal::VersionRequirement::Exact(version)))
     );
 }
 
@@ -29,7 +29,7 @@ fn assert_manifest_dev(component_dir: &Path, name: &str, version: u32) {
     let manifest = lal::Manifest::read(&component_dir).expect("read manifest");
     assert_eq!(
         manifest.devDependencies.get_key_value(name),
-        Some((&name.to_string(), &version))
+        Some((&name.to_string(), &lal::VersionRequirement::Exact(version)))
     );
 }
 
@@ -197,6 +197,78 @@ fn test_update_with_save(env_name: &str) {
     });
 }
 
+#[parameterized(env_name = {"default", "alpine"})]
+fn test_update_range_with_save(env_name: &str) {
+    let state = setup();
+    if !cfg!(feature = "docker") && env_name == "alpine" {
+        return;
+    }
+
+    state.rt.block_on(async {
+        let r = publish_component_versions(&state, env_name, "heylib", vec!["1", "2", "3"]).await;
+        assert!(r.is_ok(), "published heylib=1,2,3: {:?}", r);
+
+        let component_dir = clone_component_dir("helloworld", &state);
+
+        // lal update --save "heylib=<3"
+        let r = update::update_with_save(
+            &component_dir,
+            env_name,
+            &state.backend,
+            vec!["heylib=<3"],
+            true,
+            false,
+        )
+        .await;
+        assert!(r.is_ok(), "updated heylib=<3: {:?}", r);
+        assert_lockfile(&component_dir.join("INPUT/heylib/lockfile.json"), "heylib", 2);
+
+        // the range itself is saved rather than what it resolved to
+        let manifest = lal::Manifest::read(&component_dir).expect("read manifest");
+        assert_eq!(
+            manifest.dependencies.get("heylib"),
+            Some(&lal::VersionRequirement::Range("<3".into()))
+        );
+
+        // and pinned to what it resolved to
+        let lock = lal::ResolvedLock::read(&component_dir).expect("read resolved lock");
+        assert_eq!(lock.dependencies["heylib"].version, 2);
+        assert!(
+            lock.artifact("heylib", env_name).is_some(),
+            "recorded heylib artifact"
+        );
+    });
+}
+
+#[test]
+fn test_version_requirements() {
+    use lal::VersionRequirement;
+
+    let req = VersionRequirement::parse(">=2, <4").expect("major range");
+    assert_eq!(req.resolve(&[1, 2, 3, 4]), Some(3));
+    let req = VersionRequirement::parse("^3").expect("caret range");
+    assert!(req.matches(3) && !req.matches(4));
+
+    // published versions have no minor or patch part to match on
+    for r in &["^3.2", "~3.1", ">=3.2", "=3.0.1"] {
+        match VersionRequirement::parse(r) {
+            Err(lal::CliError::MinorVersionRequirement(s)) => assert_eq!(&s, r),
+            x => panic!("{} should be rejected: {:?}", r, x),
+        }
+    }
+    let mut manifest = lal::Manifest::new("foo", "default", std::path::PathBuf::new());
+    manifest
+        .dependencies
+        .insert("heylib".into(), VersionRequirement::Range("~3.1".into()));
+    assert!(
+        matches!(
+            manifest.verify(),
+            Err(lal::CliError::InvalidVersionRequirement(..))
+        ),
+        "manifest with a minor range is invalid"
+    );
+}
+
 #[parameterized(env_name = {"default", "alpine"})]
 fn test_update_to_latest_with_save(env_name: &str) {
     let state = setup();
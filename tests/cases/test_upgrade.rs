@@ -2,7 +2,8 @@
 #[test]
 #[ignore]
 pub fn upgrade_does_not_fail() {
-    let uc = lal::upgrade(true);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let uc = rt.block_on(lal::upgrade(true));
     assert!(uc.is_ok(), "could perform upgrade check");
     let upgraded = uc.unwrap();
     assert!(!upgraded, "we never have upgrades in the tip source tree");
@@ -22,6 +22,7 @@ pub fn options(
         sha: None,
         force: false,
         simple_verify: false,
+        no_cache: false,
     })
 }
 
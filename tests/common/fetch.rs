@@ -8,7 +8,7 @@ pub async fn fetch_input(
     let manifest = lal::Manifest::read(&component_dir)?;
     debug!("Component manifest: {:?}", manifest);
 
-    lal::fetch(&component_dir, &manifest, backend, true, &env_name).await
+    lal::fetch(component_dir, &manifest, backend, true, env_name, 1, false).await
 }
 
 pub async fn fetch_dev_input(
@@ -18,5 +18,26 @@ pub async fn fetch_dev_input(
 ) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
 
-    lal::fetch(&component_dir, &manifest, backend, false, &env_name).await
+    lal::fetch(component_dir, &manifest, backend, false, env_name, 1, false).await
+}
+
+pub async fn fetch_input_with_jobs(
+    component_dir: &Path,
+    env_name: &str,
+    backend: &dyn lal::CachedBackend,
+    jobs: usize,
+) -> lal::LalResult<()> {
+    let manifest = lal::Manifest::read(component_dir)?;
+
+    lal::fetch(component_dir, &manifest, backend, true, env_name, jobs, false).await
+}
+
+pub async fn fetch_locked_input(
+    component_dir: &Path,
+    env_name: &str,
+    backend: &dyn lal::CachedBackend,
+) -> lal::LalResult<()> {
+    let manifest = lal::Manifest::read(component_dir)?;
+
+    lal::fetch(component_dir, &manifest, backend, true, env_name, 1, true).await
 }
@@ -0,0 +1,83 @@
+use hyper::{
+    body::Bytes,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+/// Range headers of every request seen, `None` for requests without one
+pub type Requests = Arc<Mutex<Vec<Option<String>>>>;
+
+// Serves `data` at every path, but badly:
+// 1. 503 Service Unavailable
+// 2. the first half of the body, then the connection drops
+// 3+ honours the range with a chunked 206 Partial Content (no content-length)
+async fn handle(data: Bytes, seen: Requests, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let range = req
+        .headers()
+        .get("range")
+        .map(|r| r.to_str().unwrap().to_string());
+    let nth = {
+        let mut seen = seen.lock().unwrap();
+        seen.push(range.clone());
+        seen.len()
+    };
+
+    let resp = match nth {
+        1 => Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(Body::empty())
+            .unwrap(),
+        2 => {
+            let (mut tx, body) = Body::channel();
+            let half = data.slice(..data.len() / 2);
+            tokio::spawn(async move {
+                tx.send_data(half).await.unwrap();
+                // give the data time to reach the client before dropping the connection
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                tx.abort();
+            });
+            Response::builder()
+                .header("content-length", data.len())
+                .body(body)
+                .unwrap()
+        }
+        _ => {
+            let start: usize = range
+                .as_deref()
+                .and_then(|r| r.strip_prefix("bytes="))
+                .and_then(|r| r.trim_end_matches('-').parse().ok())
+                .unwrap_or(0);
+            let rest = data.slice(start..);
+            let chunks = vec![Ok::<_, Infallible>(rest)];
+            Response::builder()
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    "content-range",
+                    format!("bytes {}-{}/{}", start, data.len() - 1, data.len()),
+                )
+                .body(Body::wrap_stream(futures::stream::iter(chunks)))
+                .unwrap()
+        }
+    };
+    Ok(resp)
+}
+
+/// Start an unreliable file server on a free port in the current runtime
+pub fn mock_server(data: Vec<u8>) -> (SocketAddr, Requests) {
+    let data = Bytes::from(data);
+    let seen = Requests::default();
+    let state = seen.clone();
+    let make_svc = make_service_fn(move |_| {
+        let (data, state) = (data.clone(), state.clone());
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(data.clone(), state.clone(), req))) }
+    });
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (addr, seen)
+}
@@ -1,21 +1,21 @@
 use std::path::Path;
 
-pub fn list_environments(home: &Path) -> lal::LalResult<()> {
+pub fn list_environments(home: &Path, json: bool) -> lal::LalResult<()> {
     let config = lal::Config::read(Some(&home))?;
-    lal::list::environments(&config)
+    lal::list::environments(&config, json)
 }
 
-pub fn list_dependencies(component_dir: &Path, core: bool) -> lal::LalResult<()> {
+pub fn list_dependencies(component_dir: &Path, core: bool, json: bool) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::list::dependencies(&manifest, core)
+    lal::list::dependencies(&manifest, core, json)
 }
 
-pub fn list_configurations(component_dir: &Path) -> lal::LalResult<()> {
+pub fn list_configurations(component_dir: &Path, json: bool) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::list::configurations(&manifest.name.to_string(), &manifest)
+    lal::list::configurations(&manifest.name.to_string(), &manifest, json)
 }
 
-pub fn list_buildables(component_dir: &Path) -> lal::LalResult<()> {
+pub fn list_buildables(component_dir: &Path, json: bool) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::list::buildables(&manifest)
+    lal::list::buildables(&manifest, json)
 }
@@ -21,15 +21,19 @@ use lal::{BackendConfiguration, Config, LalResult, LocalBackend};
 pub mod build;
 pub mod envs;
 pub mod fetch;
+pub mod flaky;
 pub mod init;
 pub mod list;
+pub mod oci;
 pub mod propagate;
 pub mod publish;
+pub mod s3;
 pub mod shell;
 pub mod stash;
 pub mod status;
 pub mod update;
 pub mod verify;
+pub mod webdav;
 
 pub struct TestState {
     pub rt: Runtime,
@@ -102,10 +106,12 @@ where
         "alpine" => lal::Environment::Container(lal::Container {
             name: "clux/lal-alpine".to_string(),
             tag: "3.6".to_string(),
+            runtime: lal::ContainerRuntime::Docker,
         }),
         "xenial" => lal::Environment::Container(lal::Container {
             name: "clux/lal-xenial".to_string(),
             tag: "latest".to_string(),
+            runtime: lal::ContainerRuntime::Docker,
         }),
         _ => panic!("Unknown environment"),
     };
@@ -0,0 +1,161 @@
+use hyper::{
+    server::conn::AddrIncoming,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+const TOKEN: &str = "mock-token";
+
+#[derive(Default)]
+pub struct Registry {
+    /// Blobs by digest
+    pub blobs: BTreeMap<String, Vec<u8>>,
+    /// Manifests by `<repo>:<tag>`
+    pub manifests: BTreeMap<String, Vec<u8>>,
+}
+
+type State = Arc<Mutex<Registry>>;
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder().status(code).body(Body::empty()).unwrap()
+}
+
+// Just enough of the distribution API for `OciBackend`, behind token auth
+async fn handle(addr: SocketAddr, reg: State, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().unwrap_or_default().to_string();
+
+    // token endpoint only hands out tokens for credentials
+    if path == "/token" {
+        if !req.headers().contains_key("authorization") {
+            return Ok(status(StatusCode::UNAUTHORIZED));
+        }
+        return Ok(Response::new(Body::from(format!("{{\"token\": \"{}\"}}", TOKEN))));
+    }
+    let authed = req
+        .headers()
+        .get("authorization")
+        .map(|h| h.to_str().unwrap() == format!("Bearer {}", TOKEN))
+        .unwrap_or(false);
+    if !authed {
+        return Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(
+                "WWW-Authenticate",
+                format!("Bearer realm=\"http://{}/token\",service=\"mock\"", addr),
+            )
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    let rest = path.trim_start_matches("/v2/");
+    let resp = if rest == "_catalog" {
+        let repos: BTreeSet<String> = reg
+            .lock()
+            .unwrap()
+            .manifests
+            .keys()
+            .filter_map(|k| k.split_once(':').map(|(repo, _)| repo.to_string()))
+            .collect();
+        let body = serde_json::json!({ "repositories": repos });
+        Response::new(Body::from(body.to_string()))
+    } else if let Some(repo) = rest.strip_suffix("/tags/list") {
+        // two tags per page to exercise pagination
+        let last = query.strip_prefix("last=").unwrap_or_default().to_string();
+        let prefix = format!("{}:", repo);
+        let tags: Vec<String> = reg
+            .lock()
+            .unwrap()
+            .manifests
+            .keys()
+            .filter_map(|k| k.strip_prefix(&prefix))
+            .filter(|t| last.is_empty() || *t > last.as_str())
+            .map(String::from)
+            .collect();
+        if tags.is_empty() && last.is_empty() {
+            return Ok(status(StatusCode::NOT_FOUND));
+        }
+        let page: Vec<_> = tags.iter().take(2).collect();
+        let mut resp = Response::builder();
+        if tags.len() > 2 {
+            resp = resp.header(
+                "Link",
+                format!("</v2/{}/tags/list?last={}>; rel=\"next\"", repo, page[1]),
+            );
+        }
+        let body = serde_json::json!({ "name": repo, "tags": page });
+        resp.body(Body::from(body.to_string())).unwrap()
+    } else if let Some((repo, tag)) = rest.split_once("/manifests/") {
+        let key = format!("{}:{}", repo, tag);
+        match *req.method() {
+            Method::PUT => {
+                let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                reg.lock().unwrap().manifests.insert(key, data.to_vec());
+                status(StatusCode::CREATED)
+            }
+            _ => match reg.lock().unwrap().manifests.get(&key) {
+                Some(data) => Response::new(Body::from(data.clone())),
+                None => status(StatusCode::NOT_FOUND),
+            },
+        }
+    } else if rest.ends_with("/blobs/uploads/") && req.method() == Method::POST {
+        Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .header("Location", format!("/v2/{}some-upload-id", rest))
+            .body(Body::empty())
+            .unwrap()
+    } else if rest.contains("/blobs/uploads/") && req.method() == Method::PUT {
+        let digest = query
+            .strip_prefix("digest=")
+            .unwrap_or_default()
+            .replace("%3A", ":");
+        let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        if digest != format!("sha256:{:x}", Sha256::digest(&data)) {
+            return Ok(status(StatusCode::BAD_REQUEST));
+        }
+        reg.lock().unwrap().blobs.insert(digest, data.to_vec());
+        status(StatusCode::CREATED)
+    } else if let Some((_, digest)) = rest.split_once("/blobs/") {
+        match reg.lock().unwrap().blobs.get(digest) {
+            Some(_) if req.method() == Method::HEAD => Response::new(Body::empty()),
+            Some(data) => Response::new(Body::from(data.clone())),
+            None => status(StatusCode::NOT_FOUND),
+        }
+    } else {
+        status(StatusCode::BAD_REQUEST)
+    };
+    Ok(resp)
+}
+
+/// Start an in-memory registry on a free port in the current runtime
+pub fn mock_server() -> (SocketAddr, State) {
+    let registry = State::default();
+    let state = registry.clone();
+    let incoming = AddrIncoming::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = incoming.local_addr();
+    let make_svc = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(addr, state.clone(), req))) }
+    });
+    tokio::spawn(Server::builder(incoming).serve(make_svc));
+    (addr, registry)
+}
+
+pub fn backend(addr: SocketAddr, cache: &std::path::Path) -> lal::OciBackend {
+    let cfg = lal::OciConfig {
+        registry: format!("http://{}", addr),
+        namespace: "lal".into(),
+        credentials: Some(lal::Credentials {
+            username: "lal".into(),
+            password: "secret".into(),
+        }),
+    };
+    lal::OciBackend::new(&cfg, cache).expect("oci backend")
+}
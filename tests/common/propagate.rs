@@ -18,3 +18,52 @@ pub fn print_json(component_dir: &Path, leaf: &str) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
     lal::propagate::print(&component_dir, &manifest, leaf, true)
 }
+
+pub async fn execute(
+    component_dir: &Path,
+    home: &Path,
+    backend: &dyn lal::CachedBackend,
+    env_name: &str,
+    leaf: &str,
+    resume: bool,
+) -> lal::LalResult<()> {
+    let manifest = lal::Manifest::read(component_dir)?;
+    let config = lal::Config::read(Some(home))?;
+    let opts = lal::propagate::ExecuteOptions {
+        checkouts: lal::propagate::workspace_checkouts(home)?,
+        state: component_dir.join(lal::propagate::STATE_FILE),
+        resume,
+    };
+    lal::propagate::execute(
+        component_dir,
+        &manifest,
+        &config,
+        Some(home),
+        backend,
+        env_name,
+        leaf,
+        &opts,
+    )
+    .await
+}
+
+pub async fn compute_published(
+    backend: &dyn lal::CachedBackend,
+    root: &str,
+    version: u32,
+    env_name: &str,
+    leaf: &str,
+) -> lal::LalResult<lal::propagate::UpdateSequence> {
+    let lockfile = lal::propagate::published_lockfile(backend, root, version, env_name, 2).await?;
+    lal::propagate::compute(&lockfile, leaf)
+}
+
+pub async fn print_published(
+    backend: &dyn lal::CachedBackend,
+    root: &str,
+    version: u32,
+    env_name: &str,
+    leaf: &str,
+) -> lal::LalResult<()> {
+    lal::propagate::print_published(backend, root, version, env_name, leaf, true, 2).await
+}
@@ -0,0 +1,110 @@
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+// Just enough of the S3 API for `S3Backend` on a single bucket
+async fn handle(objects: Objects, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let signed = req
+        .headers()
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.starts_with("AWS4-HMAC-SHA256 Credential="))
+        .unwrap_or(false);
+    if !signed {
+        return Ok(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    let url = reqwest::Url::parse(&format!("http://mock{}", req.uri())).unwrap();
+    let key = url
+        .path()
+        .trim_start_matches('/')
+        .split_once('/')
+        .map(|(_, k)| k.to_string())
+        .unwrap_or_default();
+    let resp = match (req.method().clone(), key.is_empty()) {
+        // ListObjectsV2 with a delimiter
+        (Method::GET, true) => {
+            let prefix = url
+                .query_pairs()
+                .find(|(k, _)| k == "prefix")
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_default();
+            let prefixes: BTreeSet<String> = objects
+                .lock()
+                .unwrap()
+                .keys()
+                .filter_map(|k| k.strip_prefix(&prefix))
+                .filter_map(|rest| rest.find('/').map(|i| format!("{}{}", prefix, &rest[..=i])))
+                .collect();
+            let body: String = prefixes
+                .iter()
+                .map(|p| format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", p))
+                .collect();
+            Response::new(Body::from(format!(
+                "<ListBucketResult><Prefix>{}</Prefix>{}</ListBucketResult>",
+                prefix, body
+            )))
+        }
+        (Method::GET, false) => match objects.lock().unwrap().get(&key) {
+            Some(data) => Response::new(Body::from(data.clone())),
+            None => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .unwrap(),
+        },
+        (Method::PUT, false) => {
+            let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            objects.lock().unwrap().insert(key, data.to_vec());
+            Response::new(Body::empty())
+        }
+        _ => Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::empty())
+            .unwrap(),
+    };
+    Ok(resp)
+}
+
+/// Start an in-memory S3 server on a free port in the current runtime
+pub fn mock_server() -> (SocketAddr, Objects) {
+    let objects = Objects::default();
+    let state = objects.clone();
+    let make_svc = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    });
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (addr, objects)
+}
+
+pub fn backend(addr: SocketAddr, cache: &std::path::Path, signed: bool) -> lal::S3Backend {
+    let cfg = lal::S3Config {
+        endpoint: format!("http://{}", addr),
+        bucket: "lal".into(),
+        region: "us-east-1".into(),
+        credentials: if signed {
+            Some(lal::S3Credentials {
+                access_key_id: "lal".into(),
+                secret_access_key: "secret".into(),
+                session_token: None,
+            })
+        } else {
+            None
+        },
+    };
+    lal::S3Backend::new(&cfg, cache).expect("s3 backend")
+}
@@ -2,15 +2,20 @@ use std::path::Path;
 
 pub fn status(component_dir: &Path) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::status(&component_dir, &manifest, false, false, false)
+    lal::status(component_dir, &manifest, false, false, false, false)
 }
 
 pub fn full_status(component_dir: &Path) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::status(&component_dir, &manifest, true, false, false)
+    lal::status(component_dir, &manifest, true, false, false, false)
 }
 
 pub fn full_descriptive_status(component_dir: &Path) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::status(&component_dir, &manifest, true, true, true)
+    lal::status(component_dir, &manifest, true, true, true, false)
+}
+
+pub fn json_status(component_dir: &Path, full: bool) -> lal::LalResult<()> {
+    let manifest = lal::Manifest::read(component_dir)?;
+    lal::status(component_dir, &manifest, full, false, false, true)
 }
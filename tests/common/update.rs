@@ -21,6 +21,7 @@ pub async fn update(
         false,
         false,
         &env_name,
+        1,
     )
     .await
 }
@@ -31,7 +32,7 @@ pub async fn update_all(
     backend: &dyn lal::CachedBackend,
 ) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::update_all(&component_dir, &manifest, backend, false, false, &env_name).await
+    lal::update_all(component_dir, &manifest, backend, false, false, env_name, 1).await
 }
 
 pub async fn update_with_save(
@@ -57,6 +58,7 @@ pub async fn update_with_save(
         save,
         savedev,
         &env_name,
+        1,
     )
     .await
 }
@@ -69,5 +71,5 @@ pub async fn update_all_with_save(
     savedev: bool,
 ) -> lal::LalResult<()> {
     let manifest = lal::Manifest::read(&component_dir)?;
-    lal::update_all(&component_dir, &manifest, backend, save, savedev, &env_name).await
+    lal::update_all(component_dir, &manifest, backend, save, savedev, env_name, 1).await
 }
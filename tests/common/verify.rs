@@ -6,3 +6,12 @@ pub fn verify(component_dir: &Path, env_name: &str, simple: bool) -> lal::LalRes
 
     Ok(manifest)
 }
+
+pub fn verify_report(
+    component_dir: &Path,
+    env_name: &str,
+    simple: bool,
+) -> lal::LalResult<lal::VerifyReport> {
+    let manifest = lal::Manifest::read(component_dir)?;
+    lal::verify_report(component_dir, &manifest, env_name, simple)
+}
@@ -0,0 +1,127 @@
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
+    convert::Infallible,
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+type Files = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder().status(code).body(Body::empty()).unwrap()
+}
+
+fn etag(data: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    format!("\"{:x}\"", hasher.finish())
+}
+
+// Just enough WebDAV for `HttpBackend`, with nginx style autoindex listings
+//
+// A status code stored at `<path>#status` is returned for every request to `<path>`,
+// and one stored at `<METHOD>#status` for every request with that method. MOVE honours
+// `Overwrite: F` and an `If` header with the ETag of the destination.
+async fn handle(files: Files, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    let authed = req.headers().contains_key("authorization");
+    if req.method() != Method::GET && !authed {
+        return Ok(status(StatusCode::UNAUTHORIZED));
+    }
+    for key in &[format!("{}#status", path), format!("{}#status", req.method())] {
+        if let Some(code) = files.lock().unwrap().get(key) {
+            return Ok(status(StatusCode::from_bytes(code).unwrap()));
+        }
+    }
+
+    let resp = match req.method().as_str() {
+        "GET" if path.ends_with('/') => {
+            let dirs: BTreeSet<String> = files
+                .lock()
+                .unwrap()
+                .keys()
+                .filter_map(|k| k.strip_prefix(&path))
+                .filter_map(|rest| rest.find('/').map(|i| rest[..i].to_string()))
+                .collect();
+            let links: String = dirs
+                .iter()
+                .map(|d| format!("<a href=\"{0}/\">{0}/</a>\n", d))
+                .collect();
+            Response::new(Body::from(format!(
+                "<html><body><pre>{}</pre></body></html>",
+                links
+            )))
+        }
+        "GET" => match files.lock().unwrap().get(&path) {
+            Some(data) => Response::builder()
+                .header("etag", etag(data))
+                .body(Body::from(data.clone()))
+                .unwrap(),
+            None => status(StatusCode::NOT_FOUND),
+        },
+        "PUT" => {
+            let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            files.lock().unwrap().insert(path, data.to_vec());
+            status(StatusCode::CREATED)
+        }
+        "MKCOL" => status(StatusCode::CREATED),
+        "MOVE" => {
+            let dest = req.headers()["destination"].to_str().unwrap().to_string();
+            let dest = reqwest::Url::parse(&dest).unwrap().path().to_string();
+            let overwrite = req.headers().get("overwrite").is_none_or(|o| o != "F");
+            let expected = req.headers().get("if").map(|c| {
+                let c = c.to_str().unwrap();
+                c[c.find('[').unwrap() + 1..c.rfind(']').unwrap()].to_string()
+            });
+            let mut files = files.lock().unwrap();
+            let current = files.get(&dest).map(|d| etag(d));
+            if (!overwrite && current.is_some()) || expected.is_some_and(|e| Some(e) != current) {
+                return Ok(status(StatusCode::PRECONDITION_FAILED));
+            }
+            match files.remove(&path) {
+                Some(data) => {
+                    files.insert(dest, data);
+                    status(StatusCode::CREATED)
+                }
+                None => status(StatusCode::NOT_FOUND),
+            }
+        }
+        "DELETE" => {
+            files.lock().unwrap().remove(&path);
+            status(StatusCode::NO_CONTENT)
+        }
+        _ => status(StatusCode::METHOD_NOT_ALLOWED),
+    };
+    Ok(resp)
+}
+
+/// Start an in-memory WebDAV server on a free port in the current runtime
+pub fn mock_server() -> (SocketAddr, Files) {
+    let files = Files::default();
+    let state = files.clone();
+    let make_svc = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    });
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (addr, files)
+}
+
+pub fn backend(addr: SocketAddr, cache: &std::path::Path) -> lal::HttpBackend {
+    let cfg = lal::HttpConfig {
+        url: format!("http://{}/lal", addr),
+        credentials: Some(lal::Credentials {
+            username: "lal".into(),
+            password: "secret".into(),
+        }),
+        retry: Default::default(),
+    };
+    lal::HttpBackend::new(&cfg, cache).expect("http backend")
+}